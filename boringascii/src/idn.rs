@@ -0,0 +1,379 @@
+//! Support for internationalized email domains.
+//!
+//! `BoringAscii` refuses anything outside of printable ASCII, which means that an address like
+//! `jürgen@bücher.example` can't be stored directly. But the DNS already has an ASCII-only
+//! encoding for internationalized domain names: each non-ASCII label is punycode-encoded (RFC
+//! 3492) and prefixed with `xn--`. So we convert the domain to that form *before* constructing a
+//! `BoringAscii`, and only decode it back to Unicode for display. The stored (and signed) bytes
+//! are still boring ASCII, so two addresses that look the same are still the same bytes.
+//!
+//! This is deliberately much less than full IDNA (UTS #46). We lowercase non-ASCII labels, and we
+//! refuse combining marks rather than performing Unicode normalization, since silently picking a
+//! normalization form would let two visually identical inputs map to different addresses. The
+//! local part (before the `@`) must already be ASCII.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::BoringAscii;
+
+const ACE_PREFIX: &str = "xn--";
+
+// The maximum length of a DNS label, in bytes.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Convert an email address whose domain may contain non-ASCII labels into its ASCII form, with
+/// each non-ASCII label replaced by its `xn--` punycode encoding. Returns `None` if the local part
+/// isn't printable ASCII, or if any domain label can't be encoded.
+///
+/// Addresses that are already ASCII are returned unchanged.
+pub fn email_to_ascii(email: &str) -> Option<String> {
+    let (local, domain) = email.rsplit_once('@')?;
+    if !local.is_ascii() {
+        return None;
+    }
+    let labels = domain
+        .split('.')
+        .map(label_to_ascii)
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("{}@{}", local, labels.join(".")))
+}
+
+/// The Unicode form of an email address, if its domain contains any `xn--` labels that decode
+/// successfully. Returns `None` if there is nothing to decode, so callers can show the Unicode
+/// form only when it differs from the stored one.
+pub fn email_to_unicode(email: &BoringAscii) -> Option<String> {
+    let (local, domain) = email.as_str().rsplit_once('@')?;
+    let mut decoded_any = false;
+    let labels = domain
+        .split('.')
+        .map(|label| match label_to_unicode(label) {
+            Some(decoded) => {
+                decoded_any = true;
+                decoded
+            }
+            None => label.to_string(),
+        })
+        .collect::<Vec<_>>();
+    if !decoded_any {
+        return None;
+    }
+    Some(format!("{}@{}", local, labels.join(".")))
+}
+
+fn label_to_ascii(label: &str) -> Option<String> {
+    if label.is_ascii() {
+        return Some(label.to_string());
+    }
+    let lowered = label
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if lowered
+        .iter()
+        .any(|c| c.is_control() || c.is_whitespace() || is_combining_mark(*c))
+    {
+        return None;
+    }
+    let result = format!("{}{}", ACE_PREFIX, punycode::encode(&lowered)?);
+    if result.len() > MAX_LABEL_LENGTH {
+        return None;
+    }
+    Some(result)
+}
+
+fn label_to_unicode(label: &str) -> Option<String> {
+    let encoded = label
+        .get(..ACE_PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(ACE_PREFIX))
+        .map(|_| &label[ACE_PREFIX.len()..])?;
+    let decoded = punycode::decode(encoded)?;
+    // Only accept labels that we would have produced ourselves. Otherwise, e.g., an uppercase
+    // encoding could display identically to a different stored address.
+    if label_to_ascii(&decoded)? != label {
+        return None;
+    }
+    Some(decoded)
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F
+    )
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+    Other,
+}
+
+// A coarse script classification, covering the blocks that show up in practice in domain names.
+// Digits, hyphens and other ASCII punctuation don't belong to any script. Anything we don't
+// recognize is lumped into `Other`, which is treated as its own script.
+fn script(c: char) -> Option<Script> {
+    let script = match c as u32 {
+        0x00..=0x7F if c.is_ascii_alphabetic() => Script::Latin,
+        0x00..=0x7F => return None,
+        0x00C0..=0x024F | 0x1E00..=0x1EFF => Script::Latin,
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+        0x0400..=0x052F => Script::Cyrillic,
+        0x0530..=0x058F => Script::Armenian,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0600..=0x06FF | 0x0750..=0x077F => Script::Arabic,
+        0x0900..=0x097F => Script::Devanagari,
+        0x0E00..=0x0E7F => Script::Thai,
+        0x10A0..=0x10FF => Script::Georgian,
+        0x1100..=0x11FF | 0xAC00..=0xD7AF => Script::Hangul,
+        0x3040..=0x309F => Script::Hiragana,
+        0x30A0..=0x30FF => Script::Katakana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF => Script::Han,
+        _ => Script::Other,
+    };
+    Some(script)
+}
+
+/// Whether the given (Unicode) text mixes letters from scripts that don't normally appear
+/// together, like a Cyrillic "а" inside an otherwise Latin domain. This roughly follows the
+/// "highly restrictive" profile of UTS #39: a single script is fine, and so are the combinations
+/// that are used to write Japanese (Latin, Han, Hiragana, Katakana) and Korean (Latin, Han,
+/// Hangul).
+pub fn is_mixed_script(text: &str) -> bool {
+    let mut scripts = text.chars().filter_map(script).collect::<Vec<_>>();
+    scripts.sort();
+    scripts.dedup();
+    if scripts.len() <= 1 {
+        return false;
+    }
+    let japanese = [
+        Script::Latin,
+        Script::Han,
+        Script::Hiragana,
+        Script::Katakana,
+    ];
+    let korean = [Script::Latin, Script::Han, Script::Hangul];
+    !(scripts.iter().all(|s| japanese.contains(s)) || scripts.iter().all(|s| korean.contains(s)))
+}
+
+/// Whether any label of an email address's (Unicode) domain is mixed-script, as in
+/// `is_mixed_script`. Each label is checked on its own, and the local part is ignored, so that
+/// e.g. `ivan@пример.com` isn't flagged just because of the ASCII around the Cyrillic label.
+pub fn has_mixed_script_domain(email: &str) -> bool {
+    let domain = email.rsplit_once('@').map_or(email, |(_, domain)| domain);
+    domain.split('.').any(is_mixed_script)
+}
+
+/// The Bootstring algorithm from RFC 3492, with the parameters it specifies for punycode. This
+/// operates on single labels, without the `xn--` prefix.
+mod punycode {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    const BASE: u32 = 36;
+    const TMIN: u32 = 1;
+    const TMAX: u32 = 26;
+    const SKEW: u32 = 38;
+    const DAMP: u32 = 700;
+    const INITIAL_BIAS: u32 = 72;
+    const INITIAL_N: u32 = 0x80;
+
+    fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+        let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+        delta += delta / num_points;
+        let mut k = 0;
+        while delta > ((BASE - TMIN) * TMAX) / 2 {
+            delta /= BASE - TMIN;
+            k += BASE;
+        }
+        k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+    }
+
+    fn threshold(k: u32, bias: u32) -> u32 {
+        if k <= bias {
+            TMIN
+        } else if k >= bias + TMAX {
+            TMAX
+        } else {
+            k - bias
+        }
+    }
+
+    fn encode_digit(d: u32) -> char {
+        match d {
+            0..=25 => (b'a' + d as u8) as char,
+            _ => (b'0' + (d - 26) as u8) as char,
+        }
+    }
+
+    fn decode_digit(c: u8) -> Option<u32> {
+        match c {
+            b'0'..=b'9' => Some(u32::from(c - b'0') + 26),
+            b'a'..=b'z' => Some(u32::from(c - b'a')),
+            b'A'..=b'Z' => Some(u32::from(c - b'A')),
+            _ => None,
+        }
+    }
+
+    pub(super) fn encode(input: &[char]) -> Option<String> {
+        let mut output = input.iter().filter(|c| c.is_ascii()).collect::<String>();
+        let basic_length = output.len() as u32;
+        let input_length = input.len() as u32;
+        let mut handled = basic_length;
+        if basic_length > 0 {
+            output.push('-');
+        }
+
+        let mut n = INITIAL_N;
+        let mut delta: u32 = 0;
+        let mut bias = INITIAL_BIAS;
+        while handled < input_length {
+            let m = input.iter().map(|&c| c as u32).filter(|&c| c >= n).min()?;
+            delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+            n = m;
+            for &c in input {
+                let c = c as u32;
+                if c < n {
+                    delta = delta.checked_add(1)?;
+                }
+                if c == n {
+                    let mut q = delta;
+                    let mut k = BASE;
+                    loop {
+                        let t = threshold(k, bias);
+                        if q < t {
+                            break;
+                        }
+                        output.push(encode_digit(t + (q - t) % (BASE - t)));
+                        q = (q - t) / (BASE - t);
+                        k += BASE;
+                    }
+                    output.push(encode_digit(q));
+                    bias = adapt(delta, handled + 1, handled == basic_length);
+                    delta = 0;
+                    handled += 1;
+                }
+            }
+            delta = delta.checked_add(1)?;
+            n = n.checked_add(1)?;
+        }
+        Some(output)
+    }
+
+    pub(super) fn decode(input: &str) -> Option<String> {
+        let (basic, extended) = match input.rfind('-') {
+            Some(i) => (&input[..i], &input[i + 1..]),
+            None => ("", input),
+        };
+        if !basic.is_ascii() || !extended.is_ascii() {
+            return None;
+        }
+        let mut output = basic.chars().collect::<Vec<_>>();
+
+        let mut n = INITIAL_N;
+        let mut i: u32 = 0;
+        let mut bias = INITIAL_BIAS;
+        let mut digits = extended.bytes();
+        while digits.len() > 0 {
+            let old_i = i;
+            let mut w: u32 = 1;
+            let mut k = BASE;
+            loop {
+                let digit = decode_digit(digits.next()?)?;
+                i = i.checked_add(digit.checked_mul(w)?)?;
+                let t = threshold(k, bias);
+                if digit < t {
+                    break;
+                }
+                w = w.checked_mul(BASE - t)?;
+                k += BASE;
+            }
+            let length = output.len() as u32 + 1;
+            bias = adapt(i - old_i, length, old_i == 0);
+            n = n.checked_add(i / length)?;
+            i %= length;
+            // Basic code points must appear literally, never encoded.
+            if n < INITIAL_N {
+                return None;
+            }
+            output.insert(i as usize, char::from_u32(n)?);
+            i += 1;
+        }
+        Some(output.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn punycode_round_trips() {
+        for (unicode, encoded) in [
+            ("bücher", "bcher-kva"),
+            ("münchen", "mnchen-3ya"),
+            ("mañana", "maana-pta"),
+            ("правда", "80aafi6cg"),
+            ("テスト", "zckzah"),
+            ("ドメイン名例", "eckwd4c7cu47r2wf"),
+        ] {
+            let chars = unicode.chars().collect::<Vec<_>>();
+            assert_eq!(punycode::encode(&chars).as_deref(), Some(encoded));
+            assert_eq!(punycode::decode(encoded).as_deref(), Some(unicode));
+        }
+        assert!(punycode::decode("bcher-kv!").is_none());
+    }
+
+    #[test]
+    fn idn_emails_work() {
+        let email = BoringAscii::from_idn_email("jurgen@Bücher.example").unwrap();
+        assert_eq!(email.as_str(), "jurgen@xn--bcher-kva.example");
+        assert_eq!(
+            email_to_unicode(&email).as_deref(),
+            Some("jurgen@bücher.example")
+        );
+
+        let plain = BoringAscii::from_idn_email("b@w-r.me").unwrap();
+        assert_eq!(plain.as_str(), "b@w-r.me");
+        assert!(email_to_unicode(&plain).is_none());
+
+        assert!(BoringAscii::from_idn_email("jürgen@example.com").is_none());
+        assert!(BoringAscii::from_idn_email("no-at-sign").is_none());
+        assert!(BoringAscii::from_idn_email("a@bu\u{308}cher.example").is_none());
+        assert!(BoringAscii::from_idn_email("a@b ücher.example").is_none());
+
+        // Non-canonical encodings are shown as-is rather than decoded.
+        let upper = BoringAscii::from_bytes(b"a@xn--BCHER-KVA.example").unwrap();
+        assert!(email_to_unicode(&upper).is_none());
+    }
+
+    #[test]
+    fn mixed_scripts_are_detected() {
+        assert!(!is_mixed_script("example.com"));
+        assert!(!is_mixed_script("bücher.example"));
+        assert!(!is_mixed_script("правда.рф"));
+        assert!(!is_mixed_script("ドメイン名例.jp"));
+        // The first "а" is Cyrillic.
+        assert!(is_mixed_script("pаypal.com"));
+        assert!(is_mixed_script("αβc.com"));
+
+        assert!(!has_mixed_script_domain("ivan@правда.рф"));
+        assert!(!has_mixed_script_domain("ivan@пример.com"));
+        assert!(!has_mixed_script_domain("jurgen@bücher.example"));
+        assert!(has_mixed_script_domain("ivan@pаypal.com"));
+        assert!(has_mixed_script_domain("a@b.αβc.example"));
+    }
+}
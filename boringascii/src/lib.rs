@@ -1,3 +1,13 @@
+// Only alloc is needed, so that this can be used by zebra_crypto without std.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod idn;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -25,7 +35,7 @@ impl BoringAscii {
     }
 
     pub fn as_str(&self) -> &str {
-        std::str::from_utf8(&self.0).expect("BoringAscii was somehow invalid")
+        core::str::from_utf8(&self.0).expect("BoringAscii was somehow invalid")
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
@@ -45,7 +55,7 @@ impl BoringAscii {
 /* BEGIN IMPLS THAT CAN CONSTRUCT A PRINTABLEASCIISTRING */
 // These should all (indirectly) call `from_bytes`.
 
-impl std::str::FromStr for BoringAscii {
+impl core::str::FromStr for BoringAscii {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // from_bytes checks that bytes are inside the printable ASCII range, which excludes
@@ -55,38 +65,59 @@ impl std::str::FromStr for BoringAscii {
 }
 
 impl TryFrom<&str> for BoringAscii {
-    type Error = <BoringAscii as std::str::FromStr>::Err;
+    type Error = <BoringAscii as core::str::FromStr>::Err;
     fn try_from(s: &str) -> Result<BoringAscii, Self::Error> {
-        <BoringAscii as std::str::FromStr>::from_str(s)
+        <BoringAscii as core::str::FromStr>::from_str(s)
     }
 }
 
 // We serialize to a series of bytes, which means that on deserialization we just have to do the
 // exact same range check as we do on from_bytes.
 impl BorshDeserialize for BoringAscii {
-    fn deserialize_reader<R: std::io::Read>(
+    fn deserialize_reader<R: borsh::io::Read>(
         r: &mut R,
-    ) -> Result<BoringAscii, std::io::Error> {
+    ) -> Result<BoringAscii, borsh::io::Error> {
         let bytes = <Vec<u8>>::deserialize_reader(r)?;
-        BoringAscii::from_bytes(&bytes).ok_or(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        BoringAscii::from_bytes(&bytes).ok_or(borsh::io::Error::new(
+            borsh::io::ErrorKind::Other,
             "Unprintable characters when deserializing (supposedly) printable ascii string"
                 .to_string(),
         ))
     }
 }
 
+// Likewise, with serde, we serialize to a string, and deserializing does the same check.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BoringAscii {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<BoringAscii, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(d)?;
+        BoringAscii::from_bytes(s.as_bytes()).ok_or_else(|| {
+            serde::de::Error::custom(
+                "Unprintable characters when deserializing (supposedly) printable ascii string",
+            )
+        })
+    }
+}
+
+impl BoringAscii {
+    /// Construct an email address, converting any internationalized domain labels to their
+    /// ASCII-compatible `xn--` form first. See the `idn` module for details.
+    pub fn from_idn_email(email: &str) -> Option<Self> {
+        Self::from_bytes(idn::email_to_ascii(email)?.as_bytes())
+    }
+}
+
 /* END IMPLEMENTATIONS THAT CAN CONSTRUCT A PRINTABLEASCIISTRING */
 
-impl std::fmt::Display for BoringAscii {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for BoringAscii {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
 // We implement Deref, Borrow, and AsRef, but never DerefMut, BorrowMut, or AsMut, to ensure that
 // users can't change the bytes directly.
-impl std::ops::Deref for BoringAscii {
+impl core::ops::Deref for BoringAscii {
     type Target = str;
     fn deref(&self) -> &Self::Target {
         self.as_str()
@@ -111,24 +142,31 @@ impl AsRef<Vec<u8>> for BoringAscii {
     }
 }
 
-impl std::borrow::Borrow<str> for BoringAscii {
+impl core::borrow::Borrow<str> for BoringAscii {
     fn borrow(&self) -> &str {
         self.as_str()
     }
 }
 
-impl std::borrow::Borrow<[u8]> for BoringAscii {
+impl core::borrow::Borrow<[u8]> for BoringAscii {
     fn borrow(&self) -> &[u8] {
         self.as_bytes()
     }
 }
 
-impl std::borrow::Borrow<Vec<u8>> for BoringAscii {
+impl core::borrow::Borrow<Vec<u8>> for BoringAscii {
     fn borrow(&self) -> &Vec<u8> {
         &self.0
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for BoringAscii {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
 impl From<BoringAscii> for String {
     fn from(s: BoringAscii) -> String {
         String::from_utf8(s.0.clone())
@@ -0,0 +1,517 @@
+use std::io::{Read, Write};
+use std::process::{Command, ExitCode};
+use std::str::FromStr;
+
+use time::format_description::well_known::Rfc3339;
+use zebra_crypto::{PublicKey, SignedMessage, SigningOptions};
+use zebra_storage::{default_db_path, Database};
+use zebra_timestamp::Certificate;
+
+// Git signs and verifies commits and tags by running `gpg.<format>.program` with a few fixed
+// sets of gpg arguments, and then reading gpg's output (see git's gpg-interface.c). We use the
+// x509 format, because git lets its signatures start with "-----BEGIN SIGNED MESSAGE-----", which
+// we can wrap around an ordinary Zebra-signed message:
+//
+// To sign, git runs `<program> --status-fd=2 -bsau <key>`, with the payload (the commit or tag,
+// without a signature) on stdin. It expects the signature on stdout, and a line starting with
+// "[GNUPG:] SIG_CREATED " (after some other line) on the status fd.
+//
+// To verify, git runs `<program> --status-fd=1 --verify <signature file> -`, with the payload on
+// stdin. It decides whether the signature is good from the "[GNUPG:]" lines on the status fd, and
+// shows whatever we write to stderr to the user (as in `git log --show-signature`).
+//
+// The signed message's text is the whole payload, and its context is always "git", so that a
+// commit signature can't be passed off as an ordinary Zebra-signed message, or vice versa. Since
+// git asks for a detached signature, and keeps the payload itself, the signature we write out has
+// its text left empty, and we put the payload back before verifying it.
+
+const CONTEXT: &str = "git";
+
+const BEGIN: &str = "-----BEGIN SIGNED MESSAGE-----";
+const END: &str = "-----END SIGNED MESSAGE-----";
+
+/// The ring log topic for commit and tag signatures.
+const TOPIC: &str = "git";
+
+/// The git config key listing the other members of the ring: each value is a fingerprint, email
+/// address, or "Name <email>" of a public key in the Zebra database.
+const RING_CONFIG_KEY: &str = "zebra.ring";
+
+/// The git config key with the URL of an RFC 3161 timestamping authority. If it's set, signatures
+/// are timestamped by it.
+const TSA_CONFIG_KEY: &str = "zebra.tsa";
+
+/// The git config key listing the paths of the (PEM or DER) certificates of the timestamping
+/// authorities whose timestamps we trust.
+const TSA_CERTIFICATE_CONFIG_KEY: &str = "zebra.tsaCertificate";
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum Invocation {
+    Sign {
+        status_fd: u32,
+        key: String,
+    },
+    Verify {
+        status_fd: u32,
+        signature_path: String,
+    },
+}
+
+impl Invocation {
+    /// Parse the arguments (not including the program name) that git passes to gpg. Returns None
+    /// if they aren't a form that git uses, or if the status fd isn't stdout or stderr (which is
+    /// all git ever asks for), so that we never sign anything without being able to say so.
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let mut status_fd = None;
+        let mut rest = vec![];
+        for arg in args {
+            if let Some(fd) = arg.strip_prefix("--status-fd=") {
+                status_fd = Some(fd.parse().ok()?);
+            } else if !arg.starts_with("--keyid-format=") {
+                rest.push(arg.as_str());
+            }
+        }
+        match (status_fd.filter(|fd| matches!(fd, 1 | 2))?, rest.as_slice()) {
+            (status_fd, ["-bsau", key]) => Some(Self::Sign {
+                status_fd,
+                key: key.to_string(),
+            }),
+            (status_fd, ["--verify", signature_path, "-"]) => Some(Self::Verify {
+                status_fd,
+                signature_path: signature_path.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn run(self) -> ExitCode {
+        let (status_fd, result) = match self {
+            Self::Sign { status_fd, key } => (status_fd, sign(&key)),
+            Self::Verify {
+                status_fd,
+                signature_path,
+            } => (status_fd, verify(&signature_path)),
+        };
+        match result {
+            Ok(Outcome { status, good }) => {
+                if write_status(status_fd, &status).is_err() {
+                    eprintln!("Couldn't write to status fd {status_fd}");
+                    return ExitCode::FAILURE;
+                }
+                if good {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                }
+            }
+            Err(e) => {
+                eprintln!("zebra: {e}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+struct Outcome {
+    /// The "[GNUPG:]" status lines.
+    status: String,
+    good: bool,
+}
+
+fn write_status(fd: u32, status: &str) -> std::io::Result<()> {
+    match fd {
+        1 => std::io::stdout().write_all(status.as_bytes()),
+        2 => std::io::stderr().write_all(status.as_bytes()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Only status fds 1 and 2 are supported",
+        )),
+    }
+}
+
+fn error(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, message)
+}
+
+fn read_payload() -> std::io::Result<String> {
+    let mut payload = vec![];
+    std::io::stdin().read_to_end(&mut payload)?;
+    String::from_utf8(payload).map_err(|_| error("Zebra can only sign UTF-8 text".to_string()))
+}
+
+fn describe(key: &PublicKey) -> String {
+    format!("{} <{}>", key.holder().name(), key.holder().email())
+}
+
+/// Whether `spec` (from git) refers to `key`: it may be the key's fingerprint (with or without
+/// spaces), its email address, or "Name <email>", which is what git passes when
+/// `user.signingkey` isn't set.
+fn matches(key: &PublicKey, spec: &str) -> bool {
+    let spec = spec.trim();
+    let fingerprint = key.fingerprint();
+    spec == fingerprint
+        || spec.replace(' ', "") == fingerprint.replace(' ', "")
+        || spec == key.holder().email()
+        || spec == describe(key)
+}
+
+/// The one key in `keys` that `spec` refers to.
+fn find_key<'a>(
+    keys: impl Iterator<Item = &'a PublicKey>,
+    spec: &str,
+) -> std::io::Result<&'a PublicKey> {
+    let mut found = keys.filter(|k| matches(k, spec));
+    match (found.next(), found.next()) {
+        (Some(key), None) => Ok(key),
+        (None, _) => Err(error(format!(
+            "No key in the Zebra database matches {spec:?}"
+        ))),
+        (Some(_), Some(_)) => Err(error(format!(
+            "More than one key in the Zebra database matches {spec:?}; use its fingerprint"
+        ))),
+    }
+}
+
+fn config_values(key: &str) -> std::io::Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["config", "--get-all", key])
+        .output()?;
+    // git config exits with 1 if the key isn't set, which (for the ring) just means the ring is
+    // only us.
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(error(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn sign(key: &str) -> std::io::Result<Outcome> {
+    let payload = read_payload()?;
+    let mut db = Database::new(default_db_path())?;
+    let contents = &db.visible_contents;
+    let my_key = find_key(contents.my_public_keys.iter(), key)?.clone();
+    let other_keys = config_values(RING_CONFIG_KEY)?
+        .iter()
+        .map(|spec| {
+            find_key(
+                contents
+                    .my_public_keys
+                    .iter()
+                    .chain(contents.their_public_keys.keys()),
+                spec,
+            )
+            .cloned()
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let options = SigningOptions {
+        context: Some(CONTEXT.to_string()),
+        hedged: true,
+        ..SigningOptions::default()
+    };
+    let mut signed = db.sign(&payload, &options, TOPIC, &my_key, &other_keys)?;
+    if let Some(tsa_url) = config_values(TSA_CONFIG_KEY)?.last() {
+        signed = zebra_timestamp::request_timestamp(&signed, tsa_url)
+            .map_err(|e| error(format!("Couldn't timestamp the signature: {e}")))?;
+    }
+
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{}", detached_signature(&signed))?;
+    stdout.flush()?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    Ok(Outcome {
+        status: signed_status(&my_key, now),
+        good: true,
+    })
+}
+
+/// The armored signature that git stores for `signed`, without the payload that it signs.
+fn detached_signature(signed: &SignedMessage) -> String {
+    let mut detached = signed.clone();
+    detached.message = String::new();
+    format!("{BEGIN}\n{}\n{END}\n", String::from(&detached))
+}
+
+/// Parse an armored signature from `detached_signature`, putting back the `payload` that it signs.
+fn attach_payload(armored: &str, payload: String) -> Option<SignedMessage> {
+    let mut signed = armored
+        .trim()
+        .strip_prefix(BEGIN)
+        .and_then(|s| s.strip_suffix(END))
+        .and_then(|s| SignedMessage::from_str(s.trim()).ok())?;
+    signed.message = payload;
+    Some(signed)
+}
+
+/// The status lines for a signature made with `key` at `time` (in seconds since the Unix epoch).
+fn signed_status(key: &PublicKey, time: u64) -> String {
+    // git looks for SIG_CREATED after a newline, so it can't be the first line.
+    format!(
+        "[GNUPG:] BEGIN_SIGNING\n[GNUPG:] SIG_CREATED D 0 0 00 {time} {}\n",
+        key.fingerprint().replace(' ', "")
+    )
+}
+
+/// The status lines for a good or bad signature by `ring`. A good signature is fully trusted only
+/// if we've verified every member of the ring.
+fn verified_status(ring: &[&PublicKey], good: bool, all_verified: bool) -> String {
+    // gpg would give a key ID and the signer's user ID. We have neither, so we make do with the
+    // ring's size, and all of its members.
+    let key_id = format!("{}-KEY-RING", ring.len());
+    let signer = format!(
+        "One of: {}",
+        ring.iter()
+            .map(|k| describe(k))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !good {
+        return format!("[GNUPG:] NEWSIG\n[GNUPG:] BADSIG {key_id} {signer}\n");
+    }
+    let trust = if all_verified {
+        "TRUST_FULLY"
+    } else {
+        "TRUST_UNDEFINED"
+    };
+    format!("[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG {key_id} {signer}\n[GNUPG:] {trust} 0 pgp\n")
+}
+
+/// The certificates of the timestamping authorities in `TSA_CERTIFICATE_CONFIG_KEY`.
+fn trusted_tsas() -> std::io::Result<Vec<Certificate>> {
+    config_values(TSA_CERTIFICATE_CONFIG_KEY)?
+        .iter()
+        .map(|path| {
+            let bytes = std::fs::read(path)?;
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|pem| Certificate::from_str(pem).ok())
+                .or_else(|| Certificate::from_der(&bytes))
+                .ok_or_else(|| error(format!("{path} isn't a supported TSA certificate")))
+        })
+        .collect()
+}
+
+fn verify(signature_path: &str) -> std::io::Result<Outcome> {
+    let payload = read_payload()?;
+    let armored = std::fs::read_to_string(signature_path)?;
+    let signed = attach_payload(&armored, payload)
+        .ok_or_else(|| error("This isn't a Zebra signature".to_string()))?;
+
+    let ring = signed.ring().collect::<Vec<_>>();
+
+    if !signed.verify_with_context(Some(CONTEXT)) {
+        eprintln!("BAD Zebra ring signature, claiming to be by one of:");
+        for k in ring.iter() {
+            eprintln!("    {} {}", describe(k), k.fingerprint());
+        }
+        return Ok(Outcome {
+            status: verified_status(&ring, false, false),
+            good: false,
+        });
+    }
+
+    // The signature is good whether or not we know the ring members' keys, but it only means
+    // much if we've verified that each of them belongs to who it says. Git trusts signatures at
+    // any level by default, but can be told to require more with `gpg.minTrustLevel`. (The
+    // database may be unavailable, e.g. if the Zebra app has it open, in which case we don't know
+    // about any of the keys.)
+    let db = Database::new(default_db_path()).ok();
+    let known = |k: &PublicKey| {
+        db.as_ref().map_or(false, |db| {
+            let contents = &db.visible_contents;
+            contents.my_public_keys.contains(k)
+                || contents
+                    .their_public_keys
+                    .get(k)
+                    .map_or(false, |info| info.is_verified())
+        })
+    };
+
+    eprintln!("Good Zebra ring signature, by one of:");
+    for k in ring.iter() {
+        let note = if known(k) { "verified" } else { "not verified" };
+        eprintln!("    {} {} ({note})", describe(k), k.fingerprint());
+    }
+    // A bad or untrusted timestamp doesn't make the signature any less good; it just doesn't say
+    // when it was made.
+    if signed.timestamp_token().is_some() {
+        match trusted_tsas().and_then(|trusted| {
+            zebra_timestamp::verify(&signed, &trusted).map_err(|e| error(e.to_string()))
+        }) {
+            Ok(timestamp) => eprintln!(
+                "Timestamped {} by {}",
+                timestamp
+                    .time()
+                    .format(&Rfc3339)
+                    .map_err(|e| error(e.to_string()))?,
+                timestamp.authority()
+            ),
+            Err(e) => eprintln!("Timestamp not accepted: {e}"),
+        }
+    }
+    Ok(Outcome {
+        status: verified_status(&ring, true, ring.iter().all(|k| known(k))),
+        good: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_crypto::{Identity, PrivateKey};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn key(name: &str, email: &str) -> PublicKey {
+        PrivateKey::new(Identity::new(name, email).unwrap()).public()
+    }
+
+    #[test]
+    fn detached_signatures_verify_against_their_payload() {
+        let payload = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+                       author Alice <alice@example.com> 1700000000 +0000\n\
+                       committer Alice <alice@example.com> 1700000000 +0000\n\
+                       \n\
+                       Initial commit\n";
+        let alice = PrivateKey::new(Identity::new("Alice", "alice@example.com").unwrap());
+        let bob = key("Bob", "bob@example.com");
+        let options = SigningOptions {
+            context: Some(CONTEXT.to_string()),
+            ..SigningOptions::default()
+        };
+        let signed = SignedMessage::sign_with_options(payload, &alice, &[bob], &options).unwrap();
+
+        let armored = detached_signature(&signed);
+        assert!(armored.starts_with(BEGIN));
+        assert!(!armored.contains("Initial commit"));
+
+        let verified = attach_payload(&armored, payload.to_string()).unwrap();
+        assert!(verified.verify_with_context(Some(CONTEXT)));
+        assert!(!verified.verify_with_context(None));
+
+        let tampered = payload.replace("Initial", "Final");
+        assert!(!attach_payload(&armored, tampered)
+            .unwrap()
+            .verify_with_context(Some(CONTEXT)));
+        assert!(attach_payload("Not a signature", payload.to_string()).is_none());
+    }
+
+    #[test]
+    fn gpg_arguments_are_parsed() {
+        assert_eq!(
+            Invocation::parse(&args(&[
+                "--status-fd=2",
+                "-bsau",
+                "Alice <alice@example.com>"
+            ])),
+            Some(Invocation::Sign {
+                status_fd: 2,
+                key: "Alice <alice@example.com>".to_string()
+            })
+        );
+        assert_eq!(
+            Invocation::parse(&args(&[
+                "--keyid-format=long",
+                "--status-fd=1",
+                "--verify",
+                "/tmp/.git_vtag_tmpXXXXXX",
+                "-"
+            ])),
+            Some(Invocation::Verify {
+                status_fd: 1,
+                signature_path: "/tmp/.git_vtag_tmpXXXXXX".to_string()
+            })
+        );
+
+        assert_eq!(
+            Invocation::parse(&args(&["-bsau", "alice@example.com"])),
+            None
+        );
+        assert_eq!(
+            Invocation::parse(&args(&["--status-fd=3", "-bsau", "alice@example.com"])),
+            None
+        );
+        assert_eq!(
+            Invocation::parse(&args(&["--status-fd=x", "-bsau", "alice@example.com"])),
+            None
+        );
+        assert_eq!(
+            Invocation::parse(&args(&["--status-fd=1", "--verify", "sig"])),
+            None
+        );
+        assert_eq!(
+            Invocation::parse(&args(&["--status-fd=2", "-bsau", "a", "b"])),
+            None
+        );
+    }
+
+    #[test]
+    fn keys_are_found_by_fingerprint_email_or_name() {
+        let alice = key("Alice", "alice@example.com");
+        let other_alice = key("Alice", "alice@example.com");
+        let bob = key("Bob", "bob@example.com");
+        let keys = [alice.clone(), bob.clone()];
+
+        let fingerprint = alice.fingerprint();
+        for spec in [
+            fingerprint.clone(),
+            fingerprint.replace(' ', ""),
+            "alice@example.com".to_string(),
+            " Alice <alice@example.com>\n".to_string(),
+        ] {
+            assert!(matches(&alice, &spec), "{spec:?}");
+            assert!(find_key(keys.iter(), &spec).unwrap() == &alice, "{spec:?}");
+        }
+        assert!(!matches(&alice, "Alice"));
+        assert!(!matches(&alice, "bob@example.com"));
+        assert!(find_key(keys.iter(), "carol@example.com").is_err());
+
+        // The same email on two keys is ambiguous, but their fingerprints aren't.
+        let keys = [alice.clone(), other_alice.clone(), bob];
+        assert!(find_key(keys.iter(), "alice@example.com").is_err());
+        assert!(find_key(keys.iter(), &other_alice.fingerprint()).unwrap() == &other_alice);
+    }
+
+    #[test]
+    fn status_lines_are_what_git_expects() {
+        let alice = key("Alice", "alice@example.com");
+        let bob = key("Bob", "bob@example.com");
+
+        assert_eq!(
+            signed_status(&alice, 1700000000),
+            format!(
+                "[GNUPG:] BEGIN_SIGNING\n[GNUPG:] SIG_CREATED D 0 0 00 1700000000 {}\n",
+                alice.fingerprint().replace(' ', "")
+            )
+        );
+
+        let ring = [&alice, &bob];
+        let signer = "One of: Alice <alice@example.com>, Bob <bob@example.com>";
+        assert_eq!(
+            verified_status(&ring, false, true),
+            format!("[GNUPG:] NEWSIG\n[GNUPG:] BADSIG 2-KEY-RING {signer}\n")
+        );
+        assert_eq!(
+            verified_status(&ring, true, true),
+            format!(
+                "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 2-KEY-RING {signer}\n[GNUPG:] TRUST_FULLY 0 pgp\n"
+            )
+        );
+        assert_eq!(
+            verified_status(&ring, true, false),
+            format!(
+                "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 2-KEY-RING {signer}\n[GNUPG:] TRUST_UNDEFINED 0 pgp\n"
+            )
+        );
+    }
+}
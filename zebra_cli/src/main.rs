@@ -1,3 +1,33 @@
-fn main() {
-    println!("Hello, world!");
+use std::process::ExitCode;
+
+mod git;
+
+const USAGE: &str = "\
+zebra_cli signs git commits and tags with Zebra ring signatures, by pretending to be gpgsm. To
+use it, set up git like this (keys can be given by fingerprint, email, or \"Name <email>\"):
+
+    git config gpg.format x509
+    git config gpg.x509.program zebra_cli
+    git config user.signingkey <one of your keys>
+    git config --add zebra.ring <another ring member's key>
+    git config --add zebra.ring <yet another ring member's key>
+
+and then sign with `git commit -S` or `git tag -s`, and verify with `git log --show-signature`,
+`git verify-commit`, or `git verify-tag`. All of the keys must be in the Zebra database.
+
+To have signatures timestamped by an RFC 3161 timestamping authority, and to trust its timestamps
+when verifying, add:
+
+    git config zebra.tsa http://<the authority's timestamping URL>
+    git config --add zebra.tsaCertificate <path to the authority's certificate>";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match git::Invocation::parse(&args) {
+        Some(invocation) => invocation.run(),
+        None => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
 }
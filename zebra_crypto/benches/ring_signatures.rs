@@ -0,0 +1,98 @@
+//! Compares the SAG and one-of-many ring signature schemes, for signing and verifying messages
+//! with rings of various sizes, and compares batch verification with verifying messages one at a
+//! time. Run with `cargo bench -p zebra_crypto`.
+
+use std::str::FromStr;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use zebra_crypto::{Identity, PrivateKey, PublicKey, RingScheme, SignedMessage, SigningOptions};
+
+const RING_SIZES: &[usize] = &[4, 16, 64, 256, 1024];
+
+fn keys(count: usize) -> (PrivateKey, Vec<PublicKey>) {
+    let mut keys = (0..count)
+        .map(|i| {
+            PrivateKey::new(Identity::new(&format!("Member {i}"), "member@example.com").unwrap())
+        })
+        .collect::<Vec<_>>();
+    let my_key = keys.pop().unwrap();
+    (my_key, keys.iter().map(|k| k.public()).collect())
+}
+
+fn options(scheme: RingScheme) -> SigningOptions {
+    SigningOptions {
+        scheme,
+        ..SigningOptions::default()
+    }
+}
+
+fn bench_schemes(c: &mut Criterion) {
+    let mut sign = c.benchmark_group("sign");
+    sign.sample_size(10);
+    for &size in RING_SIZES {
+        let (my_key, others) = keys(size);
+        for &scheme in RingScheme::ALL {
+            sign.bench_with_input(BenchmarkId::new(scheme.name(), size), &size, |b, _| {
+                b.iter(|| {
+                    SignedMessage::sign_with_options("message", &my_key, &others, &options(scheme))
+                })
+            });
+        }
+    }
+    sign.finish();
+
+    let mut verify = c.benchmark_group("verify");
+    verify.sample_size(10);
+    for &size in RING_SIZES {
+        let (my_key, others) = keys(size);
+        for &scheme in RingScheme::ALL {
+            let signed =
+                SignedMessage::sign_with_options("message", &my_key, &others, &options(scheme))
+                    .unwrap();
+            let armored = String::from(&signed);
+            // Criterion only measures time, so report the size of each signature alongside it.
+            println!(
+                "{} signature with a ring of {size}: {} bytes of ASCII",
+                scheme.name(),
+                armored.len()
+            );
+            verify.bench_with_input(BenchmarkId::new(scheme.name(), size), &size, |b, _| {
+                b.iter(|| SignedMessage::from_str(&armored).unwrap().verify())
+            });
+        }
+    }
+    verify.finish();
+}
+
+fn bench_batches(c: &mut Criterion) {
+    // An archive-like workload: many messages, signed by members of the same group.
+    let (my_key, others) = keys(32);
+    let mut batch = c.benchmark_group("verify_batch");
+    batch.sample_size(10);
+    for &scheme in RingScheme::ALL {
+        let messages = (0..64)
+            .map(|i| {
+                SignedMessage::sign_with_options(
+                    &format!("message {i}"),
+                    &my_key,
+                    &others,
+                    &options(scheme),
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        batch.bench_function(BenchmarkId::new("one_at_a_time", scheme.name()), |b| {
+            b.iter(|| messages.iter().all(|m| m.verify()))
+        });
+        batch.bench_function(BenchmarkId::new("batch", scheme.name()), |b| {
+            b.iter(|| SignedMessage::verify_batch(&messages, false))
+        });
+        batch.bench_function(BenchmarkId::new("parallel_batch", scheme.name()), |b| {
+            b.iter(|| SignedMessage::verify_batch(&messages, true))
+        });
+    }
+    batch.finish();
+}
+
+criterion_group!(benches, bench_schemes, bench_batches);
+criterion_main!(benches);
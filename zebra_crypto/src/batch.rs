@@ -0,0 +1,134 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::one_of_many::VerificationTerms;
+use crate::{PublicKey, SignedMessage};
+
+// How batch verification works:
+//
+// Verifying a message means checking every ring member's attestation, and then the ring signature
+// itself. In an archive, the same keys show up in ring after ring, so the first (and often
+// biggest) saving is to check each distinct key's attestation only once.
+//
+// SAG signatures can't be combined: each step's challenge is a hash of the previous step, so they
+// have to be checked one at a time (though each step is a single double-scalar multiplication; see
+// `hash_ring_step_vartime`). One-of-many proofs, on the other hand, reduce to "these points sum to
+// the identity", so all of the ones in a batch are checked with a single multiscalar
+// multiplication. If that fails, we fall back to checking them one by one, to find the bad ones.
+//
+// Both stages split their work into one contiguous chunk per thread. Without std, there are no
+// threads, so everything happens on the calling thread.
+
+impl SignedMessage {
+    /// Verify many messages at once. This gives the same results as calling `verify` on each one,
+    /// but is much faster when the messages share ring members, or use the one-of-many scheme. If
+    /// `parallel` is true (and the `std` feature is on), the work is spread across all available
+    /// cores.
+    ///
+    /// Returns the indices (in increasing order) of the messages that don't verify, if there are
+    /// any.
+    pub fn verify_batch(messages: &[SignedMessage], parallel: bool) -> Result<(), Vec<usize>> {
+        let threads = if parallel { available_threads() } else { 1 };
+
+        // 1. Check each distinct key's attestation, once.
+        let keys = messages
+            .iter()
+            .flat_map(|m| m.ring())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let invalid_keys = failures(&keys, threads, |chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, k)| !k.validate_attestation())
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .into_iter()
+        .map(|i| keys[i])
+        .collect::<Vec<&PublicKey>>();
+
+        // 2. Check the signatures of the messages whose rings are all valid.
+        let bad = failures(messages, threads, |chunk| {
+            let mut bad = vec![];
+            let mut batched = vec![];
+            let mut terms = VerificationTerms::default();
+            for (i, m) in chunk.iter().enumerate() {
+                if m.ring().any(|k| invalid_keys.contains(&k)) {
+                    bad.push(i);
+                } else if let Some(t) = m.batchable_terms() {
+                    terms.extend(t);
+                    batched.push(i);
+                } else if !m.verify_proof() {
+                    bad.push(i);
+                }
+            }
+            if !batched.is_empty() && !terms.sum_to_identity() {
+                bad.extend(batched.into_iter().filter(|&i| !chunk[i].verify_proof()));
+                bad.sort_unstable();
+            }
+            bad
+        });
+
+        if bad.is_empty() {
+            Ok(())
+        } else {
+            Err(bad)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(not(feature = "std"))]
+fn available_threads() -> usize {
+    1
+}
+
+/// Split `items` into one chunk per thread, and run `check` on each chunk, which returns the
+/// indices (within the chunk) of the items that failed. Returns the indices (within `items`) of
+/// all the failures, in increasing order.
+#[cfg(feature = "std")]
+fn failures<T: Sync>(
+    items: &[T],
+    threads: usize,
+    check: impl Fn(&[T]) -> Vec<usize> + Sync,
+) -> Vec<usize> {
+    if threads <= 1 || items.len() <= 1 {
+        return check(items);
+    }
+    let chunk_size = (items.len() - 1) / threads + 1;
+    let check = &check;
+    std::thread::scope(|scope| {
+        let handles = items
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(n, chunk)| {
+                scope.spawn(move || {
+                    check(chunk)
+                        .into_iter()
+                        .map(|i| n * chunk_size + i)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("Verification thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn failures<T: Sync>(
+    items: &[T],
+    _threads: usize,
+    check: impl Fn(&[T]) -> Vec<usize> + Sync,
+) -> Vec<usize> {
+    check(items)
+}
@@ -0,0 +1,207 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha3::{Digest, Sha3_256};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::ristretto::{RistrettoPoint, Scalar};
+use crate::{
+    append_transcript_field, key_line, z85, Canonicalization, PrivateKey, PublicKey, RingScheme,
+    Signature, SignedMessage, Transcript,
+};
+
+pub(crate) const CLAIM_TRANSCRIPT_DOMAIN: &[u8] = b"Zebra authorship claim v1";
+const CLAIM_TOKEN_DOMAIN: &[u8] = b"Zebra claim token v1";
+const CLAIM_COMMITMENT_DOMAIN: &[u8] = b"Zebra claim commitment v1";
+
+// How claims work:
+//
+// When signing a claimable message, the signer derives a secret token t from their private key and
+// everything else being signed (so that signing the same message with a different ring produces an
+// unrelated token, and the two signatures can't be linked). The signed message then includes the
+// commitment H(t || K), where K is the signer's public key, and the ring signature covers that
+// commitment.
+//
+// To claim the message later, the signer reveals t, and signs it (along with the commitment) with
+// their private key alone. Anyone can then check that H(t || K) matches the commitment. Since t is
+// derived from the private key, nobody else in the ring knows it before the claim is published, and
+// once it's published, it's useless to them: the commitment binds it to K, so it would only ever
+// let someone claim the message on K's behalf, which requires K's private key anyway.
+
+/// Derive the secret claim token for a message, whose text must already be canonicalized. `ring`
+/// must be the full ring, in signing order.
+pub(crate) fn derive_token<'a>(
+    key: &Scalar,
+    message: &[u8],
+    context: Option<&str>,
+    headers: &[(String, String)],
+    scheme: RingScheme,
+    canonicalization: Canonicalization,
+    ring: impl Iterator<Item = &'a RistrettoPoint>,
+) -> [u8; 32] {
+    let mut hash = Sha3_256::new();
+    append_transcript_field(&mut hash, b"domain", CLAIM_TOKEN_DOMAIN);
+    append_transcript_field(&mut hash, b"private-key", key.as_bytes());
+    if let Some(context) = context {
+        append_transcript_field(&mut hash, b"context", context.as_bytes());
+    }
+    for (name, value) in headers {
+        append_transcript_field(&mut hash, b"header-name", name.as_bytes());
+        append_transcript_field(&mut hash, b"header-value", value.as_bytes());
+    }
+    if scheme != RingScheme::Sag {
+        append_transcript_field(&mut hash, b"ring-scheme", scheme.name().as_bytes());
+    }
+    if canonicalization != Canonicalization::Exact {
+        append_transcript_field(
+            &mut hash,
+            b"canonicalization",
+            canonicalization.name().as_bytes(),
+        );
+    }
+    append_transcript_field(&mut hash, b"message", message);
+    for keypoint in ring {
+        append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+    }
+    hash.finalize().into()
+}
+
+pub(crate) fn commitment(token: &[u8; 32], keypoint: &RistrettoPoint) -> [u8; 32] {
+    let mut hash = Sha3_256::new();
+    append_transcript_field(&mut hash, b"domain", CLAIM_COMMITMENT_DOMAIN);
+    append_transcript_field(&mut hash, b"claim-token", token);
+    append_transcript_field(&mut hash, b"claimant", &keypoint.compress());
+    hash.finalize().into()
+}
+
+/// Proof that the holder of a particular key signed an earlier, claimable SignedMessage. Anyone can
+/// check it against the message; nobody but the actual signer can produce one.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct AuthorshipClaim {
+    claimant: PublicKey,
+    token: [u8; 32],
+    // A signature of the commitment and token, with a ring of just the claimant's key.
+    signature: Signature,
+}
+
+impl AuthorshipClaim {
+    /// Claim authorship of `signed`. Returns None unless the message is claimable, and was signed
+    /// with `my_key`.
+    #[cfg(feature = "std")]
+    pub fn new(signed: &SignedMessage, my_key: &PrivateKey) -> Option<Self> {
+        Self::new_with_rng(signed, my_key, &mut OsRng)
+    }
+
+    /// Like `new`, but with randomness from `rng` rather than from the operating system.
+    pub fn new_with_rng(
+        signed: &SignedMessage,
+        my_key: &PrivateKey,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<Self> {
+        let expected = signed.claim_commitment.as_ref()?;
+        let claimant = my_key.public();
+        let token = derive_token(
+            &my_key.key,
+            signed.signed_text().as_bytes(),
+            signed.context(),
+            signed.headers(),
+            signed.scheme(),
+            signed.canonicalization(),
+            signed.ring().map(|k| &k.keypoint),
+        );
+        if &commitment(&token, &claimant.keypoint) != expected {
+            return None;
+        }
+        let signature = Signature::sign_transcript(
+            Transcript::Claim {
+                commitment: expected,
+                token: &token,
+            },
+            my_key.key.clone(),
+            &[],
+            rng,
+        );
+        Some(AuthorshipClaim {
+            claimant,
+            token,
+            signature,
+        })
+    }
+
+    /// The key whose holder is claiming authorship.
+    pub fn claimant(&self) -> &PublicKey {
+        &self.claimant
+    }
+
+    /// Check that this is a valid claim of `signed`, which must itself verify. If so, the holder
+    /// of `claimant()` signed the message.
+    pub fn verify(&self, signed: &SignedMessage) -> bool {
+        let expected = match signed.claim_commitment {
+            Some(ref c) => c,
+            None => return false,
+        };
+        // The signature must be by the claimant's key alone.
+        self.signature.ring_responses.len() == 1
+            && self.signature.ring_responses[0].0 == self.claimant.keypoint
+            && self.claimant.validate_attestation()
+            && signed.ring().any(|k| k == &self.claimant)
+            && &commitment(&self.token, &self.claimant.keypoint) == expected
+            && self.signature.verify_transcript(Transcript::Claim {
+                commitment: expected,
+                token: &self.token,
+            })
+            && signed.verify()
+    }
+}
+
+// The ASCII format of a claim is four lines:
+//
+// (0) A fixed first line
+// (1) The claimant's name, email, and fingerprint, as in the ring of a signed message
+// (2) The claim itself (z85-encoded)
+// (3) A fixed last line
+//
+// The second line is redundant, but lets a human see who's claiming what, and the parser checks
+// that it matches the encoded claim.
+
+const CLAIM_FIRST_LINE: &str = "The holder of this key claims to have signed a Zebra message:";
+const CLAIM_LAST_LINE: &str =
+    "To check this claim, paste it into the Zebra app while verifying the message it refers to.";
+
+impl From<&AuthorshipClaim> for String {
+    fn from(c: &AuthorshipClaim) -> String {
+        let mut claim_bytes = vec![];
+        c.serialize(&mut claim_bytes)
+            .expect("Failed to serialize claim into unbounded buffer");
+        [
+            CLAIM_FIRST_LINE.to_string(),
+            key_line(&c.claimant),
+            z85::encode(&claim_bytes),
+            CLAIM_LAST_LINE.to_string(),
+        ]
+        .join("\n")
+    }
+}
+
+impl FromStr for AuthorshipClaim {
+    type Err = ();
+    /// As with SignedMessage, success does *not* imply a valid claim.
+    fn from_str(s: &str) -> Result<AuthorshipClaim, ()> {
+        let lines = s.trim().split('\n').collect::<Vec<_>>();
+        if lines.len() != 4 || lines[0] != CLAIM_FIRST_LINE || lines[3] != CLAIM_LAST_LINE {
+            return Err(());
+        }
+        let claim_bytes = z85::decode(lines[2]).ok_or(())?;
+        let claim = AuthorshipClaim::try_from_slice(&claim_bytes).map_err(|_| ())?;
+        if lines[1] != key_line(&claim.claimant) {
+            return Err(());
+        }
+        Ok(claim)
+    }
+}
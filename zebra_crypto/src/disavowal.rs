@@ -0,0 +1,167 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::ristretto::RistrettoPoint;
+use crate::{
+    append_transcript_field, key_line, z85, PrivateKey, PublicKey, Signature, SignedMessage,
+    Transcript,
+};
+
+pub(crate) const DISAVOWAL_TRANSCRIPT_DOMAIN: &[u8] = b"Zebra disavowal proof v1";
+const DISAVOWAL_BASE_DOMAIN: &[u8] = b"Zebra disavowal base point v1";
+
+// How disavowals work:
+//
+// A disavowable message's signature is tagged with the signer's key image I = k H, where H is a
+// base point derived from the message (see `base_point`). The tagged ring signature proves that I
+// was computed with the private key of *some* ring member, without saying which.
+//
+// Any other ring member j can then publish their own key image I_j = k_j H, along with a proof that
+// it was computed with the private key for K_j. That proof is just a tagged signature with a ring of
+// one: it shows that log_G(K_j) = log_H(I_j). Since I_j != I, key j didn't sign the message.
+//
+// Because H is different for every message, key images can't be used to link two messages signed
+// with the same key. But note that anyone who knows one of the ring's private keys can check
+// whether that key signed the message, so a ring member who *didn't* disavow might look suspicious.
+
+/// Derive the base point for key images from a transcript that doesn't include the key image
+/// itself, and the ring.
+pub(crate) fn base_point<'a>(
+    transcript: Transcript,
+    ring: impl Iterator<Item = &'a RistrettoPoint>,
+) -> RistrettoPoint {
+    let mut hash = transcript.hash_with_ring(ring);
+    append_transcript_field(&mut hash, b"purpose", DISAVOWAL_BASE_DOMAIN);
+    RistrettoPoint::from_hash(hash)
+}
+
+/// Proof that the holder of a particular key, which is in the ring of a disavowable
+/// SignedMessage, did *not* sign that message.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct DisavowalProof {
+    disavower: PublicKey,
+    key_image: RistrettoPoint,
+    // A signature with a ring of just the disavower's key, tagged with `key_image`.
+    proof: Signature,
+}
+
+impl DisavowalProof {
+    /// Prove that `my_key` didn't sign `signed`. Returns None unless the message is disavowable,
+    /// and `my_key` is in its ring but isn't the key that signed it.
+    #[cfg(feature = "std")]
+    pub fn new(signed: &SignedMessage, my_key: &PrivateKey) -> Option<Self> {
+        Self::new_with_rng(signed, my_key, &mut OsRng)
+    }
+
+    /// Like `new`, but with randomness from `rng` rather than from the operating system.
+    pub fn new_with_rng(
+        signed: &SignedMessage,
+        my_key: &PrivateKey,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<Self> {
+        let signer_image = signed.key_image.as_ref()?;
+        let disavower = my_key.public();
+        if !signed.ring().any(|k| k == &disavower) {
+            return None;
+        }
+        let base = signed.disavowal_base();
+        let key_image = my_key.key.clone() * &base;
+        if &key_image == signer_image {
+            return None;
+        }
+        let proof = Signature::sign_tagged(
+            Transcript::Disavowal {
+                base: &base,
+                key_image: &key_image,
+            },
+            my_key.key.clone(),
+            &[],
+            Some((&base, &key_image)),
+            rng,
+        );
+        Some(DisavowalProof {
+            disavower,
+            key_image,
+            proof,
+        })
+    }
+
+    /// The key whose holder is disavowing the message.
+    pub fn disavower(&self) -> &PublicKey {
+        &self.disavower
+    }
+
+    pub(crate) fn verify(&self, signed: &SignedMessage) -> bool {
+        let signer_image = match signed.key_image {
+            Some(ref i) => i,
+            None => return false,
+        };
+        let base = signed.disavowal_base();
+        // The proof must be by the disavower's key alone.
+        self.proof.ring_responses.len() == 1
+            && self.proof.ring_responses[0].0 == self.disavower.keypoint
+            && self.disavower.validate_attestation()
+            && signed.ring().any(|k| k == &self.disavower)
+            && &self.key_image != signer_image
+            && self.proof.verify_tagged(
+                Transcript::Disavowal {
+                    base: &base,
+                    key_image: &self.key_image,
+                },
+                Some((&base, &self.key_image)),
+            )
+            && signed.verify()
+    }
+}
+
+// The ASCII format of a disavowal is four lines, just like an `AuthorshipClaim`:
+//
+// (0) A fixed first line
+// (1) The disavower's name, email, and fingerprint, as in the ring of a signed message
+// (2) The proof itself (z85-encoded)
+// (3) A fixed last line
+
+const DISAVOWAL_FIRST_LINE: &str =
+    "The holder of this key proves that they did not sign a Zebra message:";
+const DISAVOWAL_LAST_LINE: &str =
+    "To check this proof, paste it into the Zebra app while verifying the message it refers to.";
+
+impl From<&DisavowalProof> for String {
+    fn from(d: &DisavowalProof) -> String {
+        let mut proof_bytes = vec![];
+        d.serialize(&mut proof_bytes)
+            .expect("Failed to serialize disavowal into unbounded buffer");
+        [
+            DISAVOWAL_FIRST_LINE.to_string(),
+            key_line(&d.disavower),
+            z85::encode(&proof_bytes),
+            DISAVOWAL_LAST_LINE.to_string(),
+        ]
+        .join("\n")
+    }
+}
+
+impl FromStr for DisavowalProof {
+    type Err = ();
+    /// As with SignedMessage, success does *not* imply a valid proof.
+    fn from_str(s: &str) -> Result<DisavowalProof, ()> {
+        let lines = s.trim().split('\n').collect::<Vec<_>>();
+        if lines.len() != 4 || lines[0] != DISAVOWAL_FIRST_LINE || lines[3] != DISAVOWAL_LAST_LINE {
+            return Err(());
+        }
+        let proof_bytes = z85::decode(lines[2]).ok_or(())?;
+        let proof = DisavowalProof::try_from_slice(&proof_bytes).map_err(|_| ())?;
+        if lines[1] != key_line(&proof.disavower) {
+            return Err(());
+        }
+        Ok(proof)
+    }
+}
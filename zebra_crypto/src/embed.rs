@@ -0,0 +1,466 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{key_line, z85, SignedMessage};
+
+// How embedding works:
+//
+// The ASCII format is meant to be pasted around as a whole, and its quotes and signature lines look
+// out of place in a rendered web page. So a signed message can also be embedded in an HTML or
+// Markdown document, with the message shown as ordinary text, and a detached signature block next
+// to it. The block is the same in both forms:
+//
+//   (0)       This is a Zebra signature of the message it's attached to, by someone with a private key corresponding to one of these fingerprints:
+//   (1)       Gaius <notzebra@example.com> 2eYG]XX<L8 /[%xQuHl>G yo[zgI%XA0 @XOnoqcZtN
+//   (N)       Zebra <zebra@example.com> +)WwrJlc{^ zil3Bgnt:m tE[!HwzZHz P0[sCM4Sc?
+//   (N+1)     <the z85-encoded Borsh encoding of the signed message, with an empty message>
+//   (N+2)     To verify it, paste the document's HTML or Markdown source into the Zebra app.
+//
+// As with the ASCII format, the key lines must match the ring in the signature data.
+//
+// In HTML, the message is a single element, with the block in an attribute (escaped, with its line
+// breaks as "&#10;", so that it stays on one line):
+//
+//   <blockquote class="zebra-signed-message" data-zebra-signature="..." style="white-space: pre-wrap">Message</blockquote>
+//
+// The message is the element's text content, taken from the source: every tag is removed, except
+// that <br> becomes a line break (along with a line break right after it in the source, if there is
+// one), and <p> and <div> (opening or closing) become a blank line, and then character references
+// are decoded. Comments are ignored. We only ever emit text, with "&",
+// "<", ">" and CR escaped, so an exact message must be left as it was. But wikis and blog engines
+// like to rewrap text, and split it into paragraphs, which canonical text survives.
+//
+// In Markdown, the message is a block quote, with each line prefixed by "> " (or just ">", if
+// it's empty), followed by the block in a code block fenced with backticks, with the info string
+// "zebra-signature". The message is the block quote's lines, with the ">" and one space (if there
+// is one) removed from each. There may be blank lines between the two, but every line of the
+// quote must start with ">" (Markdown's "lazy" continuation lines aren't supported).
+
+const EMBEDDED_SIGNATURE_FIRST_LINE: &str = "This is a Zebra signature of the message it's attached to, by someone with a private key corresponding to one of these fingerprints:";
+const EMBEDDED_SIGNATURE_LAST_LINE: &str =
+    "To verify it, paste the document's HTML or Markdown source into the Zebra app.";
+const HTML_SIGNATURE_ATTRIBUTE: &str = "data-zebra-signature";
+const MARKDOWN_INFO_STRING: &str = "zebra-signature";
+
+impl SignedMessage {
+    /// This message as an HTML element, for embedding in a web page. See `find_embedded`.
+    pub fn to_html(&self) -> String {
+        format!(
+            "<blockquote class=\"zebra-signed-message\" {HTML_SIGNATURE_ATTRIBUTE}=\"{}\" style=\"white-space: pre-wrap\">{}</blockquote>",
+            escape_html(&self.signature_block(), true),
+            escape_html(&self.message, false),
+        )
+    }
+
+    /// This message as a Markdown block quote, followed by a fenced code block with its signature.
+    /// See `find_embedded`.
+    pub fn to_markdown(&self) -> String {
+        let quote = self
+            .message
+            .split('\n')
+            .map(|line| {
+                if line.is_empty() {
+                    ">".to_string()
+                } else {
+                    format!("> {line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let block = self.signature_block();
+        // A fence has to be longer than any run of backticks inside it.
+        let mut longest_run = 0;
+        let mut run = 0;
+        for c in block.chars() {
+            run = if c == '`' { run + 1 } else { 0 };
+            longest_run = longest_run.max(run);
+        }
+        let fence = "`".repeat(longest_run.max(2) + 1);
+        format!("{quote}\n\n{fence}{MARKDOWN_INFO_STRING}\n{block}\n{fence}")
+    }
+
+    /// Every signed message embedded in an HTML or Markdown document, as produced by `to_html` or
+    /// `to_markdown`: the HTML ones first, then the Markdown ones, each in the order they appear.
+    /// Anything that looks like an embedded message but can't be parsed is skipped.
+    ///
+    /// IMPORTANT NOTE: As with parsing the ASCII format, finding a message does *not* imply a valid
+    /// signature; each one still has to be verified.
+    pub fn find_embedded(document: &str) -> Vec<SignedMessage> {
+        let mut found = find_in_html(document);
+        found.extend(find_in_markdown(document));
+        found
+    }
+
+    fn signature_block(&self) -> String {
+        let mut detached = self.clone();
+        detached.message = String::new();
+        let mut signed_bytes = vec![];
+        detached
+            .serialize(&mut signed_bytes)
+            .expect("Failed to serialize signed message into unbounded buffer");
+        let mut lines = vec![EMBEDDED_SIGNATURE_FIRST_LINE.to_string()];
+        lines.extend(self.ring.iter().map(key_line));
+        lines.push(z85::encode(&signed_bytes));
+        lines.push(EMBEDDED_SIGNATURE_LAST_LINE.to_string());
+        lines.join("\n")
+    }
+}
+
+/// The signed message described by a signature block, with the given message.
+fn parse_signature_block(block: &str, message: String) -> Option<SignedMessage> {
+    let lines = block.trim().lines().map(str::trim).collect::<Vec<_>>();
+    if lines.len() < 4
+        || lines[0] != EMBEDDED_SIGNATURE_FIRST_LINE
+        || lines[lines.len() - 1] != EMBEDDED_SIGNATURE_LAST_LINE
+    {
+        return None;
+    }
+    let signed_bytes = z85::decode(lines[lines.len() - 2])?;
+    let mut signed = SignedMessage::try_from_slice(&signed_bytes).ok()?;
+    if !signed.message.is_empty()
+        || signed.ring.len() != lines.len() - 3
+        || signed
+            .ring
+            .iter()
+            .zip(&lines[1..])
+            .any(|(k, line)| key_line(k) != *line)
+    {
+        return None;
+    }
+    signed.message = message;
+    Some(signed)
+}
+
+fn escape_html(s: &str, in_attribute: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\r' => escaped.push_str("&#13;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            '\n' if in_attribute => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Decode the character references in some HTML text. Anything that isn't a reference we know
+/// (which is just the ones that matter for text, and numeric ones) is left alone.
+fn unescape_html(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        unescaped.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let reference = rest
+            .find(';')
+            .and_then(|end| Some((decode_reference(&rest[1..end])?, end)));
+        match reference {
+            Some((c, end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn decode_reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    u32::from_str_radix(hex, 16).ok()?
+                }
+                None if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+                    number.parse().ok()?
+                }
+                _ => return None,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// An HTML start or end tag.
+struct Tag {
+    /// Lowercased.
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    /// (lowercased name, decoded value) pairs.
+    attributes: Vec<(String, String)>,
+    /// The index just past the tag's closing ">".
+    end: usize,
+}
+
+impl Tag {
+    /// The tag starting at `start` (which must be a "<"), if there is one.
+    fn parse(document: &str, start: usize) -> Option<Tag> {
+        let bytes = document.as_bytes();
+        let mut i = start + 1;
+        let closing = bytes.get(i) == Some(&b'/');
+        if closing {
+            i += 1;
+        }
+        let name_start = i;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+            i += 1;
+        }
+        if !bytes.get(name_start)?.is_ascii_alphabetic() {
+            return None;
+        }
+        let name = document[name_start..i].to_ascii_lowercase();
+
+        let mut attributes = vec![];
+        loop {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match *bytes.get(i)? {
+                b'>' => {
+                    return Some(Tag {
+                        name,
+                        closing,
+                        self_closing: false,
+                        attributes,
+                        end: i + 1,
+                    })
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                    return Some(Tag {
+                        name,
+                        closing,
+                        self_closing: true,
+                        attributes,
+                        end: i + 2,
+                    })
+                }
+                _ => {}
+            }
+            let attribute_start = i;
+            while i < bytes.len() && !b" \t\r\n\x0c=>/".contains(&bytes[i]) {
+                i += 1;
+            }
+            if i == attribute_start {
+                // A stray "/", or "=".
+                i += 1;
+                continue;
+            }
+            let attribute = document[attribute_start..i].to_ascii_lowercase();
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let mut value = String::new();
+            if bytes.get(i) == Some(&b'=') {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                let value_range = match *bytes.get(i)? {
+                    quote @ (b'"' | b'\'') => {
+                        let end = i + 1 + bytes[i + 1..].iter().position(|&b| b == quote)?;
+                        let range = i + 1..end;
+                        i = end + 1;
+                        range
+                    }
+                    _ => {
+                        let value_start = i;
+                        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>'
+                        {
+                            i += 1;
+                        }
+                        value_start..i
+                    }
+                };
+                value = unescape_html(&document[value_range]);
+            }
+            attributes.push((attribute, value));
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// If there's a comment starting at `start`, the index just past its end.
+fn comment_end(document: &str, start: usize) -> Option<usize> {
+    if !document[start..].starts_with("<!--") {
+        return None;
+    }
+    document[start + 4..]
+        .find("-->")
+        .map(|end| start + 4 + end + 3)
+}
+
+fn find_in_html(document: &str) -> Vec<SignedMessage> {
+    // Rather than tokenizing the whole document (which may not be HTML at all, or may have
+    // signature blocks, with their stray "<"s, in Markdown code blocks), we only look at the tags
+    // that have the attribute.
+    let mut found = vec![];
+    let mut pos = 0;
+    while let Some(i) = document[pos..].find(HTML_SIGNATURE_ATTRIBUTE) {
+        let attribute_start = pos + i;
+        pos = attribute_start + HTML_SIGNATURE_ATTRIBUTE.len();
+        let Some(start) = document[..attribute_start].rfind('<') else {
+            continue;
+        };
+        let in_comment = document[..start]
+            .rfind("<!--")
+            .filter(|&open| !document[open..start].contains("-->"))
+            .is_some();
+        if in_comment {
+            continue;
+        }
+        let Some(tag) = Tag::parse(document, start) else {
+            continue;
+        };
+        let Some(block) = tag.attribute(HTML_SIGNATURE_ATTRIBUTE) else {
+            continue;
+        };
+        if tag.closing || tag.self_closing || tag.end < pos {
+            continue;
+        }
+        if let Some((text, end)) = element_text(document, &tag) {
+            if let Some(signed) = parse_signature_block(block, text) {
+                found.push(signed);
+                pos = end;
+            }
+        }
+    }
+    found
+}
+
+/// The text content of the element that `start_tag` opens (see above), and the index just past its
+/// end tag. Returns None if it has no end tag.
+fn element_text(document: &str, start_tag: &Tag) -> Option<(String, usize)> {
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut pos = start_tag.end;
+    loop {
+        let start = pos + document[pos..].find('<')?;
+        text.push_str(&unescape_html(&document[pos..start]));
+        if let Some(end) = comment_end(document, start) {
+            pos = end;
+            continue;
+        }
+        let Some(tag) = Tag::parse(document, start) else {
+            text.push('<');
+            pos = start + 1;
+            continue;
+        };
+        pos = tag.end;
+        if tag.name == start_tag.name {
+            if tag.self_closing {
+                continue;
+            } else if !tag.closing {
+                depth += 1;
+            } else if depth > 0 {
+                depth -= 1;
+            } else {
+                return Some((text, tag.end));
+            }
+        } else if tag.name == "br" && !tag.closing {
+            text.push('\n');
+            // Editors like to put a line break after each <br> in the source, too.
+            if document[pos..].starts_with("\r\n") {
+                pos += 2;
+            } else if document[pos..].starts_with('\n') {
+                pos += 1;
+            }
+        } else if tag.name == "p" || tag.name == "div" {
+            text.push_str("\n\n");
+        }
+    }
+}
+
+fn find_in_markdown(document: &str) -> Vec<SignedMessage> {
+    let lines = document.split('\n').collect::<Vec<_>>();
+    let mut found = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(fence) = opening_fence(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let Some(close) = (i + 1..lines.len()).find(|&j| closes_fence(lines[j], fence)) else {
+            break;
+        };
+        let block = lines[i + 1..close].join("\n");
+        let mut quote_end = i;
+        while quote_end > 0 && lines[quote_end - 1].trim().is_empty() {
+            quote_end -= 1;
+        }
+        let mut quote_start = quote_end;
+        while quote_start > 0 && quoted_line(lines[quote_start - 1]).is_some() {
+            quote_start -= 1;
+        }
+        if quote_start < quote_end {
+            let message = lines[quote_start..quote_end]
+                .iter()
+                .filter_map(|line| quoted_line(line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            found.extend(parse_signature_block(&block, message));
+        }
+        i = close + 1;
+    }
+    found
+}
+
+/// Markdown allows up to three spaces of indentation before most block syntax.
+fn unindented(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    Some(rest)
+}
+
+/// If `line` opens a code block with a signature block in it, the length of its fence.
+fn opening_fence(line: &str) -> Option<usize> {
+    let line = unindented(line)?;
+    let rest = line.trim_start_matches('`');
+    let fence = line.len() - rest.len();
+    if fence < 3 || rest.trim() != MARKDOWN_INFO_STRING {
+        return None;
+    }
+    Some(fence)
+}
+
+fn closes_fence(line: &str, fence: usize) -> bool {
+    let Some(line) = unindented(line) else {
+        return false;
+    };
+    let rest = line.trim_start_matches('`');
+    line.len() - rest.len() >= fence && rest.trim().is_empty()
+}
+
+/// If `line` is part of a block quote, its content.
+fn quoted_line(line: &str) -> Option<&str> {
+    let content = unindented(line)?.strip_prefix('>')?;
+    Some(content.strip_prefix(' ').unwrap_or(content))
+}
@@ -0,0 +1,278 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// The registry of signed message format versions. Each version determines the transcript that
+/// gets signed, the first line of the ASCII format, and which optional features a message can
+/// carry.
+///
+/// New versions should only ever be added here, never changed or removed: once a message has been
+/// published in some version, we need to be able to parse and verify it forever.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, BorshSerialize, BorshDeserialize,
+)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum FormatVersion {
+    /// The original format. The transcript is the raw message bytes followed by the ring, with
+    /// no domain separation, and the only content is the message itself.
+    V1_0 = 1,
+    /// A domain-separated, length-prefixed transcript. Messages may be bound to a context, carry
+    /// signed headers, be claimable and/or disavowable, and be signed as canonical text.
+    V2_0 = 2,
+}
+
+impl FormatVersion {
+    /// Every version that we know how to parse and verify, oldest first.
+    pub const ALL: &'static [FormatVersion] = &[FormatVersion::V1_0, FormatVersion::V2_0];
+
+    /// The version that new messages are signed with, unless the caller asks for another one.
+    pub const LATEST: FormatVersion = FormatVersion::V2_0;
+
+    /// The version number, as it appears in the ASCII format (e.g. "2.0").
+    pub fn number(&self) -> &'static str {
+        match self {
+            FormatVersion::V1_0 => "1.0",
+            FormatVersion::V2_0 => "2.0",
+        }
+    }
+
+    /// Whether messages in this version can be bound to a context.
+    pub fn supports_context(&self) -> bool {
+        match self {
+            FormatVersion::V1_0 => false,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// Whether messages in this version can carry signed headers (such as a date or subject).
+    pub fn supports_headers(&self) -> bool {
+        match self {
+            FormatVersion::V1_0 => false,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// Whether messages in this version can be claimable (see `AuthorshipClaim`).
+    pub fn supports_claims(&self) -> bool {
+        match self {
+            FormatVersion::V1_0 => false,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// Whether messages in this version can be disavowable (see `DisavowalProof`).
+    pub fn supports_disavowal(&self) -> bool {
+        match self {
+            FormatVersion::V1_0 => false,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// Whether messages in this version can be signed with the given canonicalization.
+    pub fn supports_canonicalization(&self, canonicalization: Canonicalization) -> bool {
+        match self {
+            FormatVersion::V1_0 => canonicalization == Canonicalization::Exact,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// Whether messages in this version can be signed with the given ring signature scheme.
+    pub fn supports_scheme(&self, scheme: RingScheme) -> bool {
+        match self {
+            FormatVersion::V1_0 => scheme == RingScheme::Sag,
+            FormatVersion::V2_0 => true,
+        }
+    }
+
+    /// The first line of a message in the ASCII format, which is what identifies the version.
+    pub(crate) fn first_line(&self) -> String {
+        format!(
+            "The following message has been signed using Zebra {}:",
+            self.number()
+        )
+    }
+
+    pub(crate) fn from_first_line(line: &str) -> Option<FormatVersion> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|version| version.first_line() == line)
+    }
+}
+
+impl Default for FormatVersion {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+impl core::fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Zebra {}", self.number())
+    }
+}
+
+/// The registry of ring signature schemes. The scheme determines how a message proves that one of
+/// its ring members signed it, but not what gets signed: every scheme signs the same transcript.
+///
+/// As with format versions, schemes should only ever be added here, never changed or removed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum RingScheme {
+    /// Spontaneous anonymous group signatures (see `Signature::sign_tagged`). Fast to sign and
+    /// verify, but the signature holds one scalar for each ring member.
+    #[default]
+    Sag,
+    /// A Groth-Kohlweiss one-of-many proof (see `one_of_many.rs`), whose size grows with the
+    /// logarithm of the ring size. Slower than SAG for small rings, but much smaller for large
+    /// ones. It can't be tagged with a key image, so messages using it can't be disavowable.
+    OneOfMany,
+}
+
+impl RingScheme {
+    /// Every scheme that we know how to parse and verify, oldest first.
+    pub const ALL: &'static [RingScheme] = &[RingScheme::Sag, RingScheme::OneOfMany];
+
+    /// The scheme's name, as it appears in the ASCII format and in transcripts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RingScheme::Sag => "SAG",
+            RingScheme::OneOfMany => "one-of-many",
+        }
+    }
+
+    /// Whether signatures in this scheme can be tagged with a key image, which disavowable
+    /// messages need.
+    pub fn supports_key_images(&self) -> bool {
+        match self {
+            RingScheme::Sag => true,
+            RingScheme::OneOfMany => false,
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<RingScheme> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scheme| scheme.name() == name)
+    }
+}
+
+impl core::fmt::Display for RingScheme {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The registry of message canonicalizations: the rules for turning a message's text into what
+/// actually gets signed. A verifier applies the same rules to the text it's given, so whatever the
+/// rules throw away can change without breaking the signature.
+///
+/// As with schemes, canonicalizations should only ever be added here, never changed or removed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum Canonicalization {
+    /// The message is signed exactly as it is, so any change to it, even to its whitespace, breaks
+    /// the signature.
+    #[default]
+    Exact,
+    /// Only the message's words and paragraph breaks are signed (see `apply`), so it survives
+    /// being copied and pasted, reflowed, or sent through apps that change line endings, strip
+    /// trailing whitespace, or turn tabs into spaces.
+    Text,
+}
+
+impl Canonicalization {
+    /// Every canonicalization that we know how to verify, oldest first.
+    pub const ALL: &'static [Canonicalization] = &[Canonicalization::Exact, Canonicalization::Text];
+
+    /// The canonicalization's name, as it appears in the ASCII format and in transcripts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Canonicalization::Exact => "exact",
+            Canonicalization::Text => "text",
+        }
+    }
+
+    /// What gets signed for `message`.
+    ///
+    /// For canonical text, the message is put in Unicode Normalization Form C, and split into
+    /// paragraphs at blank lines (lines that are empty or all whitespace, where a line ends with
+    /// LF, CRLF, or CR). Each paragraph becomes a single line, with its words (the runs of
+    /// non-whitespace characters, in the Unicode sense) separated by single spaces, and the
+    /// paragraphs are separated by single blank lines, with none at the start or end.
+    pub fn apply<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        match self {
+            Canonicalization::Exact => Cow::Borrowed(message),
+            Canonicalization::Text => {
+                let normalized = message
+                    .nfc()
+                    .collect::<String>()
+                    .replace("\r\n", "\n")
+                    .replace('\r', "\n");
+                let mut paragraphs = Vec::new();
+                let mut words = Vec::new();
+                for line in normalized.split('\n') {
+                    let line_words = line.split_whitespace().collect::<Vec<_>>();
+                    if line_words.is_empty() {
+                        if !words.is_empty() {
+                            paragraphs.push(words.join(" "));
+                            words.clear();
+                        }
+                    } else {
+                        words.extend(line_words);
+                    }
+                }
+                if !words.is_empty() {
+                    paragraphs.push(words.join(" "));
+                }
+                Cow::Owned(paragraphs.join("\n\n"))
+            }
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Canonicalization> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+}
+
+impl core::fmt::Display for Canonicalization {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Options for producing a SignedMessage. The defaults produce a message in the latest format,
+/// with no optional features.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SigningOptions {
+    /// The format version to sign (and later emit) the message in. Options that the version
+    /// doesn't support cause signing to fail rather than being silently dropped.
+    pub version: FormatVersion,
+    /// See `SignedMessage::sign_with_context`.
+    pub context: Option<String>,
+    /// Headers to sign along with the message, as (name, value) pairs, e.g. ("Subject", "Lunch").
+    /// They're emitted in this order. See `SignedMessage::sign_with_options` for what's allowed.
+    pub headers: Vec<(String, String)>,
+    /// Whether to commit to a secret that lets the signer prove authorship later, with an
+    /// `AuthorshipClaim`. The secret is derived from the private key and the signed content, so
+    /// there's nothing extra to keep safe.
+    pub claimable: bool,
+    /// Whether to tag the signature with a key image, so that every other ring member can prove
+    /// that they didn't sign it, with a `DisavowalProof`. Note that this also lets anyone who
+    /// knows one of the ring's private keys check whether *that* key signed it.
+    pub disavowable: bool,
+    /// The ring signature scheme to sign with. The default, SAG, is best for small rings; for rings
+    /// of more than a few dozen keys, `RingScheme::OneOfMany` produces much smaller signatures.
+    pub scheme: RingScheme,
+    /// How the message's text is canonicalized before it's signed. The default, `Exact`, signs it
+    /// exactly as it is; `Canonicalization::Text` lets it survive changes to its whitespace.
+    pub canonicalization: Canonicalization,
+    /// Whether to derive the signature's random values from the private key and everything being
+    /// signed, as well as from the RNG. This keeps the private key safe even if the RNG is weak or
+    /// broken, at the cost of a couple of extra hashes.
+    pub hedged: bool,
+}
@@ -0,0 +1,119 @@
+#[cfg(not(feature = "std"))]
+use borsh::BorshSerialize;
+use rand::{CryptoRng, RngCore};
+use sha3::{Digest, Sha3_512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::append_transcript_field;
+use crate::ristretto::Scalar;
+
+const HEDGED_RNG_DOMAIN: &[u8] = b"Zebra hedged nonce derivation v1";
+#[cfg(not(feature = "std"))]
+const VERIFICATION_WEIGHTS_DOMAIN: &[u8] = b"Zebra verification weights v1";
+
+// Hedged signing (see `SigningOptions::hedged`) derives every random value used while signing from
+// the private key and the transcript, as well as from fresh randomness. As long as either the RNG
+// is good or the private key is secret, the nonces are unpredictable, and they never repeat across
+// different messages, which is what it would take for a signature to leak the private key. With a
+// good RNG, they're as random as the RNG's output, so (unlike fully deterministic nonces) signing
+// the same message twice still produces unrelated signatures.
+//
+// The derived stream is H(seed || 0), H(seed || 1), ..., where seed = H(domain, key, transcript,
+// 64 bytes from the RNG), and H is Sha3-512.
+//
+// The same stream, with seed = H(domain, transcript, proof), gives the random weights for verifying
+// one-of-many proofs when there's no RNG to draw them from (without the `std` feature). Weights
+// only need to be unpredictable to whoever made the proof, before they've finished making it, and
+// these can't be known without fixing every part of the proof (as with a Fiat-Shamir challenge).
+// That includes in batches, since each proof's weights depend only on all of that proof.
+
+/// An RNG whose output is derived from a signing key and transcript, as well as from another RNG,
+/// or (for verification without std) from a transcript and proof.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub(crate) struct HedgedRng {
+    seed: [u8; 64],
+    counter: u64,
+    block: [u8; 64],
+    // How much of `block` has been used.
+    offset: usize,
+}
+
+impl HedgedRng {
+    /// `transcript_hash` is the transcript being signed, hashed with the ring (see
+    /// `Transcript::hash_with_ring`).
+    pub(crate) fn new(
+        rng: &mut (impl RngCore + CryptoRng),
+        private_value: &Scalar,
+        transcript_hash: Sha3_512,
+    ) -> Self {
+        let mut fresh = [0; 64];
+        rng.fill_bytes(&mut fresh);
+        let mut hash = Sha3_512::new();
+        append_transcript_field(&mut hash, b"domain", HEDGED_RNG_DOMAIN);
+        append_transcript_field(&mut hash, b"key", private_value.as_bytes());
+        append_transcript_field(&mut hash, b"transcript", &transcript_hash.finalize());
+        append_transcript_field(&mut hash, b"randomness", &fresh);
+        fresh.zeroize();
+        Self::from_seed(hash.finalize().into())
+    }
+
+    /// For the weights used in verifying `proof`, whose transcript (with the ring) is
+    /// `transcript_hash`.
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn for_verification(transcript_hash: Sha3_512, proof: &impl BorshSerialize) -> Self {
+        let mut hash = Sha3_512::new();
+        append_transcript_field(&mut hash, b"domain", VERIFICATION_WEIGHTS_DOMAIN);
+        append_transcript_field(&mut hash, b"transcript", &transcript_hash.finalize());
+        append_transcript_field(
+            &mut hash,
+            b"proof",
+            &borsh::to_vec(proof).expect("Failed to serialize into unbounded buffer"),
+        );
+        Self::from_seed(hash.finalize().into())
+    }
+
+    fn from_seed(seed: [u8; 64]) -> Self {
+        HedgedRng {
+            seed,
+            counter: 0,
+            block: [0; 64],
+            offset: 64,
+        }
+    }
+}
+
+impl RngCore for HedgedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.offset == self.block.len() {
+                let mut hash = Sha3_512::new();
+                hash.update(self.seed);
+                hash.update(self.counter.to_le_bytes());
+                self.block = hash.finalize().into();
+                self.counter += 1;
+                self.offset = 0;
+            }
+            *byte = self.block[self.offset];
+            self.offset += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for HedgedRng {}
@@ -1,11 +1,47 @@
+// Everything but the operating system's RNG (and parallel batch verification) works with just
+// alloc; see the `std` feature.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod batch;
+mod claim;
+mod disavowal;
+mod embed;
+mod format;
+mod hedged;
+mod merkle;
+mod one_of_many;
+mod raw;
 mod ristretto;
-
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod threshold;
+pub mod vectors;
+mod z85;
+
+pub use claim::AuthorshipClaim;
+pub use disavowal::DisavowalProof;
+pub use format::{Canonicalization, FormatVersion, RingScheme, SigningOptions};
+use hedged::HedgedRng;
+pub use merkle::{InclusionProof, SignedBatch};
+use one_of_many::OneOfManyProof;
+pub use raw::RingSignature;
 use ristretto::{RistrettoPoint, Scalar};
+pub use threshold::{ThresholdProposal, ThresholdShare, ThresholdSignedMessage};
 
-use std::str::FromStr;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use boringascii::BoringAscii;
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 use sha3::{Digest, Sha3_256, Sha3_512};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -40,6 +76,212 @@ fn hash_message_and_ring<'a>(
     message_and_ring_hash
 }
 
+const TRANSCRIPT_V2_DOMAIN: &[u8] = b"Zebra ring signature transcript v2";
+
+/// Append a labeled, length-prefixed field to a v2 transcript. Because every field carries its own
+/// label and length, no two distinct sequences of fields can produce the same bytes.
+fn append_transcript_field(hash: &mut impl Digest, label: &[u8], data: &[u8]) {
+    hash.update((label.len() as u64).to_le_bytes());
+    hash.update(label);
+    hash.update((data.len() as u64).to_le_bytes());
+    hash.update(data);
+}
+
+/// Everything that a ring signature commits to, other than the ring itself.
+#[derive(Clone, Copy)]
+enum Transcript<'a> {
+    /// The original "Zebra 1.0" transcript: just the message bytes followed by the ring, as in
+    /// `hash_message_and_ring`. Key attestations will always use this, since changing it would
+    /// change every public key. Otherwise it's only used to verify old messages.
+    V1 { message: &'a [u8] },
+    /// A domain-separated transcript, in which every field is labeled and length-prefixed. The
+    /// optional context binds the signature to a particular audience or purpose, so that it can't
+    /// be lifted out of one setting and presented in another. The headers (e.g. a date or subject)
+    /// are signed along with the message, in order. The claim commitment, if any, lets the signer
+    /// prove authorship later (see `AuthorshipClaim`). The key image, if any, lets the other ring
+    /// members prove that they *didn't* sign it (see `DisavowalProof`). The scheme is signed too, so
+    /// that a signature can't be reinterpreted as one in a different scheme, and so is the
+    /// canonicalization, in which case `message` is the canonicalized text.
+    V2 {
+        message: &'a [u8],
+        context: Option<&'a str>,
+        headers: &'a [(String, String)],
+        claim_commitment: Option<&'a [u8; 32]>,
+        key_image: Option<&'a RistrettoPoint>,
+        scheme: RingScheme,
+        canonicalization: Canonicalization,
+    },
+    /// What an `AuthorshipClaim` signs, with a ring of just the claimant's key.
+    Claim {
+        commitment: &'a [u8; 32],
+        token: &'a [u8; 32],
+    },
+    /// What a `DisavowalProof` signs, with a ring of just the disavower's key, tagged with their
+    /// key image for the disavowed message.
+    Disavowal {
+        base: &'a RistrettoPoint,
+        key_image: &'a RistrettoPoint,
+    },
+    /// What each share of a threshold signature signs (see `ThresholdProposal`).
+    Threshold { message: &'a [u8], threshold: u32 },
+    /// What a `SignedBatch` signs: the root of the Merkle tree over its documents.
+    Batch {
+        root: &'a [u8; 64],
+        document_count: u32,
+    },
+    /// What a `RingSignature` signs: bytes from some other protocol. Unlike V2, the scheme is
+    /// always included.
+    Raw {
+        message: &'a [u8],
+        scheme: RingScheme,
+    },
+}
+
+/// The transcript that the given format version signs. `message` must already be canonicalized.
+#[allow(clippy::too_many_arguments)]
+fn transcript<'a>(
+    version: FormatVersion,
+    message: &'a [u8],
+    context: Option<&'a str>,
+    headers: &'a [(String, String)],
+    claim_commitment: Option<&'a [u8; 32]>,
+    key_image: Option<&'a RistrettoPoint>,
+    scheme: RingScheme,
+    canonicalization: Canonicalization,
+) -> Transcript<'a> {
+    match version {
+        FormatVersion::V1_0 => Transcript::V1 { message },
+        FormatVersion::V2_0 => Transcript::V2 {
+            message,
+            context,
+            headers,
+            claim_commitment,
+            key_image,
+            scheme,
+            canonicalization,
+        },
+    }
+}
+
+impl Transcript<'_> {
+    fn hash_with_ring<'a>(&self, keys: impl Iterator<Item = &'a RistrettoPoint>) -> Sha3_512 {
+        match *self {
+            Transcript::V1 { message } => hash_message_and_ring(message, keys),
+            Transcript::V2 {
+                message,
+                context,
+                headers,
+                claim_commitment,
+                key_image,
+                scheme,
+                canonicalization,
+            } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(&mut hash, b"domain", TRANSCRIPT_V2_DOMAIN);
+                if let Some(context) = context {
+                    append_transcript_field(&mut hash, b"context", context.as_bytes());
+                }
+                // Like the headers below, this is omitted entirely when absent, so that signatures
+                // from before it existed still verify.
+                if let Some(commitment) = claim_commitment {
+                    append_transcript_field(&mut hash, b"claim-commitment", commitment);
+                }
+                if let Some(image) = key_image {
+                    append_transcript_field(&mut hash, b"key-image", &image.compress());
+                }
+                // SAG was the only scheme before this existed, so it's the one that's left out.
+                if scheme != RingScheme::Sag {
+                    append_transcript_field(&mut hash, b"ring-scheme", scheme.name().as_bytes());
+                }
+                // Likewise, exact messages were the only kind before this existed.
+                if canonicalization != Canonicalization::Exact {
+                    append_transcript_field(
+                        &mut hash,
+                        b"canonicalization",
+                        canonicalization.name().as_bytes(),
+                    );
+                }
+                // With no headers, this is exactly the transcript from before headers existed, so
+                // those signatures still verify.
+                for (name, value) in headers {
+                    append_transcript_field(&mut hash, b"header-name", name.as_bytes());
+                    append_transcript_field(&mut hash, b"header-value", value.as_bytes());
+                }
+                append_transcript_field(&mut hash, b"message", message);
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+            Transcript::Claim { commitment, token } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(&mut hash, b"domain", claim::CLAIM_TRANSCRIPT_DOMAIN);
+                append_transcript_field(&mut hash, b"claim-commitment", commitment);
+                append_transcript_field(&mut hash, b"claim-token", token);
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+            Transcript::Disavowal { base, key_image } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(
+                    &mut hash,
+                    b"domain",
+                    disavowal::DISAVOWAL_TRANSCRIPT_DOMAIN,
+                );
+                append_transcript_field(&mut hash, b"base-point", &base.compress());
+                append_transcript_field(&mut hash, b"key-image", &key_image.compress());
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+            Transcript::Threshold { message, threshold } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(
+                    &mut hash,
+                    b"domain",
+                    threshold::THRESHOLD_TRANSCRIPT_DOMAIN,
+                );
+                append_transcript_field(&mut hash, b"threshold", &threshold.to_le_bytes());
+                append_transcript_field(&mut hash, b"message", message);
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+            Transcript::Batch {
+                root,
+                document_count,
+            } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(&mut hash, b"domain", merkle::BATCH_TRANSCRIPT_DOMAIN);
+                append_transcript_field(
+                    &mut hash,
+                    b"document-count",
+                    &document_count.to_le_bytes(),
+                );
+                append_transcript_field(&mut hash, b"merkle-root", root);
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+            Transcript::Raw { message, scheme } => {
+                let mut hash = Sha3_512::new();
+                append_transcript_field(&mut hash, b"domain", raw::RAW_TRANSCRIPT_DOMAIN);
+                append_transcript_field(&mut hash, b"ring-scheme", scheme.name().as_bytes());
+                append_transcript_field(&mut hash, b"message", message);
+                for keypoint in keys {
+                    append_transcript_field(&mut hash, b"ring-member", &keypoint.compress());
+                }
+                hash
+            }
+        }
+    }
+}
+
 #[derive(
     Clone, PartialEq, Eq, PartialOrd, Ord, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize,
 )]
@@ -76,9 +318,47 @@ impl Signature {
     /// implementation, though I did mainly write it from scratch. However, this may constrain me
     /// to publish this section of code under an MIT license
     fn sign(
-        message: &[u8],                            // m
-        my_private_value: Scalar,                  // k_pi
-        other_public_keypoints: &[RistrettoPoint], // K_i
+        message: &[u8],
+        my_private_value: Scalar,
+        other_public_keypoints: &[RistrettoPoint],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        Self::sign_transcript(
+            Transcript::V1 { message },
+            my_private_value,
+            other_public_keypoints,
+            rng,
+        )
+    }
+
+    fn sign_transcript(
+        transcript: Transcript,
+        my_private_value: Scalar,
+        other_public_keypoints: &[RistrettoPoint],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        Self::sign_tagged(
+            transcript,
+            my_private_value,
+            other_public_keypoints,
+            None,
+            rng,
+        )
+    }
+
+    /// Compute a ring signature that is also "tagged" with the signer's key image I = k_pi H, for
+    /// some base point H whose discrete log nobody knows. This is bLSAG (Zero to Monero, section
+    /// 3.4), except that H is the same for every ring member, and is chosen by the caller rather
+    /// than being derived from the signer's key. Anyone who knows one of the ring's private keys can
+    /// compute their own key image, and show whether or not it's the one in the signature.
+    ///
+    /// Tagging with `None` produces exactly the untagged SAG signature described above.
+    fn sign_tagged(
+        transcript: Transcript,                          // m
+        my_private_value: Scalar,                        // k_pi
+        other_public_keypoints: &[RistrettoPoint],       // K_i
+        tag: Option<(&RistrettoPoint, &RistrettoPoint)>, // (H, I)
+        rng: &mut (impl RngCore + CryptoRng),
     ) -> Self {
         // K_pi
         let my_public_keypoint = RistrettoPoint::mul_base(&my_private_value);
@@ -95,24 +375,25 @@ impl Signature {
             .expect("Key just inserted into vec, but missing after sorting.");
 
         // initialized to be fake responses r_i
-        let mut responses: Vec<Scalar> = (0..ring_size).map(|_| Scalar::random()).collect();
+        let mut responses: Vec<Scalar> = (0..ring_size).map(|_| Scalar::random(rng)).collect();
 
         let mut cs: Vec<Scalar> = vec![Scalar::ZERO; ring_size];
 
-        let a = Scalar::random();
-        // c_{pi + 1} = H_n(R, m, [aG])
-        let initial_hash = hash_message_and_ring(message, ring.iter());
-        let mut next_hash_update = RistrettoPoint::mul_base(&a);
+        let a = Scalar::random(rng);
+        // c_{pi + 1} = H_n(R, m, [aG], [aH]). Note that aG = aG + 0 K_pi, so the first step is
+        // just like the others, with a in place of the response and a zero challenge.
+        let initial_hash = transcript.hash_with_ring(ring.iter());
+        let mut previous = (a.clone(), Scalar::ZERO, my_key_index);
 
         for offset_from_my_key in 1..ring_size + 1 {
             let index = (my_key_index + offset_from_my_key) % ring_size;
 
-            // C_{i + 1} = H_n(R, M, [r_i G + c_i K_i])
+            // C_{i + 1} = H_n(R, M, [r_i G + c_i K_i], [r_i H + c_i I])
             let mut hash = initial_hash.clone();
-            hash.update(next_hash_update.compress());
+            let (response, challenge, previous_index) = previous;
+            hash_ring_step(&mut hash, response, challenge, &ring[previous_index], tag);
             cs[index] = Scalar::from_hash(hash);
-            next_hash_update =
-                RistrettoPoint::mul_base(&responses[index]) + cs[index].clone() * &ring[index];
+            previous = (responses[index].clone(), cs[index].clone(), index);
         }
 
         // "Define the real response r_pi such that a = r_pi + c_pi k_pi (mod l)", i.e. r_pi = a -
@@ -132,17 +413,27 @@ impl Signature {
     /// the private keys corresponding to the public keys in the ring, must have produced this
     /// signature.
     fn verify(&self, message: &[u8]) -> bool {
-        // c_{i + 1}' = H_n(R, m, [r_i G + c_i K_i])
-        let initial_hash =
-            hash_message_and_ring(message, self.ring_responses.iter().map(|(k, _)| k));
+        self.verify_transcript(Transcript::V1 { message })
+    }
+
+    fn verify_transcript(&self, transcript: Transcript) -> bool {
+        self.verify_tagged(transcript, None)
+    }
+
+    /// Verify a signature produced by `sign_tagged`, with the same tag.
+    fn verify_tagged(
+        &self,
+        transcript: Transcript,
+        tag: Option<(&RistrettoPoint, &RistrettoPoint)>,
+    ) -> bool {
+        // c_{i + 1}' = H_n(R, m, [r_i G + c_i K_i], [r_i H + c_i I])
+        let initial_hash = transcript.hash_with_ring(self.ring_responses.iter().map(|(k, _)| k));
 
         let mut reconstructed_challenge = self.challenge.clone();
 
         for (keypoint, response) in &self.ring_responses {
             let mut h = initial_hash.clone();
-            let hash_update =
-                RistrettoPoint::mul_base(response) + reconstructed_challenge * keypoint;
-            h.update(hash_update.compress());
+            hash_ring_step_vartime(&mut h, response, &reconstructed_challenge, keypoint, tag);
             reconstructed_challenge = Scalar::from_hash(h);
         }
 
@@ -151,6 +442,47 @@ impl Signature {
     }
 }
 
+/// Add one step of the ring to a challenge hash: r G + c K, followed (for tagged signatures) by
+/// r H + c I.
+fn hash_ring_step(
+    hash: &mut Sha3_512,
+    response: Scalar,
+    challenge: Scalar,
+    keypoint: &RistrettoPoint,
+    tag: Option<(&RistrettoPoint, &RistrettoPoint)>,
+) {
+    hash.update(
+        RistrettoPoint::double_scalar_mul_basepoint(&challenge, keypoint, &response).compress(),
+    );
+    if let Some((base, image)) = tag {
+        hash.update(
+            RistrettoPoint::multiscalar_mul(&[&response, &challenge], &[base, image]).compress(),
+        );
+    }
+}
+
+/// Exactly the same as `hash_ring_step`, but faster, because it computes each sum as a single
+/// multiscalar multiplication, in variable time. That's only safe when the inputs are all public,
+/// so this is only for verifying.
+fn hash_ring_step_vartime(
+    hash: &mut Sha3_512,
+    response: &Scalar,
+    challenge: &Scalar,
+    keypoint: &RistrettoPoint,
+    tag: Option<(&RistrettoPoint, &RistrettoPoint)>,
+) {
+    hash.update(
+        RistrettoPoint::vartime_double_scalar_mul_basepoint(challenge, keypoint, response)
+            .compress(),
+    );
+    if let Some((base, image)) = tag {
+        hash.update(
+            RistrettoPoint::vartime_multiscalar_mul(&[response, challenge], &[base, image])
+                .compress(),
+        );
+    }
+}
+
 /// An identity used for creating a ring signature.
 ///
 /// An identity always contains a name and an email address. The name can be almost any utf-8
@@ -209,11 +541,11 @@ impl Identity {
 // control characters in the identity, and no non-printable or whitespace characters in the email
 // address)
 impl BorshDeserialize for Identity {
-    fn deserialize_reader<R: std::io::Read>(r: &mut R) -> std::io::Result<Identity> {
+    fn deserialize_reader<R: borsh::io::Read>(r: &mut R) -> borsh::io::Result<Identity> {
         let name = String::deserialize_reader(r)?;
         let email = BoringAscii::deserialize_reader(r)?;
-        Identity::new(&name, email.as_str()).ok_or(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Identity::new(&name, email.as_str()).ok_or(borsh::io::Error::new(
+            borsh::io::ErrorKind::Other,
             "Error constructing Identity",
         ))
     }
@@ -223,9 +555,7 @@ impl BorshDeserialize for Identity {
 /// store it to disk, or to take part in a ring signature or verification. The attestation of a
 /// constructed PublicKey object *may not be valid*. This must be checked before relying on the
 /// key's validity.
-#[derive(
-    Clone, PartialEq, Eq, PartialOrd, Ord, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize,
-)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Zeroize, ZeroizeOnDrop)]
 pub struct PublicKey {
     holder: Identity,
     keypoint: RistrettoPoint,
@@ -246,9 +576,54 @@ pub struct PublicKey {
     // of a ring signature, but in practice that would be unnecessary extra code or an extra
     // dependency.
     holder_attestation: Signature,
+
+    // The fingerprint is shown next to every key in the app, often several times per frame, and
+    // computing it means serializing and hashing the whole key. So it's computed once, when the
+    // key is constructed (by `PublicKey::new`), and never serialized.
+    fingerprint: String,
+}
+
+// As with Identity, we implement (de)serialization explicitly, so that deserializing goes through
+// `PublicKey::new`, and the fingerprint always matches the rest of the key.
+impl BorshSerialize for PublicKey {
+    fn serialize<W: borsh::io::Write>(&self, w: &mut W) -> borsh::io::Result<()> {
+        self.holder.serialize(w)?;
+        self.keypoint.serialize(w)?;
+        self.holder_attestation.serialize(w)
+    }
+}
+
+impl BorshDeserialize for PublicKey {
+    fn deserialize_reader<R: borsh::io::Read>(r: &mut R) -> borsh::io::Result<PublicKey> {
+        Ok(PublicKey::new(
+            Identity::deserialize_reader(r)?,
+            RistrettoPoint::deserialize_reader(r)?,
+            Signature::deserialize_reader(r)?,
+        ))
+    }
 }
 
 impl PublicKey {
+    fn new(holder: Identity, keypoint: RistrettoPoint, holder_attestation: Signature) -> Self {
+        let mut key = PublicKey {
+            holder,
+            keypoint,
+            holder_attestation,
+            fingerprint: String::new(),
+        };
+        let mut buffer = vec![];
+        key.serialize(&mut buffer)
+            .expect("Failed to serialize into unbounded buffer");
+        let mut fingerprint = z85::encode(&Sha3_256::digest(buffer))
+            .chars()
+            .collect::<Vec<_>>();
+        fingerprint.insert(30, ' ');
+        fingerprint.insert(20, ' ');
+        fingerprint.insert(10, ' ');
+        key.fingerprint = fingerprint.into_iter().collect();
+        key
+    }
+
     pub fn holder(&self) -> Identity {
         self.holder.clone()
     }
@@ -257,6 +632,12 @@ impl PublicKey {
         self.keypoint.clone()
     }
 
+    /// The compressed encoding of the keypoint, which is how it appears in a `RingSignature`'s
+    /// ring.
+    pub fn keypoint_bytes(&self) -> [u8; 32] {
+        self.keypoint.compress()
+    }
+
     pub fn holder_attestation(&self) -> Signature {
         self.holder_attestation.clone()
     }
@@ -278,15 +659,7 @@ impl PublicKey {
     /// (10-character) chunk of the key. This ensures a simple, more-readable, and consistent view
     /// of the fingerprint data.
     pub fn fingerprint(&self) -> String {
-        let mut buffer = vec![];
-        let _ = self.serialize(&mut buffer);
-        let mut res = z85::encode(Sha3_256::digest(buffer))
-            .chars()
-            .collect::<Vec<_>>();
-        res.insert(30, ' ');
-        res.insert(20, ' ');
-        res.insert(10, ' ');
-        res.into_iter().collect()
+        self.fingerprint.clone()
     }
 }
 
@@ -327,27 +700,27 @@ impl From<PublicKey> for String {
     }
 }
 
+/// Split `s` at its last space, requiring exactly `len` uppercase hex digits after it.
+fn split_hex_suffix(s: &str, len: usize) -> Result<(&str, &str), ()> {
+    let (rest, hex) = s.rsplit_once(' ').ok_or(())?;
+    if hex.len() != len || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'A'..=b'F')) {
+        return Err(());
+    }
+    Ok((rest, hex))
+}
+
 impl FromStr for PublicKey {
     type Err = ();
     fn from_str(s: &str) -> Result<PublicKey, ()> {
-        use regex::Regex;
-        // This regex should exactly match the description above, and not allow any matches that
-        // don't fit the pattern described. Fortunately it's pretty simple.
-        let re = match Regex::new(r"^\[([^\n]*) <([!-~]*)> ([0-9A-F]{64}) ([0-9A-F]{200})\]$") {
-            Ok(re) => re,
-            Err(_) => return Err(()),
-        };
-
-        let mut caps = re.captures_iter(s).map(|c| c.extract());
-        let (name, email, keypoint, attestation) = match caps.next() {
-            Some((overall, [name, email, keypoint, attestation])) => {
-                if overall.len() != s.len() {
-                    return Err(());
-                }
-                (name, email, keypoint, attestation)
-            }
-            _ => return Err(()),
-        };
+        // As described above, we parse from the back: the attestation and keypoint have fixed
+        // lengths, and the email can't contain spaces, so it starts just after the last " <".
+        let s = s.strip_prefix('[').ok_or(())?.strip_suffix(']').ok_or(())?;
+        let (s, attestation) = split_hex_suffix(s, 200)?;
+        let (s, keypoint) = split_hex_suffix(s, 64)?;
+        let (name, email) = s.strip_suffix('>').ok_or(())?.rsplit_once(" <").ok_or(())?;
+        if name.contains('\n') || !email.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+            return Err(());
+        }
 
         let id = Identity::new(name, email).ok_or(())?;
 
@@ -360,11 +733,7 @@ impl FromStr for PublicKey {
             .ok_or(())?;
         let attestation = Signature::deserialize(&mut attestation.as_ref()).map_err(|_| ())?;
 
-        let res = PublicKey {
-            holder: id,
-            keypoint: RistrettoPoint(keypoint),
-            holder_attestation: attestation,
-        };
+        let res = PublicKey::new(id, keypoint.into(), attestation);
 
         if !res.validate_attestation() {
             return Err(());
@@ -391,13 +760,21 @@ pub struct PrivateKey {
 }
 
 impl PrivateKey {
+    #[cfg(feature = "std")]
     pub fn new(holder: Identity) -> Self {
-        let key = Scalar::random();
+        Self::new_with_rng(holder, &mut OsRng)
+    }
+
+    /// Like `new`, but with randomness from `rng` rather than from the operating system. The same
+    /// seeded RNG always produces the same key, which is useful for tests, and only for tests.
+    pub fn new_with_rng(holder: Identity, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let key = Scalar::random(rng);
         PrivateKey {
             holder_attestation: Signature::sign(
                 &holder.bytes_for_attestation(&RistrettoPoint::mul_base(&key)),
                 key.clone(),
                 &[],
+                rng,
             ),
             holder,
             key: key.clone(),
@@ -405,25 +782,309 @@ impl PrivateKey {
     }
 
     pub fn public(&self) -> PublicKey {
-        PublicKey {
-            holder: self.holder.clone(),
-            keypoint: RistrettoPoint::mul_base(&self.key),
-            holder_attestation: self.holder_attestation.clone(),
-        }
+        PublicKey::new(
+            self.holder.clone(),
+            RistrettoPoint::mul_base(&self.key),
+            self.holder_attestation.clone(),
+        )
     }
 }
 
 /// A signed message. Contains enough information to verify that one of the given set of public keys
 /// signed the included message (and that those keys claim to correspond to the given identities).
-#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop)]
 pub struct SignedMessage {
     pub message: String,
-    challenge: Scalar,
-    ring: Vec<(PublicKey, Scalar)>,
+    #[zeroize(skip)]
+    version: FormatVersion,
+    // Exact, unless the signer chose otherwise (in a version that supports it). `message` is the
+    // text as given, not as canonicalized.
+    #[zeroize(skip)]
+    canonicalization: Canonicalization,
+    // Only ever present in versions that support it. Like an identity's name, it can't contain control
+    // characters, so that it fits on a single line of the ASCII format.
+    context: Option<String>,
+    // (name, value) pairs, in the order they were signed. Also only present in versions that
+    // support them; see `valid_headers`.
+    headers: Vec<(String, String)>,
+    // A commitment to a secret that only the signer can derive, which they can reveal later to
+    // prove authorship. Only present if the signer asked for it.
+    claim_commitment: Option<[u8; 32]>,
+    // The signer's key image for this message, which the signature is tagged with, if they made it
+    // disavowable. See `disavowal_base`.
+    key_image: Option<RistrettoPoint>,
+    ring: Vec<PublicKey>,
+    proof: RingProof,
+    // An RFC 3161 timestamp token over the message's Borsh encoding, if one has been attached (see
+    // `set_timestamp_token`). It isn't signed, so it isn't part of the Borsh encoding, or tied to
+    // any format version; only the ASCII and serde formats carry it. Never empty.
+    timestamp_token: Option<Vec<u8>>,
+}
+
+// A SignedMessage's Borsh encoding is the one that deriving it would give, with the fields in
+// order, but without the canonicalization, so that messages from before canonicalization existed
+// keep their encodings. Instead, for anything other than exact, the high bit of the version byte is
+// set, and is followed by a byte with the canonicalization's index in `Canonicalization::ALL`
+// (which can't be 0, for exact). So every message still has exactly one encoding.
+const CANONICALIZED_VERSION_FLAG: u8 = 0x80;
+
+impl BorshSerialize for SignedMessage {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        self.message.serialize(writer)?;
+        if self.canonicalization == Canonicalization::Exact {
+            (self.version as u8).serialize(writer)?;
+        } else {
+            (self.version as u8 | CANONICALIZED_VERSION_FLAG).serialize(writer)?;
+            (Canonicalization::ALL
+                .iter()
+                .position(|c| c == &self.canonicalization)
+                .expect("Every canonicalization is in ALL") as u8)
+                .serialize(writer)?;
+        }
+        self.context.serialize(writer)?;
+        self.headers.serialize(writer)?;
+        self.claim_commitment.serialize(writer)?;
+        self.key_image.serialize(writer)?;
+        self.ring.serialize(writer)?;
+        self.proof.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for SignedMessage {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let message = String::deserialize_reader(reader)?;
+        let version_byte = u8::deserialize_reader(reader)?;
+        let version = FormatVersion::try_from_slice(&[version_byte & !CANONICALIZED_VERSION_FLAG])?;
+        let canonicalization = if version_byte & CANONICALIZED_VERSION_FLAG == 0 {
+            Canonicalization::Exact
+        } else {
+            match Canonicalization::ALL.get(usize::from(u8::deserialize_reader(reader)?)) {
+                Some(&c) if c != Canonicalization::Exact => c,
+                _ => {
+                    return Err(borsh::io::Error::new(
+                        borsh::io::ErrorKind::Other,
+                        "Invalid canonicalization",
+                    ))
+                }
+            }
+        };
+        let signed = SignedMessage {
+            message,
+            version,
+            canonicalization,
+            context: BorshDeserialize::deserialize_reader(reader)?,
+            headers: BorshDeserialize::deserialize_reader(reader)?,
+            claim_commitment: BorshDeserialize::deserialize_reader(reader)?,
+            key_image: BorshDeserialize::deserialize_reader(reader)?,
+            ring: BorshDeserialize::deserialize_reader(reader)?,
+            proof: BorshDeserialize::deserialize_reader(reader)?,
+            timestamp_token: None,
+        };
+        // Nothing about the encoding stops these fields from showing up in versions that don't sign
+        // them, so they have to be checked here, like the ASCII and serde formats do.
+        if !signed.has_supported_fields() {
+            return Err(borsh::io::Error::new(
+                borsh::io::ErrorKind::Other,
+                "Unsupported in this format version",
+            ));
+        }
+        Ok(signed)
+    }
+}
+
+/// The proof that one of the ring members signed a SignedMessage, in whichever scheme the signer
+/// chose.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+enum RingProof {
+    /// A SAG signature's challenge, and its responses, one for each ring member, in ring order.
+    Sag {
+        challenge: Scalar,
+        responses: Vec<Scalar>,
+    },
+    OneOfMany(OneOfManyProof),
+}
+
+impl RingProof {
+    /// Prove knowledge of `my_private_value`, the private key for one of the keys in `ring`, which
+    /// must be the full (sorted) ring. `tag` is only supported by SAG; see `Signature::sign_tagged`.
+    fn new(
+        scheme: RingScheme,
+        transcript: Transcript,
+        my_private_value: &Scalar,
+        ring: &[RistrettoPoint],
+        tag: Option<(&RistrettoPoint, &RistrettoPoint)>,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let my_keypoint = RistrettoPoint::mul_base(my_private_value);
+        match scheme {
+            RingScheme::Sag => {
+                let sig = Signature::sign_tagged(
+                    transcript,
+                    my_private_value.clone(),
+                    &ring
+                        .iter()
+                        .filter(|k| *k != &my_keypoint)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    tag,
+                    rng,
+                );
+                RingProof::Sag {
+                    challenge: sig.challenge.clone(),
+                    responses: sig.ring_responses.iter().map(|(_, s)| s.clone()).collect(),
+                }
+            }
+            RingScheme::OneOfMany => {
+                let my_key_index = ring
+                    .binary_search(&my_keypoint)
+                    .expect("Key just inserted into ring, but missing after sorting.");
+                RingProof::OneOfMany(OneOfManyProof::prove(
+                    transcript.hash_with_ring(ring.iter()),
+                    ring,
+                    my_key_index,
+                    my_private_value,
+                    rng,
+                ))
+            }
+        }
+    }
+
+    /// Verify a proof made by `new`, with the same transcript, ring and tag.
+    fn verify(
+        &self,
+        transcript: Transcript,
+        ring: &[RistrettoPoint],
+        tag: Option<(&RistrettoPoint, &RistrettoPoint)>,
+    ) -> bool {
+        match self {
+            RingProof::Sag {
+                challenge,
+                responses,
+            } => {
+                let signature = Signature {
+                    challenge: challenge.clone(),
+                    ring_responses: ring
+                        .iter()
+                        .cloned()
+                        .zip(responses.iter().cloned())
+                        .collect(),
+                };
+                responses.len() == ring.len() && signature.verify_tagged(transcript, tag)
+            }
+            RingProof::OneOfMany(proof) => {
+                tag.is_none() && proof.verify(transcript.hash_with_ring(ring.iter()), ring)
+            }
+        }
+    }
+
+    fn scheme(&self) -> RingScheme {
+        match self {
+            RingProof::Sag { .. } => RingScheme::Sag,
+            RingProof::OneOfMany(_) => RingScheme::OneOfMany,
+        }
+    }
+}
+
+fn valid_context(context: &str) -> bool {
+    !context.is_empty() && !context.contains(char::is_control)
+}
+
+// Each header gets its own "Name: value" line in the ASCII format, between the first line and the
+// opening quotes. So names are restricted to a conservative, email-like alphabet (which also means
+// a header line can never be mistaken for the line of quotes), and can't be any of
+// `SIGNED_MESSAGE_RESERVED_NAMES`, like "Context", since those lines mean something else. Values
+// follow the same rules as contexts. Names are compared case-insensitively, and may not repeat, so
+// that there's never any question of which value applies.
+fn valid_header_name(name: &str) -> bool {
+    name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !SIGNED_MESSAGE_RESERVED_NAMES
+            .iter()
+            .any(|reserved| name.eq_ignore_ascii_case(reserved))
+}
+
+fn valid_headers(headers: &[(String, String)]) -> bool {
+    let mut seen = alloc::collections::BTreeSet::new();
+    headers.iter().all(|(name, value)| {
+        valid_header_name(name) && valid_context(value) && seen.insert(name.to_ascii_lowercase())
+    })
 }
 
 impl SignedMessage {
+    #[cfg(feature = "std")]
     pub fn sign(message: &str, my_key: &PrivateKey, other_keys: &[PublicKey]) -> Self {
+        Self::sign_with_options(message, my_key, other_keys, &SigningOptions::default())
+            .expect("Signing with default options can't fail")
+    }
+
+    /// Sign a message, binding the signature to the given context (e.g. "ACME board vote 2026").
+    /// The context is shown alongside the message, and a verifier can check that it's the one they
+    /// expect. Returns None if the context is empty or contains control characters.
+    #[cfg(feature = "std")]
+    pub fn sign_with_context(
+        message: &str,
+        context: Option<&str>,
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+    ) -> Option<Self> {
+        let options = SigningOptions {
+            context: context.map(str::to_string),
+            ..SigningOptions::default()
+        };
+        Self::sign_with_options(message, my_key, other_keys, &options)
+    }
+
+    /// Sign a message in the format version and with the features chosen in `options`. Returns
+    /// None if the options are invalid, or if they ask for something the version doesn't support.
+    ///
+    /// Header names must be at most 64 ASCII letters, digits and hyphens (starting with a letter),
+    /// may not repeat (ignoring case), and can't be "Context", "Claimable", "Disavowable",
+    /// "Scheme" or "Canonicalization" (again ignoring case). Header values, like contexts, must be
+    /// non-empty and free of control characters.
+    #[cfg(feature = "std")]
+    pub fn sign_with_options(
+        message: &str,
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+        options: &SigningOptions,
+    ) -> Option<Self> {
+        Self::sign_with_options_and_rng(message, my_key, other_keys, options, &mut OsRng)
+    }
+
+    /// Like `sign_with_options`, but with randomness from `rng` rather than from the operating
+    /// system. Signing with a seeded RNG is deterministic, which is useful for tests; otherwise,
+    /// `rng` must be cryptographically secure, or the signature may reveal the private key (unless
+    /// `options.hedged` is set).
+    pub fn sign_with_options_and_rng(
+        message: &str,
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+        options: &SigningOptions,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<Self> {
+        let version = options.version;
+        let context = options.context.as_deref();
+        if let Some(context) = context {
+            if !version.supports_context() || !valid_context(context) {
+                return None;
+            }
+        }
+        let headers = &options.headers;
+        if !headers.is_empty() && (!version.supports_headers() || !valid_headers(headers)) {
+            return None;
+        }
+        let scheme = options.scheme;
+        if (options.claimable && !version.supports_claims())
+            || (options.disavowable
+                && (!version.supports_disavowal() || !scheme.supports_key_images()))
+            || !version.supports_scheme(scheme)
+            || !version.supports_canonicalization(options.canonicalization)
+        {
+            return None;
+        }
+        let canonicalization = options.canonicalization;
+        let signed_text = canonicalization.apply(message);
         let my_public_key = my_key.public();
         // If someone selected both their public and private key, we don't want to give them away
         // by including both in the ring.
@@ -433,55 +1094,267 @@ impl SignedMessage {
             .filter(|k| k != &my_public_key)
             .collect::<Vec<_>>();
 
-        let sig = Signature::sign(
-            message.as_bytes(),
-            my_key.key.clone(),
-            &other_keys
-                .iter()
-                .map(|k| k.keypoint.clone())
-                .collect::<Vec<_>>(),
-        );
+        let ring = make_ring(my_public_key.clone(), &other_keys, |k| k.keypoint.clone());
+        let claim_commitment = if options.claimable {
+            let token = claim::derive_token(
+                &my_key.key,
+                signed_text.as_bytes(),
+                context,
+                headers,
+                scheme,
+                canonicalization,
+                ring.iter().map(|k| &k.keypoint),
+            );
+            Some(claim::commitment(&token, &my_public_key.keypoint))
+        } else {
+            None
+        };
 
-        let ring = make_ring(my_public_key, &other_keys, |k| k.keypoint.clone());
+        let key_image_base = if options.disavowable {
+            Some(disavowal::base_point(
+                transcript(
+                    version,
+                    signed_text.as_bytes(),
+                    context,
+                    headers,
+                    claim_commitment.as_ref(),
+                    None,
+                    scheme,
+                    canonicalization,
+                ),
+                ring.iter().map(|k| &k.keypoint),
+            ))
+        } else {
+            None
+        };
+        let key_image = key_image_base
+            .as_ref()
+            .map(|base| my_key.key.clone() * base);
+
+        let signed_transcript = transcript(
+            version,
+            signed_text.as_bytes(),
+            context,
+            headers,
+            claim_commitment.as_ref(),
+            key_image.as_ref(),
+            scheme,
+            canonicalization,
+        );
+        let keypoints = ring.iter().map(|k| k.keypoint.clone()).collect::<Vec<_>>();
+        let tag = key_image_base.as_ref().zip(key_image.as_ref());
+        let proof = if options.hedged {
+            let mut rng = HedgedRng::new(
+                rng,
+                &my_key.key,
+                signed_transcript.hash_with_ring(keypoints.iter()),
+            );
+            RingProof::new(
+                scheme,
+                signed_transcript,
+                &my_key.key,
+                &keypoints,
+                tag,
+                &mut rng,
+            )
+        } else {
+            RingProof::new(scheme, signed_transcript, &my_key.key, &keypoints, tag, rng)
+        };
 
-        SignedMessage {
+        Some(SignedMessage {
             message: message.to_string(),
-            challenge: sig.challenge.clone(),
-            ring: sig
-                .ring_responses
-                .clone()
-                .into_iter()
-                .zip(ring)
-                .map(|((_, s), p)| (p, s))
-                .collect(),
-        }
+            version,
+            canonicalization,
+            context: context.map(str::to_string),
+            headers: headers.clone(),
+            claim_commitment,
+            key_image,
+            ring,
+            proof,
+            timestamp_token: None,
+        })
     }
 
     pub fn verify(&self) -> bool {
         // 1. Verify that the public key attestations are valid
-        for (k, _) in self.ring.iter() {
+        for k in self.ring.iter() {
             if !k.validate_attestation() {
                 return false;
             }
         }
 
         // 2. Verify the signature itself
-        self.signature().verify(self.message.as_bytes())
+        self.verify_proof()
     }
 
-    fn signature(&self) -> Signature {
-        Signature {
-            challenge: self.challenge.clone(),
-            ring_responses: self
-                .ring
-                .iter()
-                .map(|(k, s)| (k.keypoint.clone(), s.clone()))
-                .collect(),
+    /// Whether every field is valid, and supported by the message's format version and scheme.
+    /// Fields that a version doesn't support aren't part of its transcript, so if they were let
+    /// through, anyone could add them to a signed message.
+    fn has_supported_fields(&self) -> bool {
+        let version = self.version;
+        version.supports_scheme(self.scheme())
+            && version.supports_canonicalization(self.canonicalization)
+            && self
+                .context
+                .as_deref()
+                .filter(|c| !version.supports_context() || !valid_context(c))
+                .is_none()
+            && (self.headers.is_empty()
+                || (version.supports_headers() && valid_headers(&self.headers)))
+            && (self.claim_commitment.is_none() || version.supports_claims())
+            && (self.key_image.is_none()
+                || (version.supports_disavowal() && self.scheme().supports_key_images()))
+    }
+
+    /// Verify the ring signature, but not the ring members' attestations.
+    fn verify_proof(&self) -> bool {
+        let keypoints = self
+            .ring
+            .iter()
+            .map(|k| k.keypoint.clone())
+            .collect::<Vec<_>>();
+        let base = self.key_image.as_ref().map(|_| self.disavowal_base());
+        let signed_text = self.signed_text();
+        self.has_supported_fields()
+            && self.proof.verify(
+                self.transcript(&signed_text),
+                &keypoints,
+                base.as_ref().zip(self.key_image.as_ref()),
+            )
+    }
+
+    /// If this message's proof can be checked as part of a larger multiscalar multiplication (see
+    /// `OneOfManyProof::verification_terms`), the terms that must sum to the identity. Otherwise
+    /// (for SAG signatures, and malformed proofs), None, and it has to be checked with
+    /// `verify_proof`.
+    fn batchable_terms(&self) -> Option<one_of_many::VerificationTerms> {
+        match self.proof {
+            RingProof::OneOfMany(ref proof) if self.has_supported_fields() => {
+                let keypoints = self
+                    .ring
+                    .iter()
+                    .map(|k| k.keypoint.clone())
+                    .collect::<Vec<_>>();
+                proof.verification_terms(
+                    self.transcript(&self.signed_text())
+                        .hash_with_ring(keypoints.iter()),
+                    &keypoints,
+                )
+            }
+            _ => None,
         }
     }
 
+    /// Verify the signature, and also that it was made for the expected context (or for no
+    /// context at all, if `expected_context` is None).
+    pub fn verify_with_context(&self, expected_context: Option<&str>) -> bool {
+        self.context.as_deref() == expected_context && self.verify()
+    }
+
+    /// The context that this message was signed for, if any.
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// The signed headers, as (name, value) pairs in the order they were signed.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// The value of the signed header with the given name (ignoring case), if there is one.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the signer can later prove that they signed this message, using an
+    /// `AuthorshipClaim`. Nobody else in the ring can produce such a claim.
+    pub fn is_claimable(&self) -> bool {
+        self.claim_commitment.is_some()
+    }
+
+    /// Whether the other ring members can prove that they didn't sign this message, using a
+    /// `DisavowalProof`.
+    pub fn is_disavowable(&self) -> bool {
+        self.key_image.is_some()
+    }
+
+    /// Check that `proof` shows that its disavower, a member of this message's ring, did not
+    /// sign this message (which must itself verify).
+    pub fn verify_disavowal(&self, proof: &DisavowalProof) -> bool {
+        proof.verify(self)
+    }
+
+    /// The format version that this message was signed (or parsed) in.
+    pub fn version(&self) -> FormatVersion {
+        self.version
+    }
+
+    /// The ring signature scheme that this message was signed with.
+    pub fn scheme(&self) -> RingScheme {
+        self.proof.scheme()
+    }
+
+    /// How this message's text was canonicalized before it was signed.
+    pub fn canonicalization(&self) -> Canonicalization {
+        self.canonicalization
+    }
+
+    /// What was actually signed: the message, as canonicalized.
+    pub(crate) fn signed_text(&self) -> Cow<'_, str> {
+        self.canonicalization.apply(&self.message)
+    }
+
+    /// The transcript that this message's proof signs, given its `signed_text`.
+    fn transcript<'a>(&'a self, signed_text: &'a str) -> Transcript<'a> {
+        transcript(
+            self.version,
+            signed_text.as_bytes(),
+            self.context.as_deref(),
+            &self.headers,
+            self.claim_commitment.as_ref(),
+            self.key_image.as_ref(),
+            self.scheme(),
+            self.canonicalization,
+        )
+    }
+
+    /// The base point H that key images for this message are computed on. It's derived from
+    /// everything that's signed except the key image itself, so each message (and ring) gets its
+    /// own: otherwise, anyone could tell when two messages were signed with the same key.
+    fn disavowal_base(&self) -> RistrettoPoint {
+        disavowal::base_point(
+            transcript(
+                self.version,
+                self.signed_text().as_bytes(),
+                self.context.as_deref(),
+                &self.headers,
+                self.claim_commitment.as_ref(),
+                None,
+                self.scheme(),
+                self.canonicalization,
+            ),
+            self.ring().map(|k| &k.keypoint),
+        )
+    }
+
     pub fn ring(&self) -> impl Iterator<Item = &PublicKey> {
-        self.ring.iter().map(|(k, _)| k)
+        self.ring.iter()
+    }
+
+    /// The RFC 3161 timestamp token attached to this message, if any. It isn't covered by the
+    /// signature, and `verify` doesn't check it; see the zebra_timestamp crate.
+    pub fn timestamp_token(&self) -> Option<&[u8]> {
+        self.timestamp_token.as_deref()
+    }
+
+    /// Attach a DER-encoded RFC 3161 timestamp token, whose message imprint is a hash of this
+    /// message's Borsh encoding (which never includes the token), or remove it with None. An empty
+    /// token is the same as None.
+    pub fn set_timestamp_token(&mut self, token: Option<Vec<u8>>) {
+        self.timestamp_token = token.filter(|t| !t.is_empty());
     }
 }
 
@@ -538,47 +1411,132 @@ impl SignedMessage {
 // necessarily divisible into 4-byte chunks, our protocol relies on the padding implementation in
 // that specific library. The implementation can be seen here:
 // https://github.com/decafbad/z85/blob/ca669a0682b0a559b883f770c93e746f6a7e3ebe/src/internal.rs#L51
-
-const SIGNED_MESSAGE_FIRST_LINE: &str =
-    "The following message has been signed using Zebra 1.0:";
+//
+// Later versions use the same layout, except that the first line names the version (see
+// `FormatVersion::first_line`), and, if the version supports it, may be followed by a single
+// "Context: ..." line before the opening quotes. The context can't contain newlines, and can't be
+// a line of quotes, so the message still starts unambiguously after the first quote line. A message
+// with a context is at least 13 lines long.
+//
+// Versions that support signed headers may also have any number of "Name: value" header lines after
+// the context line (if any), again before the opening quotes. See `valid_header_name` for why
+// these can't be confused with the context line or the quotes.
+//
+// A claimable message has a "Claimable: ..." line (with the claim commitment as 64 uppercase hex
+// digits) between the context line and the headers. Similarly, a disavowable message has a
+// "Disavowable: ..." line with the key image, after that. These names are also reserved, so they
+// can't be mistaken for headers.
+//
+// A message signed with any scheme other than SAG has a "Scheme: ..." line naming it, after those.
+// Its signature data is then the ring followed by the scheme's proof, rather than SAG's challenge
+// followed by (key, response) pairs.
+//
+// A message signed with any canonicalization other than exact has a "Canonicalization: ..." line
+// naming it, after the scheme line (if any). The message between the quotes is then whatever text
+// the verifier was given, and it's canonicalized again before verifying. Since canonical text is
+// meant to survive its line endings and trailing whitespace being changed, if the parser can't make
+// sense of a message as it is, it tries again with CRLF line endings made LF, and trailing
+// whitespace removed from every line; that only succeeds if the result is canonical text.
+//
+// A message with a timestamp token attached has three more lines after the signature data: a
+// blank line, SIGNED_MESSAGE_TIMESTAMP_LINE, and the token (z85 encoded), before the fixed suffix.
+// Without a token, the line before the suffix's blank line is always the signature data, so the
+// two can't be confused. The token isn't signed, so messages of any version can have one.
+
+const SIGNED_MESSAGE_RESERVED_NAMES: &[&str] = &[
+    "Context",
+    "Claimable",
+    "Disavowable",
+    "Scheme",
+    "Canonicalization",
+];
+const SIGNED_MESSAGE_CONTEXT_PREFIX: &str = "Context: ";
+const SIGNED_MESSAGE_CLAIMABLE_PREFIX: &str = "Claimable: ";
+const SIGNED_MESSAGE_DISAVOWABLE_PREFIX: &str = "Disavowable: ";
+const SIGNED_MESSAGE_SCHEME_PREFIX: &str = "Scheme: ";
+const SIGNED_MESSAGE_CANONICALIZATION_PREFIX: &str = "Canonicalization: ";
+const SIGNED_MESSAGE_HEADER_SEPARATOR: &str = ": ";
 const SIGNED_MESSAGE_SECOND_LINE: &str = "\"\"\"";
 const SIGNED_MESSAGE_INFIX_FIRST_LINE: &str = "\"\"\"";
 const SIGNED_MESSAGE_INFIX_SECOND_LINE: &str = "";
 const SIGNED_MESSAGE_INFIX_THIRD_LINE: &str =
     "It was signed by someone with a private key corresponding to one of these fingerprints:";
 const SIGNED_MESSAGE_INFIX_FOURTH_LINE: &str = "";
+const SIGNED_MESSAGE_TIMESTAMP_LINE: &str =
+    "It was timestamped by a timestamping authority, with this RFC 3161 token:";
 const SIGNED_MESSAGE_SUFFIX_FIRST_LINE: &str = "";
 const SIGNED_MESSAGE_SUFFIX_SECOND_LINE: &str = "To verify this signature, paste this entire message into the Zebra app (starting with \"The following message\" and ending with this line).";
 
 impl From<&SignedMessage> for String {
     fn from(m: &SignedMessage) -> String {
-        let mut parts = vec![
-            SIGNED_MESSAGE_FIRST_LINE.to_string(),
-            SIGNED_MESSAGE_SECOND_LINE.to_string(),
-        ];
+        let mut parts = vec![m.version.first_line()];
+        if let Some(ref context) = m.context {
+            parts.push(format!("{SIGNED_MESSAGE_CONTEXT_PREFIX}{context}"));
+        }
+        if let Some(ref commitment) = m.claim_commitment {
+            parts.push(format!(
+                "{SIGNED_MESSAGE_CLAIMABLE_PREFIX}{}",
+                hex::encode_upper(commitment)
+            ));
+        }
+        if let Some(ref image) = m.key_image {
+            parts.push(format!(
+                "{SIGNED_MESSAGE_DISAVOWABLE_PREFIX}{}",
+                hex::encode_upper(image.compress())
+            ));
+        }
+        if m.scheme() != RingScheme::Sag {
+            parts.push(format!(
+                "{SIGNED_MESSAGE_SCHEME_PREFIX}{}",
+                m.scheme().name()
+            ));
+        }
+        if m.canonicalization != Canonicalization::Exact {
+            parts.push(format!(
+                "{SIGNED_MESSAGE_CANONICALIZATION_PREFIX}{}",
+                m.canonicalization.name()
+            ));
+        }
+        for (name, value) in m.headers.iter() {
+            parts.push(format!("{name}{SIGNED_MESSAGE_HEADER_SEPARATOR}{value}"));
+        }
+        parts.push(SIGNED_MESSAGE_SECOND_LINE.to_string());
         parts.push(m.message.clone());
 
         parts.push(SIGNED_MESSAGE_INFIX_FIRST_LINE.to_string());
         parts.push(SIGNED_MESSAGE_INFIX_SECOND_LINE.to_string());
         parts.push(SIGNED_MESSAGE_INFIX_THIRD_LINE.to_string());
         parts.push(SIGNED_MESSAGE_INFIX_FOURTH_LINE.to_string());
-        for (k, s) in m.ring.iter() {
-            let mut scalar_bytes = vec![];
-            s.serialize(&mut scalar_bytes)
-                .expect("Failed to serialize scalar into unbounded buffer");
-            parts.push(format!(
-                "{} <{}> {}",
-                k.holder.name(),
-                k.holder.email(),
-                k.fingerprint()
-            ));
+        for k in m.ring.iter() {
+            parts.push(key_line(k));
         }
         let mut signature_bytes = vec![];
-        (m.challenge.clone(), m.ring.clone())
-            .serialize(&mut signature_bytes)
-            .expect("Failed to serialize scalar into unbounded buffer");
+        match m.proof {
+            // The original layout, from before there were other schemes.
+            RingProof::Sag {
+                ref challenge,
+                ref responses,
+            } => (
+                challenge.clone(),
+                m.ring
+                    .iter()
+                    .cloned()
+                    .zip(responses.iter().cloned())
+                    .collect::<Vec<_>>(),
+            )
+                .serialize(&mut signature_bytes),
+            RingProof::OneOfMany(ref proof) => {
+                (m.ring.clone(), proof.clone()).serialize(&mut signature_bytes)
+            }
+        }
+        .expect("Failed to serialize scalar into unbounded buffer");
         parts.push("".to_string());
         parts.push(z85::encode(&signature_bytes));
+        if let Some(ref token) = m.timestamp_token {
+            parts.push("".to_string());
+            parts.push(SIGNED_MESSAGE_TIMESTAMP_LINE.to_string());
+            parts.push(z85::encode(token));
+        }
         parts.push(SIGNED_MESSAGE_SUFFIX_FIRST_LINE.to_string());
         parts.push(SIGNED_MESSAGE_SUFFIX_SECOND_LINE.to_string());
         parts.join("\n")
@@ -590,93 +1548,237 @@ impl FromStr for SignedMessage {
     /// IMPORTANT NOTE: Success of this method does *not* imply a valid signature, only a
     /// syntactically correct one.
     fn from_str(s: &str) -> Result<SignedMessage, ()> {
-        // Here's the same signed message from above, reproduced to make it easier to follow the
-        // parsing algorithm:
-        /*
-          (0)         The following message has been signed using Zebra 1.0:
-          (1)         """
-          (2)         Test
-          (M+2)       """
-          (M+3)
-          (M+4)       It was signed by someone with a private key corresponding to one of these fingerprints:
-          (M+5)
-          (M+5+1)     Ben Weinstein-Raun <b@w-r.me> Z:$p&B{etV [J3I^)6^#h +4dJaeg6Q. kn-O]{7[tH
-          (M+5+N)     Joe Camel <cool@tobacco.com> :z6N5iF%x] OZV9Q-p^0C 0c0*l1i0u/ <EgnZFy!44
-          (M+5+N+1)
-          (M+5+N+2)   9%Kq+rztr@G/UUZwbP>Z7>&V*av.io+RoI^sPb&o0SSi25=.[ils>3Ss7M8-B97#czy[:{B-4D+003E[Cp^?/zdP/q!aTb+yC+&9/L5.?QFe<N&)li1*NhhYPI[LV.AhV!}*:H2!bn+D4UbI41^@[(bwbQo.H-G&Twp7%IWfs-0069?!aTb+yC+&9/L5.?QFe<N&)li1*NhhYPI[LV.Aiwihg7Yu/b[sVh2J10vI*p]H[S*gekCK-Dmz-%@0n2*}o{}4Ieyfk]hs*-j2Bx<pnD&qD0LlxRmz:?5DJgr002SfwGTuGzdNI{0001ez/oCSBz>R%v}fBfv@Dkx=*PEupvK+z::^HobOkJ[Lr%JJ]puzjY<ELiZ7-&[RpE^E2h^OKLus#2kE%Cj7j%m<z=@>!2OE#</y5?y0002S=*PEupvK+z::^HobOkJ[Lr%JJ]puzjY<ELiZ7-*^4/Af&IVi)R2moE@aE(&{@:wiKNF*Rr0q<6G8k4s6L3zuhs!s8N&9nG(NCOYtp$me1aj.^gt$f7w#4*}O
-          (M+5+N+3)
-          (M+5+N+4) To verify this signature, paste this entire message into the Zebra app (starting with "The following message" and ending with this line).
-        */
-
-        // This could have been done with regular expressions or a parser library. I intentionally
-        // designed the ASCII format to be fairly simple to reason about; my hope is that this
-        // manual parser succeeds at being easier to understand than a BNF-ish or regex-based
-        // parser.
-        let lines = s.trim().split('\n').collect::<Vec<_>>();
-        if lines.len() < 12 {
-            // The shortest allowed signed message has a single signer and one (possibly empty)
-            // line of message text. This corresponds to M = N = 1, so 1 + 5 + 1 + 4 + 1 = 12 lines.
+        parse_signed_message(s).or_else(|()| {
+            // Canonical text may have had its line endings and trailing whitespace changed along the
+            // way (see above).
+            let cleaned = s.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+            let message = parse_signed_message(&cleaned)?;
+            if message.canonicalization == Canonicalization::Exact {
+                return Err(());
+            }
+            Ok(message)
+        })
+    }
+}
+
+/// Parse a signed message in the ASCII format, exactly as it's given.
+fn parse_signed_message(s: &str) -> Result<SignedMessage, ()> {
+    // Here's the same signed message from above, reproduced to make it easier to follow the
+    // parsing algorithm:
+    /*
+      (0)         The following message has been signed using Zebra 1.0:
+      (1)         """
+      (2)         Test
+      (M+2)       """
+      (M+3)
+      (M+4)       It was signed by someone with a private key corresponding to one of these fingerprints:
+      (M+5)
+      (M+5+1)     Ben Weinstein-Raun <b@w-r.me> Z:$p&B{etV [J3I^)6^#h +4dJaeg6Q. kn-O]{7[tH
+      (M+5+N)     Joe Camel <cool@tobacco.com> :z6N5iF%x] OZV9Q-p^0C 0c0*l1i0u/ <EgnZFy!44
+      (M+5+N+1)
+      (M+5+N+2)   9%Kq+rztr@G/UUZwbP>Z7>&V*av.io+RoI^sPb&o0SSi25=.[ils>3Ss7M8-B97#czy[:{B-4D+003E[Cp^?/zdP/q!aTb+yC+&9/L5.?QFe<N&)li1*NhhYPI[LV.AhV!}*:H2!bn+D4UbI41^@[(bwbQo.H-G&Twp7%IWfs-0069?!aTb+yC+&9/L5.?QFe<N&)li1*NhhYPI[LV.Aiwihg7Yu/b[sVh2J10vI*p]H[S*gekCK-Dmz-%@0n2*}o{}4Ieyfk]hs*-j2Bx<pnD&qD0LlxRmz:?5DJgr002SfwGTuGzdNI{0001ez/oCSBz>R%v}fBfv@Dkx=*PEupvK+z::^HobOkJ[Lr%JJ]puzjY<ELiZ7-&[RpE^E2h^OKLus#2kE%Cj7j%m<z=@>!2OE#</y5?y0002S=*PEupvK+z::^HobOkJ[Lr%JJ]puzjY<ELiZ7-*^4/Af&IVi)R2moE@aE(&{@:wiKNF*Rr0q<6G8k4s6L3zuhs!s8N&9nG(NCOYtp$me1aj.^gt$f7w#4*}O
+      (M+5+N+3)
+      (M+5+N+4) To verify this signature, paste this entire message into the Zebra app (starting with "The following message" and ending with this line).
+    */
+
+    // This could have been done with regular expressions or a parser library. I intentionally
+    // designed the ASCII format to be fairly simple to reason about; my hope is that this
+    // manual parser succeeds at being easier to understand than a BNF-ish or regex-based
+    // parser.
+    let mut lines = s.trim().split('\n').collect::<Vec<_>>();
+
+    // Take out the timestamp token's lines, if there are any, leaving the usual layout.
+    let mut timestamp_token = None;
+    if lines.len() >= 15
+        && lines[lines.len() - 4] == SIGNED_MESSAGE_TIMESTAMP_LINE
+        && lines[lines.len() - 5].is_empty()
+    {
+        let line = lines[lines.len() - 3];
+        let token = z85::decode(line).ok_or(())?;
+        // Only one encoding of each token is allowed.
+        if token.is_empty() || z85::encode(&token) != line {
             return Err(());
         }
+        timestamp_token = Some(token);
+        lines.drain(lines.len() - 5..lines.len() - 2);
+    }
+
+    if lines.len() < 12 {
+        // The shortest allowed signed message has a single signer and one (possibly empty)
+        // line of message text. This corresponds to M = N = 1, so 1 + 5 + 1 + 4 + 1 = 12 lines.
+        return Err(());
+    }
 
-        // Check the fixed prefix (lines 0 and 1)
-        if lines[0] != SIGNED_MESSAGE_FIRST_LINE || lines[1] != SIGNED_MESSAGE_SECOND_LINE {
+    // Check the fixed prefix (lines 0 and 1). The first line determines the format version, and
+    // in versions that support it, there may be a context line in between.
+    let version = FormatVersion::from_first_line(lines[0]).ok_or(())?;
+    let mut context = None;
+    let mut message_start = 2;
+    if version.supports_context() {
+        if let Some(c) = lines[1].strip_prefix(SIGNED_MESSAGE_CONTEXT_PREFIX) {
+            if !valid_context(c) {
+                return Err(());
+            }
+            context = Some(c.to_string());
+            message_start += 1;
+        }
+    }
+    let mut claim_commitment = None;
+    if version.supports_claims() {
+        if let Some(c) = lines[message_start - 1].strip_prefix(SIGNED_MESSAGE_CLAIMABLE_PREFIX) {
+            claim_commitment = Some(parse_hex_32(c)?);
+            message_start += 1;
+        }
+    }
+    let mut key_image = None;
+    if version.supports_disavowal() {
+        if let Some(i) = lines[message_start - 1].strip_prefix(SIGNED_MESSAGE_DISAVOWABLE_PREFIX) {
+            let bytes = parse_hex_32(i)?;
+            key_image = Some(RistrettoPoint::try_from_slice(&bytes).map_err(|_| ())?);
+            message_start += 1;
+        }
+    }
+    let mut scheme = RingScheme::Sag;
+    if let Some(name) = lines[message_start - 1].strip_prefix(SIGNED_MESSAGE_SCHEME_PREFIX) {
+        // SAG is never named, so that each message has exactly one encoding.
+        scheme = RingScheme::from_name(name).ok_or(())?;
+        if scheme == RingScheme::Sag || !version.supports_scheme(scheme) {
             return Err(());
         }
-        // Check the fixed suffix (lines M+5+N+3 and M+5+N+4; a.k.a. lines.len() - 2 and
-        // lines.len() - 1. Then, also check the blank line before the signature data (M+5+N+1 =
-        // lines.len() - 3)
-        if lines[lines.len() - 1] != SIGNED_MESSAGE_SUFFIX_SECOND_LINE
-            || lines[lines.len() - 2] != SIGNED_MESSAGE_SUFFIX_FIRST_LINE
-            || !lines[lines.len() - 4].is_empty()
+        message_start += 1;
+    }
+    let mut canonicalization = Canonicalization::Exact;
+    if let Some(name) =
+        lines[message_start - 1].strip_prefix(SIGNED_MESSAGE_CANONICALIZATION_PREFIX)
+    {
+        // Likewise, exact is never named.
+        canonicalization = Canonicalization::from_name(name).ok_or(())?;
+        if canonicalization == Canonicalization::Exact
+            || !version.supports_canonicalization(canonicalization)
         {
             return Err(());
         }
+        message_start += 1;
+    }
+    let mut headers = vec![];
+    if version.supports_headers() {
+        // Everything up to the opening quotes is a header. The checks below guarantee that
+        // there are at least 10 more lines after the quotes, so we can't run off the end.
+        while message_start + 10 <= lines.len()
+            && lines[message_start - 1] != SIGNED_MESSAGE_SECOND_LINE
+        {
+            let (name, value) = lines[message_start - 1]
+                .split_once(SIGNED_MESSAGE_HEADER_SEPARATOR)
+                .ok_or(())?;
+            headers.push((name.to_string(), value.to_string()));
+            message_start += 1;
+        }
+        if !valid_headers(&headers) {
+            return Err(());
+        }
+    }
+    if lines[message_start - 1] != SIGNED_MESSAGE_SECOND_LINE {
+        return Err(());
+    }
+    // Check the fixed suffix (lines M+5+N+3 and M+5+N+4; a.k.a. lines.len() - 2 and
+    // lines.len() - 1. Then, also check the blank line before the signature data (M+5+N+1 =
+    // lines.len() - 3)
+    if lines[lines.len() - 1] != SIGNED_MESSAGE_SUFFIX_SECOND_LINE
+        || lines[lines.len() - 2] != SIGNED_MESSAGE_SUFFIX_FIRST_LINE
+        || !lines[lines.len() - 4].is_empty()
+    {
+        return Err(());
+    }
 
-        // extract data from the signature line (line M+5+N+2 = lines.len() - 3)
-        let signature_bytes = match z85::decode(lines[lines.len() - 3]) {
-            Ok(val) => val,
-            Err(_) => return Err(()),
-        };
-
-        let (challenge, ring) = match <(Scalar, Vec<(PublicKey, Scalar)>)>::deserialize(
-            &mut signature_bytes.as_slice(),
-        ) {
-            Ok(x) => x,
-            Err(_) => return Err(()),
-        };
-
-        // Verify that the ring in the signature data exactly matches the data in the text:
-        // (lines M+5+1 through M+5+N; a.k.a. lines.len() - 5 - (N - 1) through lines.len() - 5
-        for (i, (signer, _)) in ring.iter().rev().enumerate() {
-            if lines[lines.len() - 5 - i]
-                != format!(
-                    "{} <{}> {}",
-                    signer.holder.name(),
-                    signer.holder.email(),
-                    signer.fingerprint()
-                )
-            {
-                return Err(());
-            }
+    // extract data from the signature line (line M+5+N+2 = lines.len() - 3)
+    let signature_bytes = match z85::decode(lines[lines.len() - 3]) {
+        Some(val) => val,
+        None => return Err(()),
+    };
+
+    let (ring, proof) = match scheme {
+        RingScheme::Sag => {
+            let (challenge, ring) =
+                <(Scalar, Vec<(PublicKey, Scalar)>)>::deserialize(&mut signature_bytes.as_slice())
+                    .map_err(|_| ())?;
+            let (ring, responses) = ring.into_iter().unzip();
+            (
+                ring,
+                RingProof::Sag {
+                    challenge,
+                    responses,
+                },
+            )
+        }
+        RingScheme::OneOfMany => {
+            let (ring, proof) =
+                <(Vec<PublicKey>, OneOfManyProof)>::try_from_slice(&signature_bytes)
+                    .map_err(|_| ())?;
+            (ring, RingProof::OneOfMany(proof))
         }
+    };
 
-        // check the fixed lines between the ring info and the message (M+2 through M+5, a.k.a.
-        // lines.len() - 5 - ring.len() through lines.len() - 5 - ring.len() - 3)
-        if lines[lines.len() - 5 - ring.len()] != SIGNED_MESSAGE_INFIX_FOURTH_LINE
-            || lines[lines.len() - 5 - ring.len() - 1] != SIGNED_MESSAGE_INFIX_THIRD_LINE
-            || lines[lines.len() - 5 - ring.len() - 2] != SIGNED_MESSAGE_INFIX_SECOND_LINE
-            || lines[lines.len() - 5 - ring.len() - 3] != SIGNED_MESSAGE_INFIX_FIRST_LINE
-        {
+    // Make sure there are enough lines for the ring info and at least one line of message, so
+    // that the indexing below can't go out of bounds.
+    if lines.len() < message_start + 1 + 4 + ring.len() + 4 {
+        return Err(());
+    }
+
+    // Verify that the ring in the signature data exactly matches the data in the text:
+    // (lines M+5+1 through M+5+N; a.k.a. lines.len() - 5 - (N - 1) through lines.len() - 5
+    for (i, signer) in ring.iter().rev().enumerate() {
+        if lines[lines.len() - 5 - i] != key_line(signer) {
             return Err(());
         }
+    }
 
-        Ok(SignedMessage {
-            message: lines[2..lines.len() - 5 - ring.len() - 3].join("\n"),
-            challenge,
-            ring,
-        })
+    // check the fixed lines between the ring info and the message (M+2 through M+5, a.k.a.
+    // lines.len() - 5 - ring.len() through lines.len() - 5 - ring.len() - 3)
+    if lines[lines.len() - 5 - ring.len()] != SIGNED_MESSAGE_INFIX_FOURTH_LINE
+        || lines[lines.len() - 5 - ring.len() - 1] != SIGNED_MESSAGE_INFIX_THIRD_LINE
+        || lines[lines.len() - 5 - ring.len() - 2] != SIGNED_MESSAGE_INFIX_SECOND_LINE
+        || lines[lines.len() - 5 - ring.len() - 3] != SIGNED_MESSAGE_INFIX_FIRST_LINE
+    {
+        return Err(());
+    }
+
+    let signed = SignedMessage {
+        message: lines[message_start..lines.len() - 5 - ring.len() - 3].join("\n"),
+        version,
+        canonicalization,
+        context,
+        headers,
+        claim_commitment,
+        key_image,
+        ring,
+        proof,
+        timestamp_token,
+    };
+    // The checks above only let through fields the version supports, but a key image can still
+    // have come with a scheme that can't be tagged with one.
+    if !signed.has_supported_fields() {
+        return Err(());
     }
+    Ok(signed)
+}
+
+// A key's name, email, and fingerprint, as they appear on one line of the ASCII formats.
+fn key_line(k: &PublicKey) -> String {
+    format!(
+        "{} <{}> {}",
+        k.holder.name(),
+        k.holder.email(),
+        k.fingerprint()
+    )
+}
+
+// Only uppercase hex is allowed, so that each message has exactly one encoding.
+fn parse_hex_32(s: &str) -> Result<[u8; 32], ()> {
+    let bytes = hex::decode(s).map_err(|_| ())?;
+    if hex::encode_upper(&bytes) != s {
+        return Err(());
+    }
+    <[u8; 32]>::try_from(bytes).map_err(|_| ())
 }
 
 #[cfg(test)]
@@ -686,24 +1788,30 @@ mod tests {
     #[test]
     fn basic_signatures_work() {
         let message = b"Message";
-        let my_key = Scalar::random();
-        let signature = Signature::sign(message, my_key.clone(), &[]);
+        let my_key = Scalar::random(&mut OsRng);
+        let signature = Signature::sign(message, my_key.clone(), &[], &mut OsRng);
         assert!(
             signature.verify(message),
             "Failed to verify one-key signature"
         );
 
         let message_a = b"Message A";
-        let otherkey_a = RistrettoPoint::random();
-        let signature_a = Signature::sign(message_a, my_key.clone(), &[otherkey_a.clone()]);
+        let otherkey_a = RistrettoPoint::random(&mut OsRng);
+        let signature_a =
+            Signature::sign(message_a, my_key.clone(), &[otherkey_a.clone()], &mut OsRng);
         assert!(
             signature_a.verify(message_a),
             "Failed to verify two-key signature"
         );
 
         let message_b = b"Message B";
-        let otherkey_b = RistrettoPoint::random();
-        let signature_b = Signature::sign(message_b, my_key, &[otherkey_a.clone(), otherkey_b]);
+        let otherkey_b = RistrettoPoint::random(&mut OsRng);
+        let signature_b = Signature::sign(
+            message_b,
+            my_key,
+            &[otherkey_a.clone(), otherkey_b],
+            &mut OsRng,
+        );
         assert!(
             signature_b.verify(message_b),
             "Failed to verify three-key signature"
@@ -756,11 +1864,53 @@ mod tests {
             ref holder,
             ref keypoint,
             ref holder_attestation,
+            ref fingerprint,
         } = import.unwrap();
 
         assert!(holder == &my_key.holder);
         assert!(keypoint == &my_key.public().keypoint);
         assert!(holder_attestation == holder_attestation);
+        assert!(fingerprint == &my_key.public().fingerprint());
+
+        let decoded = PublicKey::try_from_slice(&borsh::to_vec(&my_key.public()).unwrap()).unwrap();
+        assert!(decoded.fingerprint() == my_key.public().fingerprint());
+
+        // Names can contain anything but control characters, including the delimiters.
+        let tricky_id = Identity::new("Zebra <the> [horse] > <", &my_email).unwrap();
+        let tricky_export = String::from(PrivateKey::new(tricky_id.clone()).public());
+        assert!(PublicKey::from_str(&tricky_export).unwrap().holder == tricky_id);
+
+        for bad in [
+            format!("{export}\n"),
+            format!(" {export}"),
+            export.replacen(' ', "  ", 1),
+            export.replacen(" <", "<", 1),
+            export.replacen('[', "", 1),
+            export.to_lowercase(),
+            export[..export.len() - 2].to_string() + "]",
+        ] {
+            assert!(PublicKey::from_str(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn z85_matches_the_z85_crate() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for len in 0..64 {
+            let mut bytes = vec![0; len];
+            rng.fill_bytes(&mut bytes);
+            let encoded = z85::encode(&bytes);
+            assert!(encoded == ::z85::encode(&bytes));
+            assert!(z85::decode(&encoded) == Some(bytes));
+        }
+        for bad in [
+            "0000", "#####", "####0", "#%nSc", "%nSc1", "##zzz", "%nSc0 ",
+        ] {
+            assert!(z85::decode(bad).is_none());
+        }
     }
 
     #[test]
@@ -786,4 +1936,1011 @@ mod tests {
         );
         assert!(SignedMessage::from_str(&signed_text) == Ok(signed));
     }
+
+    // Produced by the original (1.0-only) implementation.
+    const V1_SIGNED_MESSAGE: &str = r#"The following message has been signed using Zebra 1.0:
+"""
+SPARTACVSSVM
+"""
+
+It was signed by someone with a private key corresponding to one of these fingerprints:
+
+Gaius <notzebra@example.com> 2eYG]XX<L8 /[%xQuHl>G yo[zgI%XA0 @XOnoqcZtN
+Zebra <zebra@example.com> +)WwrJlc{^ zil3Bgnt:m tE[!HwzZHz P0[sCM4Sc?
+
+JY-pr{3/K&Scx1E%3mKuF-LSX=/pNs6jgU1qu[=-0SSi21POJ5m}3aOA$!PO0b=Z%DsZMavl!yGvqP$1wIdFhzdC{+x^DDtsTxs3A!VX#7A>^KSx7-JY2YVUMncO/mB8=^5{:zWF$yYY(vKFwq>b{oH(4sDz#RIFeX[D{0%5x40aXwFx^DDtsTxs3A!VX#7A>^KSx7-JY2YVUMncO/mfOWoDK?=GXqAu{fldQT[rp<h&QFcF:<nu/PQBEx0wR-06WG{Xb%hTRmY+45)v7Uqjq2QDo@=cdBW9-f4*q}k09S@yA:YKW004sRvSC)xwQ4T2Abo8rv@Dju>]{v9TK^3Y4#m*]?oAUVDISsjQ6wL+R5tBI&CR5hwrg*JE3r}y-OU94mn/P.*pP03<6CKtKI<sVMw(yQ0001P>]{v9TK^3Y4#m*]?oAUVDISsjQ6wL+R5tBI&CR3:Z[%{Mnztv>o!XEa9!zDZK]E-P(8%(Bq/W/3}5P$sk1xe1UTfTQs{^epiw0l^2hfdWbFIE<Wt&N5#jp+/
+
+To verify this signature, paste this entire message into the Zebra app (starting with "The following message" and ending with this line)."#;
+
+    #[test]
+    fn v1_messages_still_verify() {
+        let signed = SignedMessage::from_str(V1_SIGNED_MESSAGE).unwrap();
+        assert!(signed.verify());
+        assert!(signed.context().is_none());
+        assert_eq!(signed.version(), FormatVersion::V1_0);
+        assert_eq!(String::from(&signed), V1_SIGNED_MESSAGE);
+
+        let tampered = V1_SIGNED_MESSAGE.replace("SPARTACVSSVM", "SPARTACVSEST");
+        assert!(!SignedMessage::from_str(&tampered).unwrap().verify());
+    }
+
+    #[test]
+    fn context_signatures_work() {
+        let my_id = Identity::new("Zebra", "zebra@example.com").unwrap();
+        let my_key = PrivateKey::new(my_id);
+        let other_id = Identity::new("Gaius", "notzebra@example.com").unwrap();
+        let other_public = PrivateKey::new(other_id).public();
+
+        let context = "ACME board vote 2026";
+        let signed = SignedMessage::sign_with_context(
+            "SPARTACVSSVM",
+            Some(context),
+            &my_key,
+            &[other_public],
+        )
+        .unwrap();
+        assert!(signed.verify());
+        assert!(signed.verify_with_context(Some(context)));
+        assert!(!signed.verify_with_context(None));
+        assert!(!signed.verify_with_context(Some("ACME board vote 2027")));
+
+        let signed_text = String::from(&signed);
+        assert!(signed_text.contains("Context: ACME board vote 2026\n"));
+        assert!(SignedMessage::from_str(&signed_text) == Ok(signed.clone()));
+
+        // The context is covered by the signature.
+        let moved = signed_text.replace(context, "ACME board vote 2027");
+        assert!(!SignedMessage::from_str(&moved).unwrap().verify());
+        let removed = signed_text.replace("Context: ACME board vote 2026\n", "");
+        assert!(!SignedMessage::from_str(&removed).unwrap().verify());
+
+        assert!(SignedMessage::sign_with_context("m", Some(""), &my_key, &[]).is_none());
+        assert!(SignedMessage::sign_with_context("m", Some("a\nb"), &my_key, &[]).is_none());
+    }
+
+    #[test]
+    fn versions_can_be_chosen() {
+        let my_id = Identity::new("Zebra", "zebra@example.com").unwrap();
+        let my_key = PrivateKey::new(my_id);
+
+        assert_eq!(
+            SignedMessage::sign("m", &my_key, &[]).version(),
+            FormatVersion::LATEST
+        );
+        for &version in FormatVersion::ALL {
+            let options = SigningOptions {
+                version,
+                ..SigningOptions::default()
+            };
+            let signed = SignedMessage::sign_with_options("m", &my_key, &[], &options).unwrap();
+            let parsed = SignedMessage::from_str(&String::from(&signed)).unwrap();
+            assert_eq!(parsed.version(), version);
+            assert!(parsed.verify());
+        }
+
+        let v1_with_context = SigningOptions {
+            version: FormatVersion::V1_0,
+            context: Some("context".to_string()),
+            ..SigningOptions::default()
+        };
+        assert!(SignedMessage::sign_with_options("m", &my_key, &[], &v1_with_context).is_none());
+
+        // A 1.0 message relabeled as 2.0 parses, but doesn't verify.
+        let v1 = SignedMessage::sign_with_options(
+            "m",
+            &my_key,
+            &[],
+            &SigningOptions {
+                version: FormatVersion::V1_0,
+                ..SigningOptions::default()
+            },
+        )
+        .unwrap();
+        let relabeled = String::from(&v1).replace("Zebra 1.0:", "Zebra 2.0:");
+        assert!(!SignedMessage::from_str(&relabeled).unwrap().verify());
+
+        // Fields that 1.0 doesn't sign can't be spliced into its Borsh encoding, and don't verify
+        // if they're added some other way.
+        let mut bytes = borsh::to_vec(&v1).unwrap();
+        let context_at = 4 + v1.message.len() + 1;
+        assert_eq!(bytes[context_at], 0);
+        let context = borsh::to_vec(&Some("ACME board vote".to_string())).unwrap();
+        bytes.splice(context_at..context_at + 1, context);
+        assert!(SignedMessage::try_from_slice(&bytes).is_err());
+        let mut with_context = v1.clone();
+        with_context.context = Some("ACME board vote".to_string());
+        assert!(!with_context.verify_with_context(Some("ACME board vote")));
+        let mut with_headers = v1.clone();
+        with_headers.headers = vec![("Date".to_string(), "2026-01-01".to_string())];
+        assert!(!with_headers.verify());
+    }
+
+    #[test]
+    fn headers_are_signed() {
+        let my_id = Identity::new("Zebra", "zebra@example.com").unwrap();
+        let my_key = PrivateKey::new(my_id);
+        let options = SigningOptions {
+            context: Some("context".to_string()),
+            headers: vec![
+                ("Date".to_string(), "2026-10-18T12:00:00Z".to_string()),
+                ("Subject".to_string(), "Re: lunch".to_string()),
+            ],
+            ..SigningOptions::default()
+        };
+        let signed = SignedMessage::sign_with_options("message", &my_key, &[], &options).unwrap();
+        let armored = String::from(&signed);
+        assert!(armored.contains(
+            "\nContext: context\nDate: 2026-10-18T12:00:00Z\nSubject: Re: lunch\n\"\"\"\n"
+        ));
+
+        let parsed = SignedMessage::from_str(&armored).unwrap();
+        assert!(parsed.verify());
+        assert_eq!(parsed.headers(), options.headers.as_slice());
+        assert_eq!(parsed.header("subject"), Some("Re: lunch"));
+        assert_eq!(parsed.context(), Some("context"));
+        assert_eq!(parsed.message, "message");
+
+        // Changing, reordering or dropping headers invalidates the signature.
+        for tampered in [
+            armored.replace("Subject: Re: lunch", "Subject: Re: dinner"),
+            armored.replace(
+                "Date: 2026-10-18T12:00:00Z\nSubject: Re: lunch",
+                "Subject: Re: lunch\nDate: 2026-10-18T12:00:00Z",
+            ),
+            armored.replace("Date: 2026-10-18T12:00:00Z\n", ""),
+        ] {
+            assert!(!SignedMessage::from_str(&tampered).unwrap().verify());
+        }
+
+        // Malformed header lines don't parse at all.
+        assert!(SignedMessage::from_str(&armored.replace("Subject: ", "Subject ")).is_err());
+        assert!(SignedMessage::from_str(&armored.replace("Subject: ", "Date: ")).is_err());
+
+        for bad in [
+            ("Context", "x"),
+            ("X Y", "x"),
+            ("-X", "x"),
+            ("X", ""),
+            ("X", "a\nb"),
+        ] {
+            let options = SigningOptions {
+                headers: vec![(bad.0.to_string(), bad.1.to_string())],
+                ..SigningOptions::default()
+            };
+            assert!(SignedMessage::sign_with_options("m", &my_key, &[], &options).is_none());
+        }
+        let options = SigningOptions {
+            version: FormatVersion::V1_0,
+            headers: vec![("Subject".to_string(), "x".to_string())],
+            ..SigningOptions::default()
+        };
+        assert!(SignedMessage::sign_with_options("m", &my_key, &[], &options).is_none());
+    }
+
+    #[test]
+    fn claims_work() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let options = SigningOptions {
+            claimable: true,
+            ..SigningOptions::default()
+        };
+        let signed =
+            SignedMessage::sign_with_options("message", &my_key, &[other_key.public()], &options)
+                .unwrap();
+        assert!(signed.is_claimable());
+        let armored = String::from(&signed);
+        assert!(armored.contains("\nClaimable: "));
+        let signed = SignedMessage::from_str(&armored).unwrap();
+        assert!(signed.verify());
+
+        // Only the signer can claim it.
+        assert!(AuthorshipClaim::new(&signed, &other_key).is_none());
+        let claim = AuthorshipClaim::new(&signed, &my_key).unwrap();
+        let claim = AuthorshipClaim::from_str(&String::from(&claim)).unwrap();
+        assert!(claim.claimant() == &my_key.public());
+        assert!(claim.verify(&signed));
+
+        // The revealed token doesn't let anyone else claim it either.
+        let (_, token, _) =
+            <(PublicKey, [u8; 32], Signature)>::try_from_slice(&borsh::to_vec(&claim).unwrap())
+                .unwrap();
+        let signature = Signature::sign_transcript(
+            Transcript::Claim {
+                commitment: signed.claim_commitment.as_ref().unwrap(),
+                token: &token,
+            },
+            other_key.key.clone(),
+            &[],
+            &mut OsRng,
+        );
+        let stolen = AuthorshipClaim::try_from_slice(
+            &borsh::to_vec(&(other_key.public(), token, signature)).unwrap(),
+        )
+        .unwrap();
+        assert!(!stolen.verify(&signed));
+
+        // Claims don't carry over to other messages, even ones with the same text.
+        let resigned =
+            SignedMessage::sign_with_options("message", &my_key, &[other_key.public()], &options)
+                .unwrap();
+        assert_eq!(resigned.claim_commitment, signed.claim_commitment);
+        let other_ring =
+            SignedMessage::sign_with_options("message", &my_key, &[], &options).unwrap();
+        assert_ne!(other_ring.claim_commitment, signed.claim_commitment);
+        assert!(!claim.verify(&other_ring));
+        assert!(!claim.verify(&SignedMessage::sign(
+            "message",
+            &my_key,
+            &[other_key.public()]
+        )));
+
+        // Stripping the claim line invalidates the signature.
+        let lines = armored
+            .lines()
+            .filter(|l| !l.starts_with("Claimable: "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(!SignedMessage::from_str(&lines).unwrap().verify());
+        assert!(SignedMessage::from_str(&armored.replace("Claimable: ", "Claimable: 0")).is_err());
+
+        let v1 = SigningOptions {
+            version: FormatVersion::V1_0,
+            claimable: true,
+            ..SigningOptions::default()
+        };
+        assert!(SignedMessage::sign_with_options("m", &my_key, &[], &v1).is_none());
+    }
+
+    #[test]
+    fn disavowals_work() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let outsider = PrivateKey::new(Identity::new("Brutus", "brutus@example.com").unwrap());
+        let options = SigningOptions {
+            disavowable: true,
+            ..SigningOptions::default()
+        };
+        let signed =
+            SignedMessage::sign_with_options("message", &my_key, &[other_key.public()], &options)
+                .unwrap();
+        let armored = String::from(&signed);
+        assert!(armored.contains("\nDisavowable: "));
+        let signed = SignedMessage::from_str(&armored).unwrap();
+        assert!(signed.is_disavowable());
+        assert!(signed.verify());
+
+        // Changing the key image breaks the signature.
+        let other_image = RistrettoPoint::random(&mut OsRng);
+        let mut tampered = signed.clone();
+        tampered.key_image = Some(other_image);
+        assert!(!tampered.verify());
+
+        // Other ring members can disavow; the signer and outsiders can't.
+        assert!(DisavowalProof::new(&signed, &my_key).is_none());
+        assert!(DisavowalProof::new(&signed, &outsider).is_none());
+        let proof = DisavowalProof::new(&signed, &other_key).unwrap();
+        let proof = DisavowalProof::from_str(&String::from(&proof)).unwrap();
+        assert!(proof.disavower() == &other_key.public());
+        assert!(signed.verify_disavowal(&proof));
+
+        // The signer can't forge a disavowal for someone else.
+        let (_, image, _) = <(PublicKey, RistrettoPoint, Signature)>::try_from_slice(
+            &borsh::to_vec(&proof).unwrap(),
+        )
+        .unwrap();
+        let base = signed.disavowal_base();
+        let forged = Signature::sign_tagged(
+            Transcript::Disavowal {
+                base: &base,
+                key_image: &image,
+            },
+            my_key.key.clone(),
+            &[],
+            Some((&base, &image)),
+            &mut OsRng,
+        );
+        let forged = DisavowalProof::try_from_slice(
+            &borsh::to_vec(&(other_key.public(), image, forged)).unwrap(),
+        )
+        .unwrap();
+        assert!(!signed.verify_disavowal(&forged));
+
+        // Key images differ between messages, so they can't be used to link signatures.
+        let resigned =
+            SignedMessage::sign_with_options("message 2", &my_key, &[other_key.public()], &options)
+                .unwrap();
+        assert!(resigned.key_image != signed.key_image);
+        assert!(!resigned.verify_disavowal(&proof));
+        assert!(
+            !SignedMessage::sign("message", &my_key, &[other_key.public()])
+                .verify_disavowal(&proof)
+        );
+
+        let v1 = SigningOptions {
+            version: FormatVersion::V1_0,
+            disavowable: true,
+            ..SigningOptions::default()
+        };
+        assert!(SignedMessage::sign_with_options("m", &my_key, &[], &v1).is_none());
+    }
+
+    #[test]
+    fn threshold_signatures_work() {
+        let keys = ["Alice", "Bob", "Carol", "Dave"]
+            .iter()
+            .map(|name| {
+                PrivateKey::new(
+                    Identity::new(name, &format!("{}@example.com", name.to_lowercase())).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let ring = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let outsider = PrivateKey::new(Identity::new("Eve", "eve@example.com").unwrap());
+
+        assert!(ThresholdProposal::new("message", 0, &ring).is_none());
+        assert!(ThresholdProposal::new("message", 5, &ring).is_none());
+        let proposal = ThresholdProposal::new("message\n\"\"\"\nmore", 2, &ring).unwrap();
+        let proposal = ThresholdProposal::from_str(&String::from(&proposal)).unwrap();
+        assert!(proposal.validate());
+        assert_eq!(proposal.message(), "message\n\"\"\"\nmore");
+        assert_eq!(proposal.threshold(), 2);
+
+        assert!(proposal.sign(&outsider).is_none());
+        let share_a = proposal.sign(&keys[0]).unwrap();
+        let share_a = ThresholdShare::from_str(&String::from(&share_a)).unwrap();
+        let share_a_copy = share_a.clone();
+        assert!(proposal.verify_share(&share_a));
+        let share_a_again = proposal.sign(&keys[0]).unwrap();
+        let share_c = proposal.sign(&keys[2]).unwrap();
+
+        // The same signer twice doesn't count as two.
+        assert!(proposal
+            .combine(&[share_a.clone(), share_a_again.clone()])
+            .is_none());
+        let signed = proposal
+            .combine(&[share_a.clone(), share_a_again, share_c.clone()])
+            .unwrap();
+        assert_eq!(signed.signer_count(), 2);
+        let armored = String::from(&signed);
+        let signed = ThresholdSignedMessage::from_str(&armored).unwrap();
+        assert!(signed.verify());
+        assert_eq!(signed.threshold(), 2);
+        assert_eq!(signed.ring().count(), 4);
+
+        // Shares for a different proposal don't count.
+        let other = ThresholdProposal::new("other message", 2, &ring).unwrap();
+        assert!(!other.verify_share(&share_a));
+        assert!(other.combine(&[share_a, share_c]).is_none());
+
+        // The text parts have to match the data exactly.
+        assert!(ThresholdSignedMessage::from_str(&armored.replace("more", "less")).is_err());
+        assert!(
+            ThresholdSignedMessage::from_str(&armored.replace("at least 2", "at least 3")).is_err()
+        );
+
+        // Duplicate key images don't verify, even if someone crafts them directly.
+        let doubled = ThresholdSignedMessage::try_from_slice(
+            &borsh::to_vec(&(&proposal, vec![&share_a_copy, &share_a_copy])).unwrap(),
+        )
+        .unwrap();
+        assert!(!doubled.verify());
+    }
+
+    #[test]
+    fn one_of_many_signatures_work() {
+        let keys = (0..100)
+            .map(|i| PrivateKey::new(Identity::new(&format!("Key {i}"), "k@example.com").unwrap()))
+            .collect::<Vec<_>>();
+        let ring = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let options = SigningOptions {
+            scheme: RingScheme::OneOfMany,
+            ..SigningOptions::default()
+        };
+
+        // Ring sizes on both sides of powers of two, including a ring of one.
+        for size in [1, 2, 3, 4, 5, 8, 9] {
+            for signer in [0, size - 1] {
+                let signed = SignedMessage::sign_with_options(
+                    "message",
+                    &keys[signer],
+                    &ring[..size],
+                    &options,
+                )
+                .unwrap();
+                let parsed = SignedMessage::from_str(&String::from(&signed)).unwrap();
+                assert!(parsed == signed);
+                assert_eq!(parsed.scheme(), RingScheme::OneOfMany);
+                assert_eq!(parsed.ring().count(), size);
+                assert!(parsed.verify());
+            }
+        }
+
+        let signed = SignedMessage::sign_with_options(
+            "message",
+            &keys[42],
+            &ring,
+            &SigningOptions {
+                claimable: true,
+                ..options.clone()
+            },
+        )
+        .unwrap();
+        let armored = String::from(&signed);
+        assert!(armored.contains("\nScheme: one-of-many\n"));
+        assert!(signed.verify());
+        assert!(AuthorshipClaim::new(&signed, &keys[42])
+            .unwrap()
+            .verify(&signed));
+
+        // Much smaller than SAG, with the same ring.
+        let sag = SignedMessage::sign("message", &keys[42], &ring);
+        assert!(armored.len() + 1500 < String::from(&sag).len());
+
+        // Tampering with the message, or claiming a different scheme, breaks it.
+        let tampered = armored.replace("\nmessage\n", "\nmassage\n");
+        assert!(!SignedMessage::from_str(&tampered).unwrap().verify());
+        assert!(SignedMessage::from_str(&armored.replace("\nScheme: one-of-many", "")).is_err());
+        assert!(SignedMessage::from_str(&armored.replace("one-of-many\n", "SAG\n")).is_err());
+
+        // Only SAG supports disavowals (and 1.0 messages).
+        for options in [
+            SigningOptions {
+                disavowable: true,
+                ..options.clone()
+            },
+            SigningOptions {
+                version: FormatVersion::V1_0,
+                ..options.clone()
+            },
+        ] {
+            assert!(SignedMessage::sign_with_options("m", &keys[0], &ring, &options).is_none());
+        }
+    }
+
+    #[test]
+    fn canonical_text_survives_reformatting() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let options = SigningOptions {
+            canonicalization: Canonicalization::Text,
+            claimable: true,
+            disavowable: true,
+            ..SigningOptions::default()
+        };
+        let message = "Dear all,\n\nThe vote passes, seven to two.\nThanks!";
+        assert_eq!(
+            Canonicalization::Text.apply(message),
+            "Dear all,\n\nThe vote passes, seven to two. Thanks!"
+        );
+        let signed =
+            SignedMessage::sign_with_options(message, &my_key, &[other_key.public()], &options)
+                .unwrap();
+        assert_eq!(signed.canonicalization(), Canonicalization::Text);
+        assert_eq!(signed.message, message);
+        assert!(signed.verify());
+
+        let armored = String::from(&signed);
+        assert!(armored.contains("\nCanonicalization: text\n"));
+        assert!(SignedMessage::from_str(&armored) == Ok(signed.clone()));
+        let decoded = SignedMessage::try_from_slice(&borsh::to_vec(&signed).unwrap()).unwrap();
+        assert!(decoded == signed);
+
+        // Line endings, trailing whitespace, and the layout of the words don't matter...
+        let crlf = armored.replace('\n', "\r\n");
+        let trailing = armored.replace('\n', "  \n");
+        let reflowed = armored.replace("passes, seven", "passes,\n\tseven");
+        for text in [crlf, trailing, reflowed] {
+            let parsed = SignedMessage::from_str(&text).unwrap();
+            assert!(parsed.verify());
+            assert!(AuthorshipClaim::new(&parsed, &my_key)
+                .unwrap()
+                .verify(&parsed));
+            let proof = DisavowalProof::new(&parsed, &other_key).unwrap();
+            assert!(signed.verify_disavowal(&proof));
+        }
+
+        // ...but the words themselves, paragraph breaks, and the canonicalization line do.
+        let tampered = armored.replace("seven to two", "seven to three");
+        assert!(!SignedMessage::from_str(&tampered).unwrap().verify());
+        let joined = armored.replace("Dear all,\n\n", "Dear all,\n");
+        assert!(!SignedMessage::from_str(&joined).unwrap().verify());
+        let removed = armored.replace("Canonicalization: text\n", "");
+        assert!(!SignedMessage::from_str(&removed).unwrap().verify());
+        assert!(SignedMessage::from_str(&armored.replace(": text\n", ": exact\n")).is_err());
+
+        // Exact messages aren't rescued by the retry.
+        let exact = String::from(&SignedMessage::sign(message, &my_key, &[]));
+        assert!(SignedMessage::from_str(&exact.replace('\n', "\r\n")).is_err());
+
+        // 1.0 messages can only be exact.
+        let v1_text = SigningOptions {
+            version: FormatVersion::V1_0,
+            canonicalization: Canonicalization::Text,
+            ..SigningOptions::default()
+        };
+        assert!(SignedMessage::sign_with_options("m", &my_key, &[], &v1_text).is_none());
+    }
+
+    #[test]
+    fn embedded_messages_work() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let ring = [other_key.public()];
+        let exact = SignedMessage::sign(
+            "Fish & chips <b>not bold</b>\n\n  \"Indented\"\n```\nCR\rLF\n",
+            &my_key,
+            &ring,
+        );
+        let text = SignedMessage::sign_with_options(
+            "Dear all,\n\nThe vote passes,\nseven to two.",
+            &my_key,
+            &ring,
+            &SigningOptions {
+                canonicalization: Canonicalization::Text,
+                ..SigningOptions::default()
+            },
+        )
+        .unwrap();
+
+        for signed in [&exact, &text] {
+            let html = format!(
+                "<html><body><h1>News</h1>\n<!-- <blockquote> -->\n{}\n<p>Bye</p></body></html>",
+                signed.to_html()
+            );
+            let markdown = format!("# News\n\n{}\n\nBye\n", signed.to_markdown());
+            for document in [html, markdown] {
+                let found = SignedMessage::find_embedded(&document);
+                assert!(found.len() == 1 && &found[0] == signed, "{document}");
+                assert!(found[0].verify());
+            }
+        }
+
+        // Several in one document, in either form.
+        let document = format!(
+            "{}\n\n{}\n\n{}",
+            exact.to_markdown(),
+            text.to_html(),
+            text.to_markdown()
+        );
+        assert!(
+            SignedMessage::find_embedded(&document)
+                == vec![text.clone(), exact.clone(), text.clone()]
+        );
+
+        // Canonical text survives being rewrapped and split into paragraphs...
+        let html = text.to_html().replace(
+            "Dear all,\n\nThe vote passes,\nseven",
+            "<p>Dear all,</p>\n<p>The vote <em>passes</em>,<br/>\n  seven",
+        );
+        let markdown = text
+            .to_markdown()
+            .replace("> The vote passes,\n> seven", "> The vote\n> passes, seven");
+        for document in [html, markdown] {
+            let found = SignedMessage::find_embedded(&document);
+            assert!(found.len() == 1 && found[0].verify(), "{document}");
+        }
+        // ...but exact messages don't.
+        let html = exact
+            .to_html()
+            .replace("&lt;b&gt;not bold&lt;/b&gt;", "<b>not bold</b>");
+        assert!(!SignedMessage::find_embedded(&html)[0].verify());
+        let markdown = exact.to_markdown().replace(">   \"", ">  \"");
+        assert!(!SignedMessage::find_embedded(&markdown)[0].verify());
+
+        // Changing the words breaks either one, and a mangled signature block isn't found at all.
+        for signed in [&exact, &text] {
+            let tampered = signed
+                .to_html()
+                .replace("chips", "chops")
+                .replace("seven", "eleven");
+            assert!(!SignedMessage::find_embedded(&tampered)[0].verify());
+            let tampered = signed
+                .to_markdown()
+                .replace("chips", "chops")
+                .replace("seven", "eleven");
+            assert!(!SignedMessage::find_embedded(&tampered)[0].verify());
+            let mangled = signed.to_markdown().replace("Zebra <", "Zebro <");
+            assert!(SignedMessage::find_embedded(&mangled).is_empty());
+        }
+    }
+
+    #[test]
+    fn timestamp_tokens_are_carried() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let signed = SignedMessage::sign("Test\n\nmessage", &my_key, &[other_key.public()]);
+        let borsh_encoding = borsh::to_vec(&signed).unwrap();
+
+        // The token isn't checked here, so any bytes will do.
+        let mut timestamped = signed.clone();
+        timestamped.set_timestamp_token(Some(b"\x30\x03\x02\x01\x00".to_vec()));
+        assert_eq!(
+            timestamped.timestamp_token(),
+            Some(&b"\x30\x03\x02\x01\x00"[..])
+        );
+        assert!(timestamped.verify());
+        assert_eq!(borsh::to_vec(&timestamped).unwrap(), borsh_encoding);
+
+        let armored = String::from(&timestamped);
+        assert!(armored.contains(&format!("\n\n{SIGNED_MESSAGE_TIMESTAMP_LINE}\n")));
+        assert!(SignedMessage::from_str(&armored) == Ok(timestamped.clone()));
+        // Removing the token gives back the original.
+        let mut removed = SignedMessage::from_str(&armored).unwrap();
+        removed.set_timestamp_token(Some(vec![]));
+        assert!(removed == signed && String::from(&removed) == String::from(&signed));
+
+        let token_line = z85::encode(b"\x30\x03\x02\x01\x00");
+        for bad in [
+            armored.replace(&token_line, ""),
+            armored.replace(&token_line, &format!("{token_line}0")),
+            armored.replace(&format!("\n\n{SIGNED_MESSAGE_TIMESTAMP_LINE}"), "\n"),
+        ] {
+            assert!(SignedMessage::from_str(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn signed_batches_work() {
+        let my_key = PrivateKey::new(Identity::new("Zebra", "zebra@example.com").unwrap());
+        let other_key = PrivateKey::new(Identity::new("Gaius", "notzebra@example.com").unwrap());
+        let ring = [other_key.public(), other_key.public(), my_key.public()];
+
+        for count in [1, 2, 3, 5, 8, 13] {
+            let documents = (0..count)
+                .map(|i| format!("Document {i}").into_bytes())
+                .collect::<Vec<_>>();
+            let (batch, proofs) = SignedBatch::sign(&documents, &my_key, &ring).unwrap();
+            assert_eq!(batch.document_count(), count);
+            assert_eq!(batch.ring().count(), 2);
+            assert_eq!(proofs.len(), count);
+
+            let armored = String::from(&batch);
+            assert!(armored.contains(&format!("\n{count} documents, with the Merkle root:\n")));
+            let batch = SignedBatch::from_str(&armored).unwrap();
+            assert!(batch.verify());
+            for (i, (document, proof)) in documents.iter().zip(&proofs).enumerate() {
+                let proof = InclusionProof::from_str(&String::from(proof)).unwrap();
+                assert_eq!(proof.index(), i);
+                assert!(batch.verify_document(document, &proof));
+                // Each proof only works for its own document.
+                let other = &documents[(i + 1) % count];
+                assert!(count == 1 || !batch.includes(other, &proof));
+                assert!(!batch.includes(b"Document X", &proof));
+            }
+        }
+
+        let documents = ["a", "b", "c"];
+        let (batch, proofs) = SignedBatch::sign(&documents, &my_key, &[]).unwrap();
+        assert!(batch.verify_document(b"c", &proofs[2]));
+
+        // Proofs can't be moved to another position, or padded.
+        let (index, siblings) =
+            <(u32, Vec<[u8; 64]>)>::try_from_slice(&borsh::to_vec(&proofs[2]).unwrap()).unwrap();
+        assert_eq!(index, 2);
+        let proof_with = |index: u32, siblings: &[[u8; 64]]| {
+            InclusionProof::try_from_slice(&borsh::to_vec(&(index, siblings.to_vec())).unwrap())
+                .unwrap()
+        };
+        assert!(batch.includes(b"c", &proof_with(2, &siblings)));
+        assert!(!batch.includes(b"c", &proof_with(3, &siblings)));
+        assert!(!batch.includes(b"c", &proof_with(2, &[siblings[0], [0; 64]])));
+
+        // The root and the document count are both signed, and the text must match the data.
+        let (count, root, ring, signature) =
+            <(u32, [u8; 64], Vec<PublicKey>, Signature)>::try_from_slice(
+                &borsh::to_vec(&batch).unwrap(),
+            )
+            .unwrap();
+        let mut other_root = root;
+        other_root[0] ^= 1;
+        for (count, root) in [(count, other_root), (count + 1, root)] {
+            let tampered = SignedBatch::try_from_slice(
+                &borsh::to_vec(&(count, root, &ring, &signature)).unwrap(),
+            )
+            .unwrap();
+            assert!(!tampered.verify());
+        }
+        let armored = String::from(&batch);
+        assert!(SignedBatch::from_str(&armored.replace("\n3 documents", "\n4 documents")).is_err());
+
+        assert!(SignedBatch::sign(&[] as &[&[u8]], &my_key, &[]).is_none());
+    }
+
+    #[test]
+    fn batch_verification_works() {
+        let keys = (0..6)
+            .map(|i| PrivateKey::new(Identity::new(&format!("Key {i}"), "k@example.com").unwrap()))
+            .collect::<Vec<_>>();
+        let ring = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        // A key whose attestation is for someone else's identity.
+        let mislabeled = PublicKey::try_from_slice(
+            &borsh::to_vec(&(
+                Identity::new("Mallory", "m@example.com").unwrap(),
+                ring[1].keypoint(),
+                ring[1].holder_attestation(),
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut messages = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            for &scheme in RingScheme::ALL {
+                let options = SigningOptions {
+                    scheme,
+                    ..SigningOptions::default()
+                };
+                messages.push(
+                    SignedMessage::sign_with_options(&format!("{i}"), key, &ring[..i], &options)
+                        .unwrap(),
+                );
+            }
+        }
+        assert_eq!(SignedMessage::verify_batch(&messages, false), Ok(()));
+        assert_eq!(SignedMessage::verify_batch(&messages, true), Ok(()));
+        assert_eq!(SignedMessage::verify_batch(&[], true), Ok(()));
+
+        messages[3].message.push('!');
+        messages[8].message.push('!');
+        messages.push(SignedMessage::sign(
+            "m",
+            &keys[0],
+            &[ring[2].clone(), mislabeled],
+        ));
+        let expected = (0..messages.len())
+            .filter(|&i| !messages[i].verify())
+            .collect::<Vec<_>>();
+        assert_eq!(expected, vec![3, 8, messages.len() - 1]);
+        assert_eq!(
+            SignedMessage::verify_batch(&messages, false),
+            Err(expected.clone())
+        );
+        assert_eq!(SignedMessage::verify_batch(&messages, true), Err(expected));
+    }
+
+    #[test]
+    fn seeded_signing_is_deterministic() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let id = Identity::new("Zebra", "zebra@example.com").unwrap();
+        let key = PrivateKey::new_with_rng(id.clone(), &mut StdRng::seed_from_u64(1));
+        assert!(key == PrivateKey::new_with_rng(id.clone(), &mut StdRng::seed_from_u64(1)));
+        assert!(key != PrivateKey::new_with_rng(id.clone(), &mut StdRng::seed_from_u64(2)));
+        let ring = [PrivateKey::new_with_rng(id, &mut StdRng::seed_from_u64(3)).public()];
+
+        for &scheme in RingScheme::ALL {
+            for hedged in [false, true] {
+                let options = SigningOptions {
+                    scheme,
+                    hedged,
+                    ..SigningOptions::default()
+                };
+                let sign = |message, seed| {
+                    SignedMessage::sign_with_options_and_rng(
+                        message,
+                        &key,
+                        &ring,
+                        &options,
+                        &mut StdRng::seed_from_u64(seed),
+                    )
+                    .unwrap()
+                };
+                let signed = sign("m", 4);
+                assert!(signed.verify());
+                assert!(signed == sign("m", 4));
+                assert!(signed != sign("m", 5));
+            }
+        }
+
+        // With the same RNG output, the fake SAG response for the other ring member is the same for
+        // every message, unless the signature is hedged.
+        let responses = |message, hedged| {
+            let options = SigningOptions {
+                hedged,
+                ..SigningOptions::default()
+            };
+            let signed = SignedMessage::sign_with_options_and_rng(
+                message,
+                &key,
+                &ring,
+                &options,
+                &mut StdRng::seed_from_u64(4),
+            )
+            .unwrap();
+            match signed.proof {
+                RingProof::Sag { ref responses, .. } => responses.clone(),
+                RingProof::OneOfMany(_) => unreachable!(),
+            }
+        };
+        let shared = |hedged| {
+            responses("a", hedged)
+                .iter()
+                .zip(responses("b", hedged).iter())
+                .filter(|(a, b)| a == b)
+                .count()
+        };
+        assert_eq!(shared(false), 1);
+        assert_eq!(shared(true), 0);
+    }
+
+    #[test]
+    fn known_answer_vectors_pass() {
+        assert_eq!(vectors::check(&vectors::Native), Ok(()));
+    }
+
+    #[test]
+    fn raw_signatures_work() {
+        let keys = (0..3)
+            .map(|i| PrivateKey::new(Identity::new(&format!("Key {i}"), "k@example.com").unwrap()))
+            .collect::<Vec<_>>();
+        let ring = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let message = b"\x00 arbitrary \xFF bytes";
+
+        for &scheme in RingScheme::ALL {
+            let signature = RingSignature::sign(message, &keys[1], &ring, scheme);
+            assert!(signature.scheme() == scheme);
+            assert!(signature.verify(message));
+            assert!(!signature.verify(b"other bytes"));
+            assert!(signature.verify_with_keys(message, &ring));
+            assert!(!signature.verify_with_keys(message, &ring[1..]));
+            assert!(!signature.verify_with_keys(message, &[ring.clone(), ring.clone()].concat()));
+            let mut keypoints = ring.iter().map(|k| k.keypoint_bytes()).collect::<Vec<_>>();
+            keypoints.sort();
+            assert!(signature.ring().eq(keypoints.iter().cloned()));
+
+            let bytes = signature.to_bytes();
+            assert_eq!(bytes[0], 1);
+            if scheme == RingScheme::Sag {
+                assert_eq!(bytes.len(), 1 + (4 + 3 * 32) + (1 + 32 + 4 + 3 * 32));
+            }
+            assert!(RingSignature::from_bytes(&bytes).unwrap() == signature);
+            let mut wrong_version = bytes.clone();
+            wrong_version[0] = 2;
+            assert!(RingSignature::from_bytes(&wrong_version).is_none());
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            assert!(RingSignature::from_bytes(&trailing).is_none());
+            // Swap the first two ring members.
+            let mut unsorted = bytes.clone();
+            let (first, second) = unsorted[5..69].split_at_mut(32);
+            first.swap_with_slice(second);
+            assert!(RingSignature::from_bytes(&unsorted).is_none());
+
+            // Bare keypoints work too, and an invalid one is rejected.
+            let signature =
+                RingSignature::sign_keypoints(message, &keys[0], &keypoints, scheme).unwrap();
+            assert!(signature.verify(message));
+            assert!(
+                RingSignature::sign_keypoints(message, &keys[0], &[[0xFF; 32]], scheme).is_none()
+            );
+        }
+
+        // Raw signatures and signed messages can't be confused.
+        let signed = SignedMessage::sign("m", &keys[0], &ring);
+        let raw = RingSignature::sign(b"m", &keys[0], &ring, RingScheme::Sag);
+        let (_, proof) =
+            <(Vec<RistrettoPoint>, RingProof)>::try_from_slice(&raw.to_bytes()[1..]).unwrap();
+        let mut forged = signed.clone();
+        forged.proof = proof;
+        assert!(!forged.verify());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_encodings_work() {
+        fn cbor<T: serde::Serialize>(value: &T) -> Vec<u8> {
+            let mut bytes = vec![];
+            ciborium::into_writer(value, &mut bytes).unwrap();
+            bytes
+        }
+        fn from_cbor<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+            ciborium::from_reader(bytes).ok()
+        }
+
+        let keys = (0..3)
+            .map(|i| PrivateKey::new(Identity::new(&format!("Key {i}"), "k@example.com").unwrap()))
+            .collect::<Vec<_>>();
+        let ring = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+
+        let key = &ring[0];
+        let json = serde_json::to_string(key).unwrap();
+        assert!(json.contains(&hex::encode_upper(key.keypoint_bytes())));
+        let decoded: PublicKey = serde_json::from_str(&json).unwrap();
+        assert!(&decoded == key && decoded.fingerprint() == key.fingerprint());
+        assert!(from_cbor::<PublicKey>(&cbor(key)).unwrap() == *key);
+        let attestation = key.holder_attestation();
+        assert!(
+            serde_json::from_str::<Signature>(&serde_json::to_string(&attestation).unwrap())
+                .unwrap()
+                == attestation
+        );
+        assert!(from_cbor::<Signature>(&cbor(&attestation)).unwrap() == attestation);
+
+        // The same things that Borsh rejects.
+        for bad in [
+            json.replace(&hex::encode_upper(key.keypoint_bytes()), &"F".repeat(64)),
+            json.replace(
+                &hex::encode_upper(key.keypoint_bytes()),
+                &hex::encode(key.keypoint_bytes()),
+            ),
+            json.replace("Key 0", "Key\n0"),
+            json.replace("k@example.com", "k @example.com"),
+            json.replace("\"holder\"", "\"extra\":1,\"holder\""),
+        ] {
+            assert!(serde_json::from_str::<PublicKey>(&bad).is_err(), "{bad}");
+        }
+        let mut value = ciborium::value::Value::serialized(key).unwrap();
+        if let ciborium::value::Value::Map(ref mut fields) = value {
+            fields[0].1 = ciborium::value::Value::serialized(&IdentityForTest {
+                name: "Key 0",
+                email: "k@example .com",
+            })
+            .unwrap();
+        }
+        assert!(from_cbor::<PublicKey>(&cbor(&value)).is_none());
+
+        let options = [
+            SigningOptions {
+                version: FormatVersion::V1_0,
+                ..SigningOptions::default()
+            },
+            SigningOptions {
+                context: Some("Serde".to_string()),
+                headers: vec![("Subject".to_string(), "Lunch".to_string())],
+                claimable: true,
+                disavowable: true,
+                ..SigningOptions::default()
+            },
+            SigningOptions {
+                scheme: RingScheme::OneOfMany,
+                canonicalization: Canonicalization::Text,
+                ..SigningOptions::default()
+            },
+        ];
+        for options in options {
+            let signed = SignedMessage::sign_with_options("m", &keys[1], &ring, &options).unwrap();
+            let json = serde_json::to_string(&signed).unwrap();
+            let decoded: SignedMessage = serde_json::from_str(&json).unwrap();
+            assert!(decoded == signed && decoded.verify());
+            let decoded: SignedMessage = from_cbor(&cbor(&signed)).unwrap();
+            assert!(decoded == signed && decoded.verify());
+            // Absent fields are omitted, rather than null.
+            assert!(!json.contains("null"));
+            assert!(serde_json::from_str::<SignedMessage>(
+                &json.replace("\"scheme\"", "\"context\":null,\"scheme\"")
+            )
+            .is_err());
+        }
+
+        let mut signed = SignedMessage::sign("m", &keys[1], &ring);
+        signed.set_timestamp_token(Some(vec![0x30, 0]));
+        let json = serde_json::to_string(&signed).unwrap();
+        assert!(json.contains("\"timestamp_token\":\"3000\""));
+        assert!(serde_json::from_str::<SignedMessage>(&json).unwrap() == signed);
+        assert!(from_cbor::<SignedMessage>(&cbor(&signed)).unwrap() == signed);
+        signed.set_timestamp_token(None);
+        let json = serde_json::to_string(&signed).unwrap();
+        for bad in [
+            json.replace("\"2.0\"", "\"3.0\""),
+            json.replace("\"SAG\"", "\"one-of-many\""),
+            json.replace("\"headers\":[]", "\"headers\":[[\"Context\",\"x\"]]"),
+            json.replace("\"scheme\"", "\"context\":\"\",\"scheme\""),
+            json.replace("\"headers\"", "\"canonicalization\":\"exact\",\"headers\""),
+            format!("{},\"timestamp_token\":\"\"}}", &json[..json.len() - 1]),
+        ] {
+            assert!(
+                serde_json::from_str::<SignedMessage>(&bad).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(serde::Serialize)]
+    struct IdentityForTest<'a> {
+        name: &'a str,
+        email: &'a str,
+    }
 }
@@ -0,0 +1,298 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha3::{Digest, Sha3_512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::threshold::{parse, z85_line};
+use crate::{
+    append_transcript_field, key_line, make_ring, PrivateKey, PublicKey, Signature, Transcript,
+};
+
+pub(crate) const BATCH_TRANSCRIPT_DOMAIN: &[u8] = b"Zebra signed batch v1";
+const LEAF_DOMAIN: &[u8] = b"Zebra Merkle leaf v1";
+const NODE_DOMAIN: &[u8] = b"Zebra Merkle node v1";
+
+// How batch signing works:
+//
+// Each document is hashed into a leaf, and the leaves are paired up (in order) and hashed into
+// nodes, and so on, until there's a single root. When a level has an odd number of hashes, the
+// last one moves up to the next level as it is, rather than being paired with itself, so that no
+// two different batches have the same tree. Leaves and nodes are hashed with different domains,
+// so a node can't pass for a document.
+//
+// The root, and the number of documents, are ring-signed once, as an ordinary SAG signature. Each
+// document's inclusion proof is then just its index and the hashes that it's paired with on the
+// way up to the root. Since the shape of the tree only depends on the number of documents, a
+// verifier can tell from the index which side each of those goes on, and how many there should
+// be.
+
+/// The root of a Merkle tree over a batch of documents, signed by one of the holders of the keys
+/// in `ring()`. Each document is checked against it with its own `InclusionProof`.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct SignedBatch {
+    document_count: u32,
+    root: [u8; 64],
+    // Sorted by keypoint, as in the signature.
+    ring: Vec<PublicKey>,
+    signature: Signature,
+}
+
+/// Evidence that a document is the one at `index()` in a `SignedBatch`.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct InclusionProof {
+    index: u32,
+    // From the leaves up.
+    siblings: Vec<[u8; 64]>,
+}
+
+impl SignedBatch {
+    /// Sign a batch of documents at once, returning the signed batch, and an inclusion proof for
+    /// each document, in the same order. Returns None if there are no documents, or more than
+    /// 2^32 - 1 of them.
+    #[cfg(feature = "std")]
+    pub fn sign(
+        documents: &[impl AsRef<[u8]>],
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+    ) -> Option<(SignedBatch, Vec<InclusionProof>)> {
+        Self::sign_with_rng(documents, my_key, other_keys, &mut OsRng)
+    }
+
+    /// Like `sign`, but with randomness from `rng` rather than from the operating system.
+    pub fn sign_with_rng(
+        documents: &[impl AsRef<[u8]>],
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<(SignedBatch, Vec<InclusionProof>)> {
+        let document_count = u32::try_from(documents.len()).ok().filter(|&n| n > 0)?;
+        let mut levels = vec![documents
+            .iter()
+            .map(|d| leaf_hash(d.as_ref()))
+            .collect::<Vec<_>>()];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [promoted] => *promoted,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        let root = levels[levels.len() - 1][0];
+
+        let my_public_key = my_key.public();
+        let mut other_keys = other_keys
+            .iter()
+            .filter(|k| *k != &my_public_key)
+            .cloned()
+            .collect::<Vec<_>>();
+        other_keys.sort_by_key(|k| k.keypoint.compress());
+        other_keys.dedup_by_key(|k| k.keypoint.compress());
+        let ring = make_ring(my_public_key, &other_keys, |k| k.keypoint.clone());
+        let signature = Signature::sign_transcript(
+            Transcript::Batch {
+                root: &root,
+                document_count,
+            },
+            my_key.key.clone(),
+            &other_keys
+                .iter()
+                .map(|k| k.keypoint.clone())
+                .collect::<Vec<_>>(),
+            rng,
+        );
+
+        let proofs = (0..document_count)
+            .map(|index| {
+                let mut siblings = vec![];
+                let mut i = index as usize;
+                for level in &levels[..levels.len() - 1] {
+                    if let Some(sibling) = level.get(i ^ 1) {
+                        siblings.push(*sibling);
+                    }
+                    i >>= 1;
+                }
+                InclusionProof { index, siblings }
+            })
+            .collect();
+        Some((
+            SignedBatch {
+                document_count,
+                root,
+                ring,
+                signature,
+            },
+            proofs,
+        ))
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.document_count as usize
+    }
+
+    /// The Merkle root, which the signature covers.
+    pub fn root(&self) -> [u8; 64] {
+        self.root
+    }
+
+    pub fn ring(&self) -> impl Iterator<Item = &PublicKey> {
+        self.ring.iter()
+    }
+
+    /// Verify the signature of the root, and the ring members' attestations. This doesn't check
+    /// any documents; see `verify_document`.
+    pub fn verify(&self) -> bool {
+        self.document_count > 0
+            && self.ring.iter().all(|k| k.validate_attestation())
+            && self.signature.ring_responses.len() == self.ring.len()
+            && self
+                .signature
+                .ring_responses
+                .iter()
+                .zip(self.ring.iter())
+                .all(|((keypoint, _), k)| keypoint == &k.keypoint)
+            && self.signature.verify_transcript(Transcript::Batch {
+                root: &self.root,
+                document_count: self.document_count,
+            })
+    }
+
+    /// Verify that `document` is in this batch, according to `proof`, and that the batch's
+    /// signature is valid.
+    pub fn verify_document(&self, document: &[u8], proof: &InclusionProof) -> bool {
+        self.verify() && self.includes(document, proof)
+    }
+
+    /// Check `proof` against the root, without verifying the signature. This is much faster, so
+    /// when checking many documents from the same batch, it's worth calling `verify` once, and
+    /// then this for each document.
+    pub fn includes(&self, document: &[u8], proof: &InclusionProof) -> bool {
+        if proof.index >= self.document_count {
+            return false;
+        }
+        let mut hash = leaf_hash(document);
+        let mut index = proof.index;
+        let mut width = self.document_count;
+        let mut siblings = proof.siblings.iter();
+        while width > 1 {
+            if index & 1 == 1 {
+                let Some(left) = siblings.next() else {
+                    return false;
+                };
+                hash = node_hash(left, &hash);
+            } else if index + 1 < width {
+                let Some(right) = siblings.next() else {
+                    return false;
+                };
+                hash = node_hash(&hash, right);
+            }
+            index >>= 1;
+            width = (width >> 1) + (width & 1);
+        }
+        siblings.next().is_none() && hash == self.root
+    }
+}
+
+impl InclusionProof {
+    /// The document's position in the batch, starting from 0.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+fn leaf_hash(document: &[u8]) -> [u8; 64] {
+    let mut hash = Sha3_512::new();
+    append_transcript_field(&mut hash, b"domain", LEAF_DOMAIN);
+    append_transcript_field(&mut hash, b"document", document);
+    finalize(hash)
+}
+
+fn node_hash(left: &[u8; 64], right: &[u8; 64]) -> [u8; 64] {
+    let mut hash = Sha3_512::new();
+    append_transcript_field(&mut hash, b"domain", NODE_DOMAIN);
+    append_transcript_field(&mut hash, b"left", left);
+    append_transcript_field(&mut hash, b"right", right);
+    finalize(hash)
+}
+
+fn finalize(hash: Sha3_512) -> [u8; 64] {
+    let mut out = [0; 64];
+    out.copy_from_slice(&hash.finalize());
+    out
+}
+
+// The ASCII format for a signed batch looks like a threshold-signed message, with the number of
+// documents and the root in place of the message, and, as there, the human-readable parts must
+// match the data line exactly. Inclusion proofs are just the data line, between fixed first and
+// last lines, like threshold shares.
+
+const BATCH_FIRST_LINE: &str = "The following batch of documents has been signed using Zebra:";
+const BATCH_COUNT_LINE: &str = "{n} documents, with the Merkle root:";
+const BATCH_RING_LINE: &str =
+    "It was signed by someone with a private key corresponding to one of these fingerprints:";
+const BATCH_LAST_LINE: &str = "To verify a document from this batch, pass this entire message (starting with \"The following batch\" and ending with this line), the document, and its inclusion proof to SignedBatch::verify_document in the zebra_crypto library.";
+
+const PROOF_FIRST_LINE: &str =
+    "The following proves that document {i} is part of a batch signed using Zebra:";
+const PROOF_LAST_LINE: &str = "To check it, pass it, the document, and the signed batch to SignedBatch::verify_document in the zebra_crypto library.";
+
+impl From<&SignedBatch> for String {
+    fn from(b: &SignedBatch) -> String {
+        let mut parts = vec![
+            BATCH_FIRST_LINE.to_string(),
+            "\"\"\"".to_string(),
+            BATCH_COUNT_LINE.replace("{n}", &b.document_count.to_string()),
+            hex::encode_upper(b.root),
+            "\"\"\"".to_string(),
+            "".to_string(),
+            BATCH_RING_LINE.to_string(),
+            "".to_string(),
+        ];
+        parts.extend(b.ring.iter().map(key_line));
+        parts.push("".to_string());
+        parts.push(z85_line(b));
+        parts.push("".to_string());
+        parts.push(BATCH_LAST_LINE.to_string());
+        parts.join("\n")
+    }
+}
+
+impl FromStr for SignedBatch {
+    type Err = ();
+    /// Success does *not* imply a valid signature.
+    fn from_str(s: &str) -> Result<SignedBatch, ()> {
+        parse(s, |value: &SignedBatch| String::from(value))
+    }
+}
+
+impl From<&InclusionProof> for String {
+    fn from(proof: &InclusionProof) -> String {
+        [
+            // Counting from 1, for humans.
+            PROOF_FIRST_LINE.replace("{i}", &(u64::from(proof.index) + 1).to_string()),
+            "".to_string(),
+            z85_line(proof),
+            "".to_string(),
+            PROOF_LAST_LINE.to_string(),
+        ]
+        .join("\n")
+    }
+}
+
+impl FromStr for InclusionProof {
+    type Err = ();
+    /// Success does *not* imply a valid proof; see `SignedBatch::verify_document`.
+    fn from_str(s: &str) -> Result<InclusionProof, ()> {
+        parse(s, |value: &InclusionProof| String::from(value))
+    }
+}
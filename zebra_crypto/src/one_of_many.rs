@@ -0,0 +1,365 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint as DalekPoint;
+use curve25519_dalek::traits::{IsIdentity, MultiscalarMul, VartimeMultiscalarMul};
+use curve25519_dalek::Scalar as DalekScalar;
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha3::{Digest, Sha3_512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::append_transcript_field;
+use crate::ristretto::{RistrettoPoint, Scalar};
+
+const COMMITMENT_GENERATOR_DOMAIN: &[u8] = b"Zebra one-of-many commitment generator v1";
+
+// How one-of-many proofs work:
+//
+// This is the proof from Groth and Kohlweiss, "One-out-of-Many Proofs: Or How to Leak a Secret and
+// Spend a Coin" (https://eprint.iacr.org/2014/764, figure 2), with n = 2, made non-interactive with
+// the Fiat-Shamir transform. We use Pedersen commitments Com(v; r) = v H + r G, where G is the
+// usual basepoint, and H is hashed to a point so that nobody knows its discrete log. A public key
+// K = k G is then a commitment to zero, with the private key as its randomness, so "I know a
+// private key for one of these public keys" is exactly "I can open one of these commitments to
+// zero", which is what the paper proves.
+//
+// The ring is padded to N = 2^m keys by repeating its last key, and the signer's index l is written
+// in binary as l_0 ... l_{m-1}. For each bit, the signer commits to l_j, and proves that it's a bit.
+// Those proofs reveal f_j = l_j x + a_j, for the challenge x and a random a_j, which is enough for
+// the verifier to evaluate, for every ring member i, the polynomial
+//
+//     p_i(x) = prod_j (f_j if i_j = 1, else x - f_j).
+//
+// Since f_j is linear in x, p_i has degree m, and its leading coefficient is 1 for i = l, and 0 for
+// every other i. So sum_i p_i(x) K_i = x^m K_l + (terms of lower degree in x). The signer commits
+// to the lower-degree terms (the c_d below) before seeing x, and then reveals z_d, which shows that
+// what's left, x^m K_l, is a multiple of G that they know: x^m k_l.
+//
+// A proof has 4 points and 3 scalars for each of the m bits, plus one more scalar: for a ring of
+// 1,024 keys, that's about 2.3 KB, where a SAG signature's responses alone take 32 KB.
+
+/// Proof that the prover knows the private key for one of the keys in a ring.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub(crate) struct OneOfManyProof {
+    // One for each bit of the (padded) ring size.
+    bits: Vec<BitProof>,
+    // c_d_k, for k in 0..m: commitments to the coefficients of x^k.
+    coefficient_commitments: Vec<RistrettoPoint>,
+    // z_d
+    response: Scalar,
+}
+
+/// Proof that c_l commits to a bit, which also reveals f = l x + a.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+struct BitProof {
+    // c_l = Com(l; r)
+    bit_commitment: RistrettoPoint,
+    // c_a = Com(a; s)
+    a_commitment: RistrettoPoint,
+    // c_b = Com(l a; t)
+    b_commitment: RistrettoPoint,
+    // f = l x + a
+    f: Scalar,
+    // z_a = r x + s
+    z_a: Scalar,
+    // z_b = r (x - f) + t
+    z_b: Scalar,
+}
+
+/// The number of bits m needed to index a ring of the given size. Always at least one, so that
+/// even a ring of one key gets a real proof.
+fn bit_count(ring_size: usize) -> usize {
+    (usize::BITS - ring_size.saturating_sub(1).leading_zeros()).max(1) as usize
+}
+
+fn commitment_generator() -> DalekPoint {
+    let mut hash = Sha3_512::new();
+    append_transcript_field(&mut hash, b"domain", COMMITMENT_GENERATOR_DOMAIN);
+    DalekPoint::from_hash(hash)
+}
+
+/// Compute, for every index i in 0..2^m, the product over bits j of `factors[j].1` if bit j of i
+/// is set, and `factors[j].0` otherwise. Each table doubles the previous one, so this takes about
+/// 2^(m+1) multiplications.
+fn bit_products<T, F>(one: T, factors: &[(F, F)], mul: impl Fn(&T, &F) -> T) -> Vec<T> {
+    let mut products = vec![one];
+    for (if_zero, if_one) in factors {
+        let mut next = Vec::with_capacity(products.len() * 2);
+        next.extend(products.iter().map(|p| mul(p, if_zero)));
+        next.extend(products.iter().map(|p| mul(p, if_one)));
+        products = next;
+    }
+    products
+}
+
+/// Fold the values for the padding indices (ring.len() and up) into the last ring member, since
+/// that's who the padding repeats.
+fn fold_padding<T: core::ops::AddAssign + Clone>(mut values: Vec<T>, ring_size: usize) -> Vec<T> {
+    let padding = values.split_off(ring_size);
+    let last = values.last_mut().expect("Rings are never empty");
+    for value in padding {
+        *last += value;
+    }
+    values
+}
+
+/// The challenge x, from the transcript (which already includes the ring) and the commitments.
+fn challenge(
+    mut hash: Sha3_512,
+    bits: &[(RistrettoPoint, RistrettoPoint, RistrettoPoint)],
+    coefficient_commitments: &[RistrettoPoint],
+) -> DalekScalar {
+    for (c_l, c_a, c_b) in bits {
+        append_transcript_field(&mut hash, b"bit-commitment", &c_l.compress());
+        append_transcript_field(&mut hash, b"a-commitment", &c_a.compress());
+        append_transcript_field(&mut hash, b"b-commitment", &c_b.compress());
+    }
+    for c_d in coefficient_commitments {
+        append_transcript_field(&mut hash, b"coefficient-commitment", &c_d.compress());
+    }
+    DalekScalar::from_hash(hash)
+}
+
+impl OneOfManyProof {
+    /// Prove knowledge of `private_value`, the private key for `ring[index]`. `transcript_hash` is
+    /// the transcript, hashed with the ring (see `Transcript::hash_with_ring`).
+    pub(crate) fn prove(
+        transcript_hash: Sha3_512,
+        ring: &[RistrettoPoint],
+        index: usize,
+        private_value: &Scalar,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let m = bit_count(ring.len());
+        let h = commitment_generator();
+        let g = RISTRETTO_BASEPOINT_POINT;
+        let commit = |v: &DalekScalar, r: &DalekScalar| v * h + DalekPoint::mul_base(r);
+        let mut random = || Scalar::random(rng).0;
+
+        // l_j, r_j, a_j, s_j, t_j. These (like the rhos below) would reveal the signer's index, so
+        // they get zeroized at the end.
+        let mut secrets = (0..m)
+            .map(|j| {
+                let l = DalekScalar::from(((index >> j) & 1) as u64);
+                [l, random(), random(), random(), random()]
+            })
+            .collect::<Vec<_>>();
+        let bit_commitments = secrets
+            .iter()
+            .map(|[l, r, a, s, t]| {
+                (
+                    RistrettoPoint::from(commit(l, r)),
+                    RistrettoPoint::from(commit(a, s)),
+                    RistrettoPoint::from(commit(&(l * a), t)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The coefficients of p_i, lowest degree first, for every (padded) index i. f_{j,1}(x) =
+        // l_j x + a_j, and f_{j,0}(x) = x - f_{j,1}(x) = (1 - l_j) x - a_j.
+        let mut factors = secrets
+            .iter()
+            .map(|[l, _, a, _, _]| ((-a, DalekScalar::ONE - l), (*a, *l)))
+            .collect::<Vec<_>>();
+        let mut polynomials = fold_padding(
+            bit_products(vec![DalekScalar::ONE], &factors, |p, (c0, c1)| {
+                let mut product = vec![DalekScalar::ZERO; p.len() + 1];
+                for (k, coefficient) in p.iter().enumerate() {
+                    product[k] += coefficient * c0;
+                    product[k + 1] += coefficient * c1;
+                }
+                product
+            })
+            .into_iter()
+            .map(Polynomial)
+            .collect(),
+            ring.len(),
+        );
+
+        // c_d_k = sum_i p_{i,k} K_i + Com(0; rho_k)
+        let mut rhos = (0..m).map(|_| random()).collect::<Vec<_>>();
+        let coefficient_commitments = rhos
+            .iter()
+            .enumerate()
+            .map(|(k, rho)| {
+                RistrettoPoint::from(DalekPoint::multiscalar_mul(
+                    polynomials.iter().map(|p| p.0[k]).chain([*rho]),
+                    ring.iter().map(|keypoint| keypoint.inner()).chain([g]),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let x = challenge(transcript_hash, &bit_commitments, &coefficient_commitments);
+
+        let bits = secrets
+            .iter()
+            .zip(bit_commitments)
+            .map(|([l, r, a, s, t], (c_l, c_a, c_b))| {
+                let f = l * x + a;
+                BitProof {
+                    bit_commitment: c_l,
+                    a_commitment: c_a,
+                    b_commitment: c_b,
+                    f: Scalar(f),
+                    z_a: Scalar(r * x + s),
+                    z_b: Scalar(r * (x - f) + t),
+                }
+            })
+            .collect();
+
+        // z_d = k x^m - sum_k rho_k x^k
+        let mut x_power = DalekScalar::ONE;
+        let mut response = DalekScalar::ZERO;
+        for rho in rhos.iter() {
+            response -= rho * x_power;
+            x_power *= x;
+        }
+        response += private_value.0 * x_power;
+
+        secrets.zeroize();
+        factors.zeroize();
+        rhos.zeroize();
+        polynomials.zeroize();
+
+        OneOfManyProof {
+            bits,
+            coefficient_commitments,
+            response: Scalar(response),
+        }
+    }
+
+    /// Verify a proof made by `prove`, with the same transcript and ring.
+    pub(crate) fn verify(&self, transcript_hash: Sha3_512, ring: &[RistrettoPoint]) -> bool {
+        match self.verification_terms(transcript_hash, ring) {
+            Some(terms) => terms.sum_to_identity(),
+            None => false,
+        }
+    }
+
+    /// Everything that `verify` checks, as a single random linear combination of points, which
+    /// sums to the identity if (and, except with negligible probability, only if) the proof is
+    /// valid. Returns None if the proof is malformed.
+    ///
+    /// Since each equation gets its own random weight, the terms of many proofs can be added
+    /// together and checked with one multiscalar multiplication, which is much faster than checking
+    /// them one at a time. See `SignedMessage::verify_batch`.
+    pub(crate) fn verification_terms(
+        &self,
+        transcript_hash: Sha3_512,
+        ring: &[RistrettoPoint],
+    ) -> Option<VerificationTerms> {
+        let m = bit_count(ring.len());
+        if ring.is_empty() || self.bits.len() != m || self.coefficient_commitments.len() != m {
+            return None;
+        }
+        // Without std, there's no RNG to draw the weights from, so they're derived from the proof
+        // instead; see `HedgedRng::for_verification`.
+        #[cfg(feature = "std")]
+        let mut rng = OsRng;
+        #[cfg(not(feature = "std"))]
+        let mut rng = crate::HedgedRng::for_verification(transcript_hash.clone(), self);
+        let mut random = || Scalar::random(&mut rng).0;
+
+        let x = challenge(
+            transcript_hash,
+            &self
+                .bits
+                .iter()
+                .map(|b| {
+                    (
+                        b.bit_commitment.clone(),
+                        b.a_commitment.clone(),
+                        b.b_commitment.clone(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            &self.coefficient_commitments,
+        );
+
+        let mut terms = VerificationTerms::default();
+        let mut h_scalar = DalekScalar::ZERO;
+        let mut g_scalar = DalekScalar::ZERO;
+
+        // For each bit, with random weights u and v:
+        // u (x c_l + c_a - Com(f; z_a)) + v ((x - f) c_l + c_b - Com(0; z_b)) = 0
+        for b in self.bits.iter() {
+            let (u, v) = (random(), random());
+            let (f, z_a, z_b) = (b.f.0, b.z_a.0, b.z_b.0);
+            terms.push(u * x + v * (x - f), b.bit_commitment.inner());
+            terms.push(u, b.a_commitment.inner());
+            terms.push(v, b.b_commitment.inner());
+            h_scalar -= u * f;
+            g_scalar -= u * z_a + v * z_b;
+        }
+
+        // With another random weight w:
+        // w (sum_i p_i(x) K_i - sum_k x^k c_d_k - Com(0; z_d)) = 0
+        let w = random();
+        let evaluations = fold_padding(
+            bit_products(
+                w,
+                &self
+                    .bits
+                    .iter()
+                    .map(|b| (x - b.f.0, b.f.0))
+                    .collect::<Vec<_>>(),
+                |p, f| p * f,
+            ),
+            ring.len(),
+        );
+        for (evaluation, keypoint) in evaluations.into_iter().zip(ring) {
+            terms.push(evaluation, keypoint.inner());
+        }
+        let mut x_power = w;
+        for c_d in self.coefficient_commitments.iter() {
+            terms.push(-x_power, c_d.inner());
+            x_power *= x;
+        }
+        g_scalar -= w * self.response.0;
+
+        terms.push(h_scalar, commitment_generator());
+        terms.push(g_scalar, RISTRETTO_BASEPOINT_POINT);
+        Some(terms)
+    }
+}
+
+/// A linear combination of points, which should sum to the identity. See
+/// `OneOfManyProof::verification_terms`.
+#[derive(Default)]
+pub(crate) struct VerificationTerms {
+    scalars: Vec<DalekScalar>,
+    points: Vec<DalekPoint>,
+}
+
+impl VerificationTerms {
+    fn push(&mut self, scalar: DalekScalar, point: DalekPoint) {
+        self.scalars.push(scalar);
+        self.points.push(point);
+    }
+
+    pub(crate) fn extend(&mut self, other: VerificationTerms) {
+        self.scalars.extend(other.scalars);
+        self.points.extend(other.points);
+    }
+
+    pub(crate) fn sum_to_identity(&self) -> bool {
+        DalekPoint::vartime_multiscalar_mul(&self.scalars, &self.points).is_identity()
+    }
+}
+
+/// A polynomial's coefficients, lowest degree first. Only here so that they can be added up (in
+/// `fold_padding`) and zeroized.
+#[derive(Clone, Zeroize)]
+struct Polynomial(Vec<DalekScalar>);
+
+impl core::ops::AddAssign for Polynomial {
+    fn add_assign(&mut self, rhs: Polynomial) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a += b;
+        }
+    }
+}
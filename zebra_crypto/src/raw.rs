@@ -0,0 +1,159 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::ristretto::RistrettoPoint;
+use crate::{PrivateKey, PublicKey, RingProof, RingScheme, Transcript};
+
+pub(crate) const RAW_TRANSCRIPT_DOMAIN: &[u8] = b"Zebra raw ring signature v1";
+
+/// The first byte of every encoded `RingSignature`.
+const RAW_ENCODING_VERSION: u8 = 1;
+
+// The binary encoding of a RingSignature is stable: anything encoded with a given version byte will
+// always decode the same way. It's the version byte (currently 1), followed by:
+//
+// - The ring: a little-endian u32 count, then each member's 32-byte compressed keypoint, in strictly
+//   increasing (lexicographic) order, so that every ring has exactly one encoding.
+// - The proof: a one-byte scheme tag, then, for SAG (0), the 32-byte challenge, a u32 count, and
+//   that many 32-byte responses, one for each ring member, in ring order; or for one-of-many (1),
+//   the `OneOfManyProof`, as Borsh encodes it.
+//
+// Scalars are little-endian and must be fully reduced, and points must be canonical. Nothing may
+// follow the proof. This is all just the Borsh encoding of (ring, RingProof), so RingProof's
+// variants must never be reordered.
+//
+// The transcript is domain-separated from all of our other transcripts, so a RingSignature can't be
+// passed off as a signed message (or a key attestation), or vice versa.
+
+/// A ring signature of arbitrary bytes by one of a ring of keypoints, with no identities and no
+/// ASCII armor, for embedding in other protocols. See `to_bytes` for its binary encoding.
+///
+/// Nothing about a ring member's identity is signed, or checked by `verify`; use
+/// `verify_with_keys` to check that the ring is a particular set of valid public keys. Protocols
+/// that embed these signatures should also include some protocol-specific domain separation in the
+/// signed bytes.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop)]
+pub struct RingSignature {
+    // Sorted, with no repeats.
+    ring: Vec<RistrettoPoint>,
+    proof: RingProof,
+}
+
+impl RingSignature {
+    /// Sign `message` with `my_key`, with a ring of `my_key` and `other_keys`.
+    #[cfg(feature = "std")]
+    pub fn sign(
+        message: &[u8],
+        my_key: &PrivateKey,
+        other_keys: &[PublicKey],
+        scheme: RingScheme,
+    ) -> Self {
+        Self::sign_keypoints(
+            message,
+            my_key,
+            &other_keys
+                .iter()
+                .map(|k| k.keypoint_bytes())
+                .collect::<Vec<_>>(),
+            scheme,
+        )
+        .expect("Public keys always have valid keypoints")
+    }
+
+    /// Sign `message` with `my_key`, with a ring of `my_key` and the given (compressed) keypoints.
+    /// Returns None if any of them isn't a valid point.
+    #[cfg(feature = "std")]
+    pub fn sign_keypoints(
+        message: &[u8],
+        my_key: &PrivateKey,
+        other_keypoints: &[[u8; 32]],
+        scheme: RingScheme,
+    ) -> Option<Self> {
+        Self::sign_keypoints_with_rng(message, my_key, other_keypoints, scheme, &mut OsRng)
+    }
+
+    /// Like `sign_keypoints`, but with randomness from `rng` rather than from the operating system.
+    pub fn sign_keypoints_with_rng(
+        message: &[u8],
+        my_key: &PrivateKey,
+        other_keypoints: &[[u8; 32]],
+        scheme: RingScheme,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<Self> {
+        let mut ring = BTreeSet::new();
+        for keypoint in other_keypoints {
+            ring.insert(RistrettoPoint::try_from_slice(keypoint).ok()?);
+        }
+        ring.insert(RistrettoPoint::mul_base(&my_key.key));
+        let ring = ring.into_iter().collect::<Vec<_>>();
+        let proof = RingProof::new(
+            scheme,
+            Transcript::Raw { message, scheme },
+            &my_key.key,
+            &ring,
+            None,
+            rng,
+        );
+        Some(RingSignature { ring, proof })
+    }
+
+    /// Verify that the holder of one of the ring's private keys signed `message`.
+    pub fn verify(&self, message: &[u8]) -> bool {
+        let scheme = self.scheme();
+        self.proof
+            .verify(Transcript::Raw { message, scheme }, &self.ring, None)
+    }
+
+    /// Verify the signature, and also that the ring is exactly the given keys (in any order), and
+    /// that their attestations are valid.
+    pub fn verify_with_keys(&self, message: &[u8], keys: &[PublicKey]) -> bool {
+        let keypoints = keys
+            .iter()
+            .map(|k| k.keypoint.clone())
+            .collect::<BTreeSet<_>>();
+        keypoints.len() == keys.len()
+            && keypoints.into_iter().eq(self.ring.iter().cloned())
+            && keys.iter().all(|k| k.validate_attestation())
+            && self.verify(message)
+    }
+
+    /// The ring signature scheme that this was signed with.
+    pub fn scheme(&self) -> RingScheme {
+        self.proof.scheme()
+    }
+
+    /// The ring's (compressed) keypoints, in increasing order.
+    pub fn ring(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.ring.iter().map(|k| k.compress())
+    }
+
+    /// The binary encoding of this signature. Its format is stable, and documented in the source.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![RAW_ENCODING_VERSION];
+        (&self.ring, &self.proof)
+            .serialize(&mut bytes)
+            .expect("Failed to serialize into unbounded buffer");
+        bytes
+    }
+
+    /// Decode a signature encoded by `to_bytes`. Returns None unless `bytes` is exactly the
+    /// encoding of some signature (which may or may not verify).
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&version, rest) = bytes.split_first()?;
+        if version != RAW_ENCODING_VERSION {
+            return None;
+        }
+        let (ring, proof) = <(Vec<RistrettoPoint>, RingProof)>::try_from_slice(rest).ok()?;
+        if ring.is_empty() || ring.windows(2).any(|w| w[0] >= w[1]) {
+            return None;
+        }
+        Some(RingSignature { ring, proof })
+    }
+}
@@ -1,94 +1,169 @@
-use rand::rngs::OsRng;
+use alloc::format;
+use alloc::string::ToString;
+
+use curve25519_dalek::traits::{MultiscalarMul, VartimeMultiscalarMul};
+use rand::{CryptoRng, RngCore};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use sha3::Sha3_512;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+type DalekPoint = curve25519_dalek::ristretto::RistrettoPoint;
+
 // We use newtype wrappers for RistrettoPoint and Scalar because we need to (de)serialize them
 // using Borsh, and foreign impls aren't allowed. Their APIs stay private to this file, and
-// we only duplicate as much as we use from the dalek API.
-#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
-pub struct RistrettoPoint(pub(crate) curve25519_dalek::ristretto::RistrettoPoint);
+// we only duplicate as much as we use from the dalek API. The exception is `one_of_many.rs`, which
+// needs enough of the dalek arithmetic (multiscalar multiplication, mostly) that it works on the
+// inner values directly.
+//
+// A point also carries its compressed encoding, which is what we compare, sort, hash and serialize,
+// and which is expensive to compute (it takes a field inversion and a square root). It's computed
+// exactly once, whenever a point is created: by `From<DalekPoint>`, or by deserialization, which
+// only accepts canonical encodings, so the bytes it read are the encoding. Nothing else can
+// construct or modify a point, so `compressed` is always `point.compress()`.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct RistrettoPoint {
+    point: DalekPoint,
+    compressed: [u8; 32],
+}
+
+impl From<DalekPoint> for RistrettoPoint {
+    fn from(point: DalekPoint) -> Self {
+        RistrettoPoint {
+            compressed: point.compress().to_bytes(),
+            point,
+        }
+    }
+}
+
+// The dalek docs specify that "two points are equal if and only if their encodings are equal", so
+// we can compare the cached encodings rather than the points. We implement Ord and PartialOrd
+// because we use these in BTree-based containers, and they're consistent with Eq for the same
+// reason.
+impl PartialEq for RistrettoPoint {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.compressed == rhs.compressed
+    }
+}
+
+impl Eq for RistrettoPoint {}
 
-// We implement Ord and PartialOrd because we use these in BTree-based containers. Note that these
-// implementations use the compressed representation, and the dalek docs speicfy that "two points
-// are equal if and only if their encodings are equal". Thus, these Ord/PartialOrd implementations
-// are compatible with the provided Eq/PartialEq instances (the byte sequences' equality
-// corresponsd to the point structs' equality).
 impl Ord for RistrettoPoint {
-    fn cmp(&self, rhs: &Self) -> std::cmp::Ordering {
-        self.compress().cmp(&rhs.compress())
+    fn cmp(&self, rhs: &Self) -> core::cmp::Ordering {
+        self.compressed.cmp(&rhs.compressed)
     }
 }
 
 impl PartialOrd for RistrettoPoint {
-    fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(rhs))
     }
 }
 
 impl RistrettoPoint {
     pub(crate) fn compress(&self) -> [u8; 32] {
-        self.0.compress().to_bytes()
+        self.compressed
+    }
+
+    /// The dalek point itself. See `one_of_many.rs`.
+    pub(crate) fn inner(&self) -> DalekPoint {
+        self.point
     }
 
     pub(crate) fn mul_base(s: &Scalar) -> Self {
-        RistrettoPoint(curve25519_dalek::ristretto::RistrettoPoint::mul_base(&s.0))
+        DalekPoint::mul_base(&s.0).into()
     }
 
-    #[cfg(test)]
-    pub(crate) fn random() -> Self {
-        RistrettoPoint(curve25519_dalek::ristretto::RistrettoPoint::random(
-            &mut OsRng,
-        ))
+    /// Hash to a point whose discrete log (relative to the basepoint) nobody knows.
+    pub(crate) fn from_hash(hash: Sha3_512) -> Self {
+        DalekPoint::from_hash(hash).into()
     }
-}
 
-impl std::ops::Add<RistrettoPoint> for RistrettoPoint {
-    type Output = RistrettoPoint;
+    /// a A + b G, where G is the basepoint, in constant time.
+    pub(crate) fn double_scalar_mul_basepoint(
+        a: &Scalar,
+        point: &RistrettoPoint,
+        b: &Scalar,
+    ) -> Self {
+        (a.0 * point.point + DalekPoint::mul_base(&b.0)).into()
+    }
 
-    fn add(self, p: RistrettoPoint) -> Self::Output {
-        RistrettoPoint(self.0 + p.0)
+    /// a A + b G, where G is the basepoint. Variable time, so only for public inputs.
+    pub(crate) fn vartime_double_scalar_mul_basepoint(
+        a: &Scalar,
+        point: &RistrettoPoint,
+        b: &Scalar,
+    ) -> Self {
+        DalekPoint::vartime_double_scalar_mul_basepoint(&a.0, &point.point, &b.0).into()
+    }
+
+    /// The sum of s_i P_i, in constant time.
+    pub(crate) fn multiscalar_mul(scalars: &[&Scalar], points: &[&RistrettoPoint]) -> Self {
+        DalekPoint::multiscalar_mul(
+            scalars.iter().map(|s| &s.0),
+            points.iter().map(|p| &p.point),
+        )
+        .into()
+    }
+
+    /// The sum of s_i P_i. Variable time, so only for public inputs.
+    pub(crate) fn vartime_multiscalar_mul(scalars: &[&Scalar], points: &[&RistrettoPoint]) -> Self {
+        DalekPoint::vartime_multiscalar_mul(
+            scalars.iter().map(|s| &s.0),
+            points.iter().map(|p| &p.point),
+        )
+        .into()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn random(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        DalekPoint::random(rng).into()
     }
 }
 
 // We use Borsh serialization because they explicitly aim to have a bijective mapping between
 // encoded types and encodings. This is a common problem with serialization formats.
 impl BorshSerialize for RistrettoPoint {
-    fn serialize<W: std::io::Write>(&self, w: &mut W) -> Result<(), std::io::Error> {
-        self.compress().serialize(w)
+    fn serialize<W: borsh::io::Write>(&self, w: &mut W) -> Result<(), borsh::io::Error> {
+        self.compressed.serialize(w)
     }
 }
 
 impl BorshDeserialize for RistrettoPoint {
-    fn deserialize_reader<R: std::io::Read>(r: &mut R) -> Result<RistrettoPoint, std::io::Error> {
+    fn deserialize_reader<R: borsh::io::Read>(
+        r: &mut R,
+    ) -> Result<RistrettoPoint, borsh::io::Error> {
         let bytes = <[u8; 32]>::deserialize_reader(r)?;
         let compressed_point = curve25519_dalek::ristretto::CompressedRistretto::from_slice(&bytes)
             .map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
+                borsh::io::Error::new(
+                    borsh::io::ErrorKind::Other,
                     format!("Could not deserialize ristretto point: {e:?}"),
                 )
             })?;
-        let point = compressed_point.decompress().ok_or(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        let point = compressed_point.decompress().ok_or(borsh::io::Error::new(
+            borsh::io::ErrorKind::Other,
             "Could not decompress ristretto point".to_string(),
         ))?;
-        Ok(RistrettoPoint(point))
+        // Decompression rejects every non-canonical encoding, so these bytes are the encoding.
+        Ok(RistrettoPoint {
+            point,
+            compressed: bytes,
+        })
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
-pub struct Scalar(curve25519_dalek::Scalar);
+pub struct Scalar(pub(crate) curve25519_dalek::Scalar);
 
 impl Ord for Scalar {
-    fn cmp(&self, rhs: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, rhs: &Self) -> core::cmp::Ordering {
         self.0.as_bytes().cmp(rhs.0.as_bytes())
     }
 }
 
 impl PartialOrd for Scalar {
-    fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(rhs))
     }
 }
@@ -100,12 +175,16 @@ impl Scalar {
         Scalar(curve25519_dalek::Scalar::from_hash(hash))
     }
 
-    pub(crate) fn random() -> Self {
-        Scalar(curve25519_dalek::Scalar::random(&mut OsRng))
+    pub(crate) fn random(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        Scalar(curve25519_dalek::Scalar::random(rng))
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        self.0.as_bytes()
     }
 }
 
-impl std::ops::Mul<Scalar> for Scalar {
+impl core::ops::Mul<Scalar> for Scalar {
     type Output = Scalar;
 
     fn mul(self, s: Scalar) -> Self::Output {
@@ -113,15 +192,15 @@ impl std::ops::Mul<Scalar> for Scalar {
     }
 }
 
-impl std::ops::Mul<&RistrettoPoint> for Scalar {
+impl core::ops::Mul<&RistrettoPoint> for Scalar {
     type Output = RistrettoPoint;
 
     fn mul(self, p: &RistrettoPoint) -> Self::Output {
-        RistrettoPoint(self.0 * p.0)
+        (self.0 * p.point).into()
     }
 }
 
-impl std::ops::Sub<Scalar> for Scalar {
+impl core::ops::Sub<Scalar> for Scalar {
     type Output = Scalar;
 
     fn sub(self, s: Scalar) -> Self::Output {
@@ -130,17 +209,17 @@ impl std::ops::Sub<Scalar> for Scalar {
 }
 
 impl BorshSerialize for Scalar {
-    fn serialize<W: std::io::Write>(&self, w: &mut W) -> Result<(), std::io::Error> {
+    fn serialize<W: borsh::io::Write>(&self, w: &mut W) -> Result<(), borsh::io::Error> {
         self.0.as_bytes().serialize(w)
     }
 }
 
 impl BorshDeserialize for Scalar {
-    fn deserialize_reader<R: std::io::Read>(r: &mut R) -> Result<Scalar, std::io::Error> {
+    fn deserialize_reader<R: borsh::io::Read>(r: &mut R) -> Result<Scalar, borsh::io::Error> {
         let bytes = <[u8; 32]>::deserialize_reader(r)?;
         let s = Option::from(curve25519_dalek::Scalar::from_canonical_bytes(bytes)).ok_or(
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
+            borsh::io::Error::new(
+                borsh::io::ErrorKind::Other,
                 "Could not deserialize ristretto scalar".to_string(),
             ),
         )?;
@@ -0,0 +1,342 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use boringascii::BoringAscii;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::one_of_many::OneOfManyProof;
+use crate::ristretto::{RistrettoPoint, Scalar};
+use crate::{
+    valid_context, valid_headers, Canonicalization, FormatVersion, Identity, PublicKey, RingProof,
+    RingScheme, Signature, SignedMessage,
+};
+
+// How the serde representations work:
+//
+// Each type is converted to and from a plain struct (its "repr"), which serde derives the
+// representation of. Unknown fields are rejected, and absent optional fields are omitted (rather
+// than being null), so that each value has exactly one representation in a given format.
+//
+// Names, emails, messages, and so on are strings. Points, scalars, and proofs are their Borsh
+// encodings, decoded with their Borsh impls, so they're validated exactly as strictly. In
+// human-readable formats (like JSON) those bytes are uppercase hex, as in the ASCII formats, and
+// otherwise (like CBOR) they're byte strings. Identities are checked by `Identity::new`, as in
+// their Borsh impl, and signed messages get the same checks as in the ASCII parser.
+
+/// Bytes, as uppercase hex in human-readable formats, and a byte string otherwise.
+struct Bytes(Vec<u8>);
+
+impl Bytes {
+    fn encode(value: &impl BorshSerialize) -> Self {
+        Bytes(borsh::to_vec(value).expect("Failed to serialize into unbounded buffer"))
+    }
+
+    fn decode<T: BorshDeserialize, E: Error>(&self) -> Result<T, E> {
+        T::try_from_slice(&self.0).map_err(E::custom)
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_str(&hex::encode_upper(&self.0))
+        } else {
+            s.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Bytes, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(f, "bytes, or uppercase hex")
+            }
+
+            fn visit_str<E: Error>(self, s: &str) -> Result<Bytes, E> {
+                // Only uppercase hex is allowed, so that each value has exactly one encoding.
+                let bytes = hex::decode(s).map_err(E::custom)?;
+                if hex::encode_upper(&bytes) != s {
+                    return Err(E::custom("Hex must be uppercase"));
+                }
+                Ok(Bytes(bytes))
+            }
+
+            fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Bytes, E> {
+                Ok(Bytes(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E: Error>(self, bytes: Vec<u8>) -> Result<Bytes, E> {
+                Ok(Bytes(bytes))
+            }
+        }
+
+        if d.is_human_readable() {
+            d.deserialize_str(BytesVisitor)
+        } else {
+            d.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+/// For optional fields: absent is None, and present must be a value (not null).
+fn some<'de, D: Deserializer<'de>, T: Deserialize<'de>>(d: D) -> Result<Option<T>, D::Error> {
+    T::deserialize(d).map(Some)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentityRepr {
+    name: String,
+    email: BoringAscii,
+}
+
+impl Serialize for Identity {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        IdentityRepr {
+            name: self.name.clone(),
+            email: self.email.clone(),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identity {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Identity, D::Error> {
+        let repr = IdentityRepr::deserialize(d)?;
+        Identity::new(&repr.name, repr.email.as_str())
+            .ok_or_else(|| D::Error::custom("Error constructing Identity"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignatureRepr {
+    challenge: Bytes,
+    ring_responses: Vec<(Bytes, Bytes)>,
+}
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        SignatureRepr {
+            challenge: Bytes::encode(&self.challenge),
+            ring_responses: self
+                .ring_responses
+                .iter()
+                .map(|(k, r)| (Bytes::encode(k), Bytes::encode(r)))
+                .collect(),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Signature, D::Error> {
+        let repr = SignatureRepr::deserialize(d)?;
+        Ok(Signature {
+            challenge: repr.challenge.decode()?,
+            ring_responses: repr
+                .ring_responses
+                .iter()
+                .map(|(k, r)| Ok((k.decode()?, r.decode()?)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PublicKeyRepr {
+    holder: Identity,
+    keypoint: Bytes,
+    holder_attestation: Signature,
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        PublicKeyRepr {
+            holder: self.holder.clone(),
+            keypoint: Bytes::encode(&self.keypoint),
+            holder_attestation: self.holder_attestation.clone(),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<PublicKey, D::Error> {
+        let repr = PublicKeyRepr::deserialize(d)?;
+        Ok(PublicKey::new(
+            repr.holder,
+            repr.keypoint.decode()?,
+            repr.holder_attestation,
+        ))
+    }
+}
+
+// The fields are in the same order as in the ASCII format.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignedMessageRepr {
+    /// As in the ASCII format's first line, e.g. "2.0".
+    version: String,
+    #[serde(
+        default,
+        deserialize_with = "some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    context: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    claim_commitment: Option<Bytes>,
+    #[serde(
+        default,
+        deserialize_with = "some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    key_image: Option<Bytes>,
+    /// See `RingScheme::name`.
+    scheme: String,
+    /// See `Canonicalization::name`; omitted for exact messages.
+    #[serde(
+        default,
+        deserialize_with = "some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    canonicalization: Option<String>,
+    headers: Vec<(String, String)>,
+    message: String,
+    ring: Vec<PublicKey>,
+    /// For SAG, the challenge and then the responses (as a Vec), in ring order. For other schemes,
+    /// the scheme's proof.
+    proof: Bytes,
+    /// The DER-encoded RFC 3161 token, if there is one.
+    #[serde(
+        default,
+        deserialize_with = "some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    timestamp_token: Option<Bytes>,
+}
+
+impl Serialize for SignedMessage {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        SignedMessageRepr {
+            version: self.version.number().to_string(),
+            context: self.context.clone(),
+            claim_commitment: self.claim_commitment.map(|c| Bytes(c.to_vec())),
+            key_image: self.key_image.as_ref().map(Bytes::encode),
+            scheme: self.scheme().name().to_string(),
+            canonicalization: match self.canonicalization {
+                Canonicalization::Exact => None,
+                c => Some(c.name().to_string()),
+            },
+            headers: self.headers.clone(),
+            message: self.message.clone(),
+            ring: self.ring.clone(),
+            proof: match self.proof {
+                RingProof::Sag {
+                    ref challenge,
+                    ref responses,
+                } => Bytes::encode(&(challenge, responses)),
+                RingProof::OneOfMany(ref proof) => Bytes::encode(proof),
+            },
+            timestamp_token: self.timestamp_token.clone().map(Bytes),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignedMessage {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<SignedMessage, D::Error> {
+        let repr = SignedMessageRepr::deserialize(d)?;
+        let version = FormatVersion::ALL
+            .iter()
+            .copied()
+            .find(|v| v.number() == repr.version)
+            .ok_or_else(|| D::Error::custom("Unknown format version"))?;
+        let scheme = RingScheme::from_name(&repr.scheme)
+            .ok_or_else(|| D::Error::custom("Unknown ring signature scheme"))?;
+        let canonicalization = match repr.canonicalization {
+            Some(ref name) => match Canonicalization::from_name(name) {
+                Some(Canonicalization::Exact) | None => {
+                    return Err(D::Error::custom("Unknown canonicalization"))
+                }
+                Some(c) => c,
+            },
+            None => Canonicalization::Exact,
+        };
+        let claim_commitment = match repr.claim_commitment {
+            Some(c) => Some(
+                <[u8; 32]>::try_from(c.0)
+                    .map_err(|_| D::Error::custom("Claim commitments are 32 bytes"))?,
+            ),
+            None => None,
+        };
+        let key_image = match repr.key_image {
+            Some(i) => Some(i.decode::<RistrettoPoint, _>()?),
+            None => None,
+        };
+        let proof = match scheme {
+            RingScheme::Sag => {
+                let (challenge, responses) = repr.proof.decode::<(Scalar, Vec<Scalar>), _>()?;
+                RingProof::Sag {
+                    challenge,
+                    responses,
+                }
+            }
+            RingScheme::OneOfMany => {
+                RingProof::OneOfMany(repr.proof.decode::<OneOfManyProof, _>()?)
+            }
+        };
+
+        if !version.supports_scheme(scheme)
+            || !version.supports_canonicalization(canonicalization)
+            || (claim_commitment.is_some() && !version.supports_claims())
+            || (key_image.is_some()
+                && (!version.supports_disavowal() || !scheme.supports_key_images()))
+        {
+            return Err(D::Error::custom("Unsupported in this format version"));
+        }
+        if let Some(ref context) = repr.context {
+            if !version.supports_context() || !valid_context(context) {
+                return Err(D::Error::custom("Invalid context"));
+            }
+        }
+        if !repr.headers.is_empty()
+            && (!version.supports_headers() || !valid_headers(&repr.headers))
+        {
+            return Err(D::Error::custom("Invalid headers"));
+        }
+        if repr
+            .timestamp_token
+            .as_ref()
+            .filter(|t| t.0.is_empty())
+            .is_some()
+        {
+            return Err(D::Error::custom("Empty timestamp token"));
+        }
+
+        Ok(SignedMessage {
+            message: repr.message,
+            version,
+            context: repr.context,
+            headers: repr.headers,
+            claim_commitment,
+            key_image,
+            ring: repr.ring,
+            proof,
+            canonicalization,
+            timestamp_token: repr.timestamp_token.map(|t| t.0),
+        })
+    }
+}
@@ -0,0 +1,366 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::ristretto::RistrettoPoint;
+use crate::{append_transcript_field, key_line, z85, PrivateKey, PublicKey, Signature, Transcript};
+
+pub(crate) const THRESHOLD_TRANSCRIPT_DOMAIN: &[u8] = b"Zebra threshold ring signature v1";
+const THRESHOLD_BASE_DOMAIN: &[u8] = b"Zebra threshold base point v1";
+
+// How threshold signatures work:
+//
+// A threshold signature is just k tagged ring signatures (see `Signature::sign_tagged`) of the same
+// proposal, each with the whole ring, and each tagged with a different key image. The base point
+// for the key images is derived from the proposal, so every co-signer uses the same one, and a key
+// always produces the same key image for a given proposal. Each tagged signature proves that its key
+// image was made with *some* ring member's private key, so k distinct key images mean that at least
+// k distinct ring members signed, without revealing which ones.
+//
+// Since the base point is different for each proposal, key images can't be used to link a
+// co-signer's shares across proposals.
+//
+// Signing is a two-round protocol, with each round's messages passed around as ASCII text:
+//
+// 1. Someone writes a ThresholdProposal (the message, the ring, and the threshold), and sends it to
+//    the ring members.
+// 2. Each ring member who agrees to sign sends back a ThresholdShare. Anyone with the proposal
+//    can check a share, and once there are enough of them, combine them into a
+//    ThresholdSignedMessage.
+//
+// Shares don't identify their signer, so whoever combines them only knows who signed if the way
+// the shares were passed around tells them.
+
+/// A request for at least `threshold` members of a ring to sign a message together.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct ThresholdProposal {
+    message: String,
+    threshold: u32,
+    // Sorted by keypoint, without duplicates, so that every co-signer uses the same ring.
+    ring: Vec<PublicKey>,
+}
+
+/// One co-signer's contribution to a threshold signature.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct ThresholdShare {
+    key_image: RistrettoPoint,
+    signature: Signature,
+}
+
+/// A message signed by at least `threshold()` of the holders of the keys in `ring()`.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop, BorshSerialize, BorshDeserialize)]
+pub struct ThresholdSignedMessage {
+    proposal: ThresholdProposal,
+    shares: Vec<ThresholdShare>,
+}
+
+impl ThresholdProposal {
+    /// Propose that at least `threshold` of the holders of `ring` sign `message`. Returns None if
+    /// the threshold is zero, or larger than the number of distinct keys in the ring.
+    pub fn new(message: &str, threshold: usize, ring: &[PublicKey]) -> Option<Self> {
+        let mut ring = ring.to_vec();
+        ring.sort_by_key(|k| k.keypoint.compress());
+        ring.dedup_by_key(|k| k.keypoint.compress());
+        if threshold == 0 || threshold > ring.len() {
+            return None;
+        }
+        Some(ThresholdProposal {
+            message: message.to_string(),
+            threshold: u32::try_from(threshold).ok()?,
+            ring,
+        })
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold as usize
+    }
+
+    pub fn ring(&self) -> impl Iterator<Item = &PublicKey> {
+        self.ring.iter()
+    }
+
+    /// Whether this proposal is well-formed, and all of its keys have valid attestations.
+    pub fn validate(&self) -> bool {
+        self.threshold >= 1
+            && self.threshold() <= self.ring.len()
+            && self
+                .ring
+                .windows(2)
+                .all(|w| w[0].keypoint.compress() < w[1].keypoint.compress())
+            && self.ring.iter().all(|k| k.validate_attestation())
+    }
+
+    /// Sign this proposal. Returns None if `my_key` isn't in the ring. Signing the same proposal
+    /// twice with the same key produces shares with the same key image, which only count once.
+    #[cfg(feature = "std")]
+    pub fn sign(&self, my_key: &PrivateKey) -> Option<ThresholdShare> {
+        self.sign_with_rng(my_key, &mut OsRng)
+    }
+
+    /// Like `sign`, but with randomness from `rng` rather than from the operating system.
+    pub fn sign_with_rng(
+        &self,
+        my_key: &PrivateKey,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Option<ThresholdShare> {
+        let my_public_key = my_key.public();
+        if !self.ring.contains(&my_public_key) {
+            return None;
+        }
+        let base = self.base_point();
+        let key_image = my_key.key.clone() * &base;
+        let signature = Signature::sign_tagged(
+            self.transcript(),
+            my_key.key.clone(),
+            &self
+                .ring
+                .iter()
+                .filter(|k| *k != &my_public_key)
+                .map(|k| k.keypoint.clone())
+                .collect::<Vec<_>>(),
+            Some((&base, &key_image)),
+            rng,
+        );
+        Some(ThresholdShare {
+            key_image,
+            signature,
+        })
+    }
+
+    /// Check that `share` is a valid signature of this proposal by one of the ring members.
+    pub fn verify_share(&self, share: &ThresholdShare) -> bool {
+        self.verify_share_with_base(share, &self.base_point())
+    }
+
+    /// Combine shares into a threshold signature. Invalid and duplicate shares are ignored.
+    /// Returns None if there aren't at least `threshold()` valid, distinct shares.
+    pub fn combine(&self, shares: &[ThresholdShare]) -> Option<ThresholdSignedMessage> {
+        let base = self.base_point();
+        let mut seen = BTreeSet::new();
+        let shares = shares
+            .iter()
+            .filter(|s| self.verify_share_with_base(s, &base))
+            .filter(|s| seen.insert(s.key_image.compress()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if shares.len() < self.threshold() {
+            return None;
+        }
+        Some(ThresholdSignedMessage {
+            proposal: self.clone(),
+            shares,
+        })
+    }
+
+    fn transcript(&self) -> Transcript<'_> {
+        Transcript::Threshold {
+            message: self.message.as_bytes(),
+            threshold: self.threshold,
+        }
+    }
+
+    fn base_point(&self) -> RistrettoPoint {
+        let mut hash = self
+            .transcript()
+            .hash_with_ring(self.ring.iter().map(|k| &k.keypoint));
+        append_transcript_field(&mut hash, b"purpose", THRESHOLD_BASE_DOMAIN);
+        RistrettoPoint::from_hash(hash)
+    }
+
+    fn verify_share_with_base(&self, share: &ThresholdShare, base: &RistrettoPoint) -> bool {
+        share.signature.ring_responses.len() == self.ring.len()
+            && share
+                .signature
+                .ring_responses
+                .iter()
+                .zip(self.ring.iter())
+                .all(|((keypoint, _), k)| keypoint == &k.keypoint)
+            && share
+                .signature
+                .verify_tagged(self.transcript(), Some((base, &share.key_image)))
+    }
+}
+
+impl ThresholdSignedMessage {
+    pub fn message(&self) -> &str {
+        self.proposal.message()
+    }
+
+    /// The number of ring members that (at least) must have signed.
+    pub fn threshold(&self) -> usize {
+        self.proposal.threshold()
+    }
+
+    /// The number of distinct ring members that signed, which may be more than the threshold.
+    pub fn signer_count(&self) -> usize {
+        self.shares.len()
+    }
+
+    pub fn ring(&self) -> impl Iterator<Item = &PublicKey> {
+        self.proposal.ring()
+    }
+
+    pub fn verify(&self) -> bool {
+        let base = self.proposal.base_point();
+        let distinct_images = self
+            .shares
+            .iter()
+            .map(|s| s.key_image.compress())
+            .collect::<BTreeSet<_>>();
+        self.proposal.validate()
+            && self.shares.len() >= self.proposal.threshold()
+            && distinct_images.len() == self.shares.len()
+            && self
+                .shares
+                .iter()
+                .all(|s| self.proposal.verify_share_with_base(s, &base))
+    }
+}
+
+// The ASCII formats for proposals and signed messages look much like a SignedMessage, with the
+// message, then the ring, then all of the data z85-encoded on one line. The human-readable parts
+// are only there for humans: the parser decodes the data line, and then checks that it renders
+// exactly the same text, so there's still exactly one encoding of each value. Shares are just the
+// data line, between fixed first and last lines.
+
+const PROPOSAL_FIRST_LINE: &str =
+    "The following message has been proposed for Zebra threshold signing:";
+const PROPOSAL_RING_LINE: &str =
+    "It needs signatures from at least {k} of the holders of these {n} keys:";
+const PROPOSAL_LAST_LINE: &str = "To sign it, paste this entire proposal into the Zebra app (starting with \"The following message\" and ending with this line).";
+
+const SHARE_FIRST_LINE: &str = "The following is a share of a Zebra threshold signature:";
+const SHARE_LAST_LINE: &str =
+    "To use it, paste it into the Zebra app along with the proposal it signs.";
+
+const SIGNED_FIRST_LINE: &str =
+    "The following message has been signed using Zebra threshold signatures:";
+const SIGNED_RING_LINE: &str =
+    "It was signed by at least {k} of the holders of private keys corresponding to these {n} fingerprints:";
+const SIGNED_LAST_LINE: &str = "To verify this signature, paste this entire message into the Zebra app (starting with \"The following message\" and ending with this line).";
+
+pub(crate) fn z85_line<T: BorshSerialize>(value: &T) -> String {
+    let mut bytes = vec![];
+    value
+        .serialize(&mut bytes)
+        .expect("Failed to serialize into unbounded buffer");
+    z85::encode(&bytes)
+}
+
+fn render(
+    first_line: &str,
+    ring_line: &str,
+    last_line: &str,
+    proposal: &ThresholdProposal,
+    data: String,
+) -> String {
+    let mut parts = vec![
+        first_line.to_string(),
+        "\"\"\"".to_string(),
+        proposal.message.clone(),
+        "\"\"\"".to_string(),
+        "".to_string(),
+        ring_line
+            .replace("{k}", &proposal.threshold.to_string())
+            .replace("{n}", &proposal.ring.len().to_string()),
+        "".to_string(),
+    ];
+    parts.extend(proposal.ring.iter().map(key_line));
+    parts.push("".to_string());
+    parts.push(data);
+    parts.push("".to_string());
+    parts.push(last_line.to_string());
+    parts.join("\n")
+}
+
+// Decode the data line (which is always the third-to-last line), and check that the value renders
+// back to exactly the same text.
+pub(crate) fn parse<T: BorshDeserialize>(
+    s: &str,
+    rendered: impl Fn(&T) -> String,
+) -> Result<T, ()> {
+    let s = s.trim();
+    let lines = s.split('\n').collect::<Vec<_>>();
+    if lines.len() < 3 {
+        return Err(());
+    }
+    let bytes = z85::decode(lines[lines.len() - 3]).ok_or(())?;
+    let value = T::try_from_slice(&bytes).map_err(|_| ())?;
+    if rendered(&value) != s {
+        return Err(());
+    }
+    Ok(value)
+}
+
+impl From<&ThresholdProposal> for String {
+    fn from(p: &ThresholdProposal) -> String {
+        render(
+            PROPOSAL_FIRST_LINE,
+            PROPOSAL_RING_LINE,
+            PROPOSAL_LAST_LINE,
+            p,
+            z85_line(p),
+        )
+    }
+}
+
+impl FromStr for ThresholdProposal {
+    type Err = ();
+    /// Success does *not* imply a valid proposal; see `validate`.
+    fn from_str(s: &str) -> Result<ThresholdProposal, ()> {
+        parse(s, |value: &ThresholdProposal| String::from(value))
+    }
+}
+
+impl From<&ThresholdShare> for String {
+    fn from(share: &ThresholdShare) -> String {
+        [
+            SHARE_FIRST_LINE.to_string(),
+            "".to_string(),
+            z85_line(share),
+            "".to_string(),
+            SHARE_LAST_LINE.to_string(),
+        ]
+        .join("\n")
+    }
+}
+
+impl FromStr for ThresholdShare {
+    type Err = ();
+    /// Success does *not* imply a valid share; see `ThresholdProposal::verify_share`.
+    fn from_str(s: &str) -> Result<ThresholdShare, ()> {
+        parse(s, |value: &ThresholdShare| String::from(value))
+    }
+}
+
+impl From<&ThresholdSignedMessage> for String {
+    fn from(m: &ThresholdSignedMessage) -> String {
+        render(
+            SIGNED_FIRST_LINE,
+            SIGNED_RING_LINE,
+            SIGNED_LAST_LINE,
+            &m.proposal,
+            z85_line(m),
+        )
+    }
+}
+
+impl FromStr for ThresholdSignedMessage {
+    type Err = ();
+    /// Success does *not* imply a valid signature.
+    fn from_str(s: &str) -> Result<ThresholdSignedMessage, ()> {
+        parse(s, |value: &ThresholdSignedMessage| String::from(value))
+    }
+}
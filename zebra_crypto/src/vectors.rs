@@ -0,0 +1,285 @@
+//! Known-answer test vectors for Zebra's public formats: the text format of public keys, the ASCII
+//! format of signed messages, and the Borsh encodings of both. An independent implementation can
+//! run `check` against them, and this crate checks itself against them (see `Native`).
+//!
+//! The vectors themselves live in the `vectors` directory of this crate, one file each, so that
+//! they can be read without Rust:
+//!
+//! - `public_keys/NAME.txt` is a public key in text format, and `public_keys/NAME.hex` is its
+//!   Borsh encoding (in uppercase hex), if it's valid. If there's no `.hex` file, the text must be
+//!   rejected.
+//! - `signed_messages/NAME.txt` is a signed message in the ASCII format, and
+//!   `signed_messages/NAME.hex` is its Borsh encoding, if it's well-formed. If there's no `.hex`
+//!   file, the text must be rejected. Well-formed messages may still fail to verify; see
+//!   `SignedMessageVector::valid`.
+//! - `encodings/NAME.hex` is a Borsh encoding that must be rejected.
+//!
+//! None of the files end with a newline. The keys and messages were made with
+//! `PrivateKey::new_with_rng` and `SignedMessage::sign_with_options_and_rng`, with a `StdRng`
+//! (from rand 0.8) seeded with a small integer. They're part of the formats' specification, so
+//! they should never change; new cases get new files.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use borsh::BorshDeserialize;
+
+use crate::{PublicKey, SignedMessage};
+
+/// A public key in text format, and what it should parse to.
+pub struct PublicKeyVector {
+    pub name: &'static str,
+    pub text: &'static str,
+    /// The key's Borsh encoding, in uppercase hex, or None if `text` must be rejected. Decoding it
+    /// must give back `text` exactly.
+    pub encoding: Option<&'static str>,
+    /// The key's fingerprint, if `text` is valid.
+    pub fingerprint: Option<&'static str>,
+}
+
+/// A signed message in the ASCII format, and what it should parse to.
+pub struct SignedMessageVector {
+    pub name: &'static str,
+    pub text: &'static str,
+    /// The message's Borsh encoding, in uppercase hex, or None if `text` must be rejected. Decoding
+    /// it must give back `text` exactly.
+    pub encoding: Option<&'static str>,
+    /// Whether the message verifies. Always false if `encoding` is None.
+    pub valid: bool,
+}
+
+/// What a Borsh encoding claims to encode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EncodingKind {
+    PublicKey,
+    SignedMessage,
+}
+
+/// A Borsh encoding that must be rejected.
+pub struct InvalidEncodingVector {
+    pub name: &'static str,
+    pub kind: EncodingKind,
+    /// In uppercase hex.
+    pub encoding: &'static str,
+}
+
+macro_rules! vector_file {
+    ($dir:literal, $name:literal, $extension:literal) => {
+        include_str!(concat!("../vectors/", $dir, "/", $name, ".", $extension))
+    };
+}
+
+macro_rules! public_key {
+    ($name:literal) => {
+        PublicKeyVector {
+            name: $name,
+            text: vector_file!("public_keys", $name, "txt"),
+            encoding: None,
+            fingerprint: None,
+        }
+    };
+    ($name:literal, $fingerprint:literal) => {
+        PublicKeyVector {
+            name: $name,
+            text: vector_file!("public_keys", $name, "txt"),
+            encoding: Some(vector_file!("public_keys", $name, "hex")),
+            fingerprint: Some($fingerprint),
+        }
+    };
+}
+
+macro_rules! signed_message {
+    ($name:literal) => {
+        SignedMessageVector {
+            name: $name,
+            text: vector_file!("signed_messages", $name, "txt"),
+            encoding: None,
+            valid: false,
+        }
+    };
+    ($name:literal, $valid:literal) => {
+        SignedMessageVector {
+            name: $name,
+            text: vector_file!("signed_messages", $name, "txt"),
+            encoding: Some(vector_file!("signed_messages", $name, "hex")),
+            valid: $valid,
+        }
+    };
+}
+
+macro_rules! invalid_encoding {
+    ($name:literal, $kind:ident) => {
+        InvalidEncodingVector {
+            name: $name,
+            kind: EncodingKind::$kind,
+            encoding: vector_file!("encodings", $name, "hex"),
+        }
+    };
+}
+
+pub const PUBLIC_KEYS: &[PublicKeyVector] = &[
+    public_key!("alice", "&Ybjch*Es> J7F5Z{]{1m tW6*?!u)/p >n9Rcy:G4)"),
+    public_key!("bob", "-5G$JL&[7< YR[!zpt>!? tEGv^ljF@B VNgE[A]XyV"),
+    // A name outside of ASCII.
+    public_key!("carol", "Y]}sh).n%2 zVOEN}rYIh xXE&TeS=$f ncKstZ]9L]"),
+    // Only uppercase hex is allowed.
+    public_key!("lowercase_hex"),
+    public_key!("trailing_space"),
+    // Bob's keypoint and attestation, with a different name. The attestation doesn't match.
+    public_key!("mislabeled"),
+    // 0xFF...FF isn't the encoding of any point.
+    public_key!("non_canonical_keypoint"),
+];
+
+pub const SIGNED_MESSAGES: &[SignedMessageVector] = &[
+    signed_message!("v1_sag", true),
+    signed_message!("v2_sag_context_headers_claimable", true),
+    // A message of several lines, some empty, with quotes and non-ASCII characters.
+    signed_message!("v2_sag_disavowable", true),
+    signed_message!("v2_one_of_many", true),
+    // An empty message.
+    signed_message!("v2_one_of_many_claimable", true),
+    // Signed as canonical text, so the second vector, which is the first with its text reflowed,
+    // has the same signature, and still verifies.
+    signed_message!("v2_canonical_text", true),
+    signed_message!("v2_canonical_text_reflowed", true),
+    signed_message!("tampered_message", false),
+    signed_message!("tampered_header", false),
+    // The low bit of the final response is flipped.
+    signed_message!("tampered_sag_response", false),
+    signed_message!("tampered_one_of_many_response", false),
+    // The ring includes the "mislabeled" key above, whose attestation is invalid.
+    signed_message!("mislabeled_ring_member", false),
+    // SAG is never named.
+    signed_message!("named_sag_scheme"),
+    signed_message!("wrong_fingerprint"),
+    // Version 1.0 doesn't support contexts.
+    signed_message!("context_in_v1"),
+    signed_message!("lowercase_claim_commitment"),
+    // Header names are compared case-insensitively.
+    signed_message!("repeated_header"),
+    signed_message!("unknown_scheme"),
+    signed_message!("missing_suffix"),
+];
+
+pub const INVALID_ENCODINGS: &[InvalidEncodingVector] = &[
+    invalid_encoding!("public_key_truncated", PublicKey),
+    invalid_encoding!("public_key_non_canonical_keypoint", PublicKey),
+    invalid_encoding!("public_key_newline_in_name", PublicKey),
+    // A valid encoding followed by an extra zero byte.
+    invalid_encoding!("signed_message_trailing_byte", SignedMessage),
+    // The "v1_sag" message, with a context or a header that version 1.0 doesn't sign.
+    invalid_encoding!("signed_message_context_in_v1", SignedMessage),
+    invalid_encoding!("signed_message_headers_in_v1", SignedMessage),
+    // The "v2_one_of_many" message, with a key image (the Ristretto basepoint), which only SAG
+    // signatures can be tagged with.
+    invalid_encoding!("signed_message_key_image_with_one_of_many", SignedMessage),
+    // The "v2_canonical_text" message, with the canonicalization flag set on its version byte, but
+    // followed by index 0 (exact), which must never be flagged.
+    invalid_encoding!(
+        "signed_message_exact_canonicalization_flagged",
+        SignedMessage
+    ),
+];
+
+/// The operations that the vectors check. Each one should be rejected (returning None) exactly
+/// when this crate would reject it.
+pub trait Implementation {
+    /// Parse a public key in text format, returning its Borsh encoding.
+    fn parse_public_key(&self, text: &str) -> Option<Vec<u8>>;
+
+    /// Decode a public key's Borsh encoding, returning its text format and its fingerprint. This
+    /// doesn't check the key's attestation.
+    fn decode_public_key(&self, encoding: &[u8]) -> Option<(String, String)>;
+
+    /// Parse a signed message in the ASCII format, returning its Borsh encoding, and whether it
+    /// verifies.
+    fn parse_signed_message(&self, text: &str) -> Option<(Vec<u8>, bool)>;
+
+    /// Decode a signed message's Borsh encoding, returning its ASCII format.
+    fn decode_signed_message(&self, encoding: &[u8]) -> Option<String>;
+}
+
+/// This crate, as an `Implementation`.
+pub struct Native;
+
+impl Implementation for Native {
+    fn parse_public_key(&self, text: &str) -> Option<Vec<u8>> {
+        let key = PublicKey::from_str(text).ok()?;
+        Some(borsh::to_vec(&key).expect("Failed to serialize into unbounded buffer"))
+    }
+
+    fn decode_public_key(&self, encoding: &[u8]) -> Option<(String, String)> {
+        let key = PublicKey::try_from_slice(encoding).ok()?;
+        let fingerprint = key.fingerprint();
+        Some((String::from(key), fingerprint))
+    }
+
+    fn parse_signed_message(&self, text: &str) -> Option<(Vec<u8>, bool)> {
+        let message = SignedMessage::from_str(text).ok()?;
+        Some((
+            borsh::to_vec(&message).expect("Failed to serialize into unbounded buffer"),
+            message.verify(),
+        ))
+    }
+
+    fn decode_signed_message(&self, encoding: &[u8]) -> Option<String> {
+        let message = SignedMessage::try_from_slice(encoding).ok()?;
+        Some(String::from(&message))
+    }
+}
+
+/// Check `implementation` against every vector. Returns a description of each mismatch, if there
+/// are any.
+pub fn check(implementation: &impl Implementation) -> Result<(), Vec<String>> {
+    let mut failures = vec![];
+    let hex = |s: &str| hex::decode(s).expect("Test vectors are valid hex");
+
+    for v in PUBLIC_KEYS {
+        let expected = v.encoding.map(hex);
+        if implementation.parse_public_key(v.text) != expected {
+            failures.push(format!("public key {}: wrong parse result", v.name));
+        }
+        if let (Some(encoding), Some(fingerprint)) = (expected, v.fingerprint) {
+            if implementation.decode_public_key(&encoding)
+                != Some((v.text.to_string(), fingerprint.to_string()))
+            {
+                failures.push(format!("public key {}: wrong decode result", v.name));
+            }
+        }
+    }
+
+    for v in SIGNED_MESSAGES {
+        let expected = v.encoding.map(hex);
+        if implementation.parse_signed_message(v.text) != expected.clone().map(|e| (e, v.valid)) {
+            failures.push(format!("signed message {}: wrong parse result", v.name));
+        }
+        if let Some(encoding) = expected {
+            if implementation.decode_signed_message(&encoding).as_deref() != Some(v.text) {
+                failures.push(format!("signed message {}: wrong decode result", v.name));
+            }
+        }
+    }
+
+    for v in INVALID_ENCODINGS {
+        let encoding = hex(v.encoding);
+        let accepted = match v.kind {
+            EncodingKind::PublicKey => implementation.decode_public_key(&encoding).is_some(),
+            EncodingKind::SignedMessage => {
+                implementation.decode_signed_message(&encoding).is_some()
+            }
+        };
+        if accepted {
+            failures.push(format!("encoding {}: wrongly accepted", v.name));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Z85 (https://rfc.zeromq.org/spec/32/), as our ASCII formats use it, which is how version 3 of the
+// `z85` crate does it (we used to depend on it, but it needs std): Each 4 bytes become 5 characters.
+// If the length isn't a multiple of 4, the last 1 to 3 bytes are padded at the front with zeros to
+// make a final chunk, and then as many of its characters as there were padding bytes are replaced
+// with '#'. ('#' is also an ordinary Z85 character, but a chunk can only start with it if it's
+// padded, because a 4-byte chunk's first character is at most '%'.)
+
+const LETTERS: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+const PADDING: u8 = b'#';
+
+fn encode_chunk(chunk: [u8; 4]) -> [u8; 5] {
+    let mut value = u32::from_be_bytes(chunk);
+    let mut out = [0; 5];
+    for letter in out.iter_mut().rev() {
+        *letter = LETTERS[(value % 85) as usize];
+        value /= 85;
+    }
+    out
+}
+
+fn decode_chunk(letters: &[u8]) -> Option<u32> {
+    let mut value = 0u64;
+    for &letter in letters {
+        let digit = LETTERS.iter().position(|&l| l == letter)?;
+        value = value * 85 + digit as u64;
+    }
+    u32::try_from(value).ok()
+}
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 5 + 5);
+    let chunks = bytes.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        out.extend(encode_chunk(chunk.try_into().expect("Chunks are 4 bytes")));
+    }
+    if !tail.is_empty() {
+        let padding = 4 - tail.len();
+        let mut chunk = [0; 4];
+        chunk[padding..].copy_from_slice(tail);
+        let mut letters = encode_chunk(chunk);
+        letters[..padding].fill(PADDING);
+        out.extend(letters);
+    }
+    String::from_utf8(out).expect("Z85 is ASCII")
+}
+
+/// Returns None unless `s` is exactly what `encode` produces for some bytes. (The `z85` crate also
+/// accepted a final chunk of "####0", encoding no bytes at all, which `encode` never produces.)
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    let chunks = s.as_bytes().chunks_exact(5);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() / 5 * 4);
+    let mut chunks = chunks.peekable();
+    while let Some(chunk) = chunks.next() {
+        if chunks.peek().is_none() && chunk[0] == PADDING {
+            let padding = chunk.iter().take_while(|&&l| l == PADDING).count();
+            if padding > 3 {
+                return None;
+            }
+            let value = decode_chunk(&chunk[padding..])?;
+            if u64::from(value) >= 1 << (8 * (4 - padding)) {
+                return None;
+            }
+            out.extend_from_slice(&value.to_be_bytes()[padding..]);
+        } else {
+            out.extend_from_slice(&decode_chunk(chunk)?.to_be_bytes());
+        }
+    }
+    Some(out)
+}
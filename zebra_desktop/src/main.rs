@@ -10,11 +10,18 @@ use dioxus_free_icons::{
     Icon,
 };
 use copypasta::{ClipboardContext, ClipboardProvider};
+use time::format_description::well_known::Rfc3339;
 
-use boringascii::BoringAscii;
+use boringascii::{idn, BoringAscii};
 use zebra::about::About;
-use zebra_crypto::{PublicKey, SignedMessage};
-use zebra_storage::{default_db_path, Database, VerificationInfo};
+use zebra_crypto::{
+    AuthorshipClaim, Canonicalization, DisavowalProof, PublicKey, RingScheme, SignedMessage,
+    SigningOptions, ThresholdProposal, ThresholdShare, ThresholdSignedMessage,
+};
+use zebra_storage::{default_db_path, Database, RingAnalysis, VerificationInfo};
+
+// The ring size that suggestions aim for, until the user picks a different one.
+const DEFAULT_TARGET_RING_SIZE: usize = 8;
 
 fn make_config() -> dioxus_desktop::Config {
     dioxus_desktop::Config::default().with_window(
@@ -44,18 +51,55 @@ enum ActiveTab {
     MyKeys,
     OtherKeys,
     Sign,
+    CoSign,
     Verify,
     About,
     Danger,
 }
 
+/// How a signed message is copied to the clipboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SignedMessageFormat {
+    Text,
+    Html,
+    Markdown,
+}
+
 struct NewPrivateName(String);
-struct NewPrivateEmail(BoringAscii);
+struct NewPrivateEmail(String);
 struct TextToSign(String);
+struct SigningTopic(String);
+struct SigningContext(String);
+struct SigningSubject(String);
+struct IncludeSigningDate(bool);
+struct ClaimableSignature(bool);
+struct ClaimToCheck(Option<AuthorshipClaim>);
+struct DisavowableSignature(bool);
+struct DisavowalToCheck(Option<DisavowalProof>);
+struct CompactSignature(bool);
+struct CanonicalTextSignature(bool);
+struct SignatureFormat(SignedMessageFormat);
+struct CoSignersNeeded(usize);
+struct CoSignProposal(Option<ThresholdProposal>);
+struct CoSignShares(Vec<ThresholdShare>);
+struct ThresholdMessageToVerify(Option<ThresholdSignedMessage>);
+struct PendingRingAnalysis(Option<AnalyzedRing>);
+struct SigningError(Option<String>);
+struct TargetRingSize(usize);
 struct MessageToVerify(Option<SignedMessage>);
 struct SelectedPrivateSigner(Option<PublicKey>);
 struct SelectedPublicSigners(BTreeSet<PublicKey>);
 
+/// A ring analysis with warnings, which the user has been shown, along with the key, topic and
+/// ring it was for. Signing anyway only skips the analysis if none of those have changed since.
+#[derive(Clone)]
+struct AnalyzedRing {
+    key: PublicKey,
+    topic: String,
+    ring: Vec<PublicKey>,
+    analysis: RingAnalysis,
+}
+
 #[derive(Clone)]
 struct TableFilter {
     name: String,
@@ -136,8 +180,26 @@ fn App() -> Element {
 
     use_context_provider(|| Signal::new(ActiveTab::MyKeys));
     use_context_provider(|| Signal::new(NewPrivateName(String::new())));
-    use_context_provider(|| Signal::new(NewPrivateEmail(BoringAscii::default())));
+    use_context_provider(|| Signal::new(NewPrivateEmail(String::new())));
     use_context_provider(|| Signal::new(TextToSign(String::new())));
+    use_context_provider(|| Signal::new(SigningTopic(String::new())));
+    use_context_provider(|| Signal::new(SigningContext(String::new())));
+    use_context_provider(|| Signal::new(SigningSubject(String::new())));
+    use_context_provider(|| Signal::new(IncludeSigningDate(true)));
+    use_context_provider(|| Signal::new(ClaimableSignature(false)));
+    use_context_provider(|| Signal::new(ClaimToCheck(None)));
+    use_context_provider(|| Signal::new(DisavowableSignature(false)));
+    use_context_provider(|| Signal::new(DisavowalToCheck(None)));
+    use_context_provider(|| Signal::new(CompactSignature(false)));
+    use_context_provider(|| Signal::new(CanonicalTextSignature(false)));
+    use_context_provider(|| Signal::new(SignatureFormat(SignedMessageFormat::Text)));
+    use_context_provider(|| Signal::new(CoSignersNeeded(2)));
+    use_context_provider(|| Signal::new(CoSignProposal(None)));
+    use_context_provider(|| Signal::new(CoSignShares(vec![])));
+    use_context_provider(|| Signal::new(ThresholdMessageToVerify(None)));
+    use_context_provider(|| Signal::new(PendingRingAnalysis(None)));
+    use_context_provider(|| Signal::new(SigningError(None)));
+    use_context_provider(|| Signal::new(TargetRingSize(DEFAULT_TARGET_RING_SIZE)));
     use_context_provider(|| Signal::new(MessageToVerify(None)));
     use_context_provider(|| Signal::new(SelectedPublicSigners(BTreeSet::new())));
     use_context_provider(|| {
@@ -197,6 +259,7 @@ fn App() -> Element {
                         ActiveTab::MyKeys => rsx! { MyKeys {} },
                         ActiveTab::OtherKeys => rsx! { OtherKeys {} },
                         ActiveTab::Sign => rsx! { Sign {} },
+                        ActiveTab::CoSign => rsx! { CoSign {} },
                         ActiveTab::Verify => rsx! { Verify {} },
                         ActiveTab::About => rsx! { About {} },
                         ActiveTab::Danger => rsx! { Danger {} },
@@ -247,6 +310,17 @@ fn TabSelect() -> Element {
                 },
                 "Sign"
             }
+            div {
+                onclick: move |_| {*use_context::<Signal<ActiveTab>>().write() = ActiveTab::CoSign},
+                class: {
+                    if let ActiveTab::CoSign = active_tab {
+                        "tab_choice active_tab"
+                    } else {
+                        "tab_choice inactive_tab"
+                    }
+                },
+                "Co-Sign"
+            }
             div {
                 onclick: move |_| {*use_context::<Signal<ActiveTab>>().write() = ActiveTab::Verify},
                 class: {
@@ -392,6 +466,42 @@ fn VerifyButton(props: VerifyButtonProps) -> Element {
     }
 }
 
+#[derive(Clone, PartialEq, Props)]
+struct EmailDisplayProps {
+    email: String,
+}
+
+/// Shows an email address as stored, plus its Unicode form if the domain is internationalized.
+/// The stored (ASCII) form is what gets signed, so it always comes first.
+fn EmailDisplay(props: EmailDisplayProps) -> Element {
+    let unicode = BoringAscii::from_str(&props.email)
+        .ok()
+        .and_then(|email| idn::email_to_unicode(&email));
+    if let Some(unicode) = unicode {
+        let mixed_script = idn::has_mixed_script_domain(&unicode);
+        rsx! {
+            "{props.email}"
+            br {}
+            span {
+                class: "idn_email",
+                "({unicode})"
+            }
+            if mixed_script {
+                br {}
+                span {
+                    class: "mixed_script_warning",
+                    title: "This domain mixes characters from different scripts, which is a common way to imitate another domain. Compare the stored form carefully.",
+                    "Warning: mixed scripts"
+                }
+            }
+        }
+    } else {
+        rsx! {
+            "{props.email}"
+        }
+    }
+}
+
 fn FilterRow<T: Filter + 'static>() -> Element {
     let mut filter = use_context::<Signal<T>>();
 
@@ -509,7 +619,7 @@ fn Danger() -> Element {
                                 }
                                 td {
                                     class: "email",
-                                    {k.holder().email().as_str().to_string()},
+                                    EmailDisplay { email: k.holder().email() }
                                 }
                                 td {
                                     class: "fingerprint",
@@ -540,6 +650,11 @@ fn MyKeys() -> Element {
     let mut new_private_email = use_context::<Signal<NewPrivateEmail>>();
     let new_private_email_val = new_private_email.read().deref().0.clone();
     let new_private_email_copy = new_private_email.read().deref().0.clone();
+    let idn_preview = if new_private_email_val.is_ascii() {
+        None
+    } else {
+        idn::email_to_ascii(&new_private_email_val)
+    };
     let mut selected_private_signer = use_context::<Signal<SelectedPrivateSigner>>();
     let keys = match dbread.deref() {
         Ok(ref db) => db.visible_contents.my_public_keys.clone(),
@@ -563,10 +678,10 @@ fn MyKeys() -> Element {
             onsubmit: move |_| {
                 match dbresult.write().deref_mut() {
                     Ok(ref mut db) => {
-                        if let Ok(email) = BoringAscii::from_str(&new_private_email_copy) {
+                        if let Some(email) = BoringAscii::from_idn_email(&new_private_email_copy) {
                             if let Ok(()) = db.new_private_key(&new_private_name_copy, &email) {
                                 *new_private_name.write() = NewPrivateName("".to_string());
-                                *new_private_email.write() = NewPrivateEmail(BoringAscii::default());
+                                *new_private_email.write() = NewPrivateEmail(String::new());
                                 let mut selected_private_signer_write = selected_private_signer.write();
                                 if selected_private_signer_write.deref().0.is_none() {
                                     *selected_private_signer_write = SelectedPrivateSigner(db.visible_contents.my_public_keys.iter().next().cloned());
@@ -602,13 +717,22 @@ fn MyKeys() -> Element {
                 placeholder: "New Key Email",
                 form: new_key_form_id,
                 oninput: move |evt| {
-                    if let Ok(new) = BoringAscii::from_str(&evt.value()) {
+                    // Non-ASCII is allowed while typing, since the domain may be internationalized.
+                    // Anything else that `from_idn_email` would reject is refused immediately.
+                    let new = evt.value();
+                    if !new.contains(|c: char| c.is_whitespace() || c.is_control()) {
                         *new_private_email.write() = NewPrivateEmail(new)
                     } else {
                         *new_private_email.write() = NewPrivateEmail(new_private_email_val.clone())
                     }
                 }
             }
+            if let Some(ascii) = idn_preview {
+                span {
+                    class: "idn_preview",
+                    "Will be stored as {ascii}"
+                }
+            }
             input {
                 "type": "submit",
                 form: new_key_form_id,
@@ -650,7 +774,7 @@ fn MyKeys() -> Element {
                                     }
                                     td {
                                         class: "email",
-                                        {k.holder().email().as_str().to_string()},
+                                        EmailDisplay { email: k.holder().email() }
                                     }
                                     td {
                                         class: "fingerprint",
@@ -772,7 +896,7 @@ fn OtherKeys() -> Element {
                                     }
                                     td {
                                         class: "email",
-                                        {k.0.holder().email().as_str().to_string()},
+                                        EmailDisplay { email: k.0.holder().email() }
                                     }
                                     td {
                                         class: "fingerprint",
@@ -892,10 +1016,13 @@ fn PublicSignerSelect(props: PublicSignerSelectProps) -> Element {
 }
 
 fn Sign() -> Element {
-    let dbresult = use_context::<Signal<std::io::Result<Database>>>();
+    let mut dbresult = use_context::<Signal<std::io::Result<Database>>>();
     let dbread = dbresult.read();
-    let their_keys = match dbread.deref() {
-        Ok(ref db) => db.visible_contents.their_public_keys.clone(),
+    let (their_keys, ring_log_enabled) = match dbread.deref() {
+        Ok(ref db) => (
+            db.visible_contents.their_public_keys.clone(),
+            db.visible_contents.ring_log_enabled,
+        ),
         Err(ref e) => {
             return rsx! {
                 "Error reading database: {e}"
@@ -905,6 +1032,40 @@ fn Sign() -> Element {
 
     let mut text_to_sign = use_context::<Signal<TextToSign>>();
     let text_to_sign_val = text_to_sign.read().deref().0.clone();
+    let mut signing_topic = use_context::<Signal<SigningTopic>>();
+    let signing_topic_val = signing_topic.read().deref().0.clone();
+    let mut signing_context = use_context::<Signal<SigningContext>>();
+    let signing_context_val = signing_context.read().deref().0.clone();
+    let mut signing_subject = use_context::<Signal<SigningSubject>>();
+    let signing_subject_val = signing_subject.read().deref().0.clone();
+    let mut include_signing_date = use_context::<Signal<IncludeSigningDate>>();
+    let include_signing_date_val = include_signing_date.read().deref().0;
+    let mut claimable_signature = use_context::<Signal<ClaimableSignature>>();
+    let claimable_signature_val = claimable_signature.read().deref().0;
+    let mut disavowable_signature = use_context::<Signal<DisavowableSignature>>();
+    let disavowable_signature_val = disavowable_signature.read().deref().0;
+    let mut compact_signature = use_context::<Signal<CompactSignature>>();
+    let compact_signature_val = compact_signature.read().deref().0;
+    let mut canonical_text_signature = use_context::<Signal<CanonicalTextSignature>>();
+    let canonical_text_signature_val = canonical_text_signature.read().deref().0;
+    let mut signature_format = use_context::<Signal<SignatureFormat>>();
+    let signature_format_val = signature_format.read().deref().0;
+
+    let mut target_ring_size = use_context::<Signal<TargetRingSize>>();
+    let target_ring_size_val = target_ring_size.read().deref().0;
+    let mut co_signers_needed = use_context::<Signal<CoSignersNeeded>>();
+    let co_signers_needed_val = co_signers_needed.read().deref().0;
+    let mut selected_public_signers = use_context::<Signal<SelectedPublicSigners>>();
+    let selected_private_signer = use_context::<Signal<SelectedPrivateSigner>>();
+    let suggestions = match (dbread.deref(), selected_private_signer.read().deref().0.as_ref()) {
+        (Ok(ref db), Some(my_key)) => db.visible_contents.suggest_ring_members(
+            my_key,
+            &selected_public_signers.read().0,
+            target_ring_size_val,
+        ),
+        _ => vec![],
+    };
+    let suggestions_copy = suggestions.clone();
 
     let filter = use_context::<Signal<SignerFilter>>();
 
@@ -923,9 +1084,11 @@ fn Sign() -> Element {
                 icon: GoShieldLock,
             }
             SignAndCopy {}
+            ProposeThreshold {}
         },
         div {
             class: "data",
+        RingWarnings {}
         b {
             "Text To Sign: "
         }
@@ -937,12 +1100,177 @@ fn Sign() -> Element {
         }
         br {}
         br {}
+        b {
+            "Context: "
+        }
+        input {
+            class: "context_input",
+            value: "{signing_context_val}",
+            placeholder: "Optional; e.g. \"ACME board vote 2026\". Shown with the message and covered by the signature",
+            oninput: move |evt| {
+                let new = evt.value();
+                if !new.contains(char::is_control) {
+                    *signing_context.write() = SigningContext(new);
+                }
+            },
+        }
+        br {}
+        b {
+            "Subject: "
+        }
+        input {
+            class: "subject_input",
+            value: "{signing_subject_val}",
+            placeholder: "Optional; signed along with the message",
+            oninput: move |evt| {
+                let new = evt.value();
+                if !new.contains(char::is_control) {
+                    *signing_subject.write() = SigningSubject(new);
+                }
+            },
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: include_signing_date_val,
+                oninput: move |e| *include_signing_date.write() = IncludeSigningDate(e.value() == "true"),
+            }
+            "Sign the current date and time along with the message, so it can't be passed off as new later"
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: claimable_signature_val,
+                oninput: move |e| *claimable_signature.write() = ClaimableSignature(e.value() == "true"),
+            }
+            "Make this signature claimable, so that I can choose to prove I wrote it later (nobody else in the ring can)"
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: disavowable_signature_val,
+                disabled: compact_signature_val,
+                oninput: move |e| *disavowable_signature.write() = DisavowableSignature(e.value() == "true"),
+            }
+            "Make this signature disavowable, so that everyone else in the ring can prove they didn't write it (not for compact signatures)"
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: compact_signature_val,
+                disabled: disavowable_signature_val,
+                oninput: move |e| *compact_signature.write() = CompactSignature(e.value() == "true"),
+            }
+            "Use a compact signature, which is much smaller for large rings (can't be made disavowable)"
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: canonical_text_signature_val,
+                oninput: move |e| *canonical_text_signature.write() = CanonicalTextSignature(e.value() == "true"),
+            }
+            "Sign only the words and paragraphs of the message, so that it still verifies after being pasted into apps that change its spacing or line breaks"
+        }
+        br {}
+        b {
+            "Copy as: "
+        }
+        select {
+            oninput: move |e| *signature_format.write() = SignatureFormat(match e.value().as_str() {
+                "html" => SignedMessageFormat::Html,
+                "markdown" => SignedMessageFormat::Markdown,
+                _ => SignedMessageFormat::Text,
+            }),
+            option {
+                value: "text",
+                selected: signature_format_val == SignedMessageFormat::Text,
+                "Plain text"
+            }
+            option {
+                value: "html",
+                selected: signature_format_val == SignedMessageFormat::Html,
+                "HTML, for web pages"
+            }
+            option {
+                value: "markdown",
+                selected: signature_format_val == SignedMessageFormat::Markdown,
+                "Markdown, for wikis and blogs"
+            }
+        }
+        br {}
+        b {
+            "Topic: "
+        }
+        input {
+            class: "topic_input",
+            value: "{signing_topic_val}",
+            placeholder: "Optional; used to warn about signing the same topic with different rings",
+            oninput: move |evt| *signing_topic.write() = SigningTopic(evt.value().clone()),
+        }
+        br {}
+        label {
+            input {
+                "type": "checkbox",
+                checked: ring_log_enabled,
+                oninput: move |e| {
+                    if let Ok(ref mut db) = dbresult.write().deref_mut() {
+                        let _ = db.set_ring_log_enabled(e.value() == "true");
+                    }
+                },
+            }
+            "Remember the rings I sign with (never the messages), to warn me when a new ring would narrow down who I am"
+        }
+        br {}
+        br {}
         b {
             "My Key: "
         }
         PrivateSignerSelect {}
         br {}
         br {}
+        b {
+            "Target Ring Size: "
+        }
+        input {
+            "type": "number",
+            min: "1",
+            value: "{target_ring_size_val}",
+            oninput: move |evt| {
+                if let Ok(size) = evt.value().parse() {
+                    *target_ring_size.write() = TargetRingSize(size);
+                }
+            },
+        }
+        if !suggestions.is_empty() {
+            button {
+                onclick: move |_| {
+                    selected_public_signers.write().0.extend(suggestions_copy.iter().cloned());
+                },
+                "Add {suggestions.len()} Suggested Keys"
+            }
+        }
+        br {}
+        b {
+            "Co-Signers Needed: "
+        }
+        input {
+            "type": "number",
+            min: "1",
+            value: "{co_signers_needed_val}",
+            title: "For threshold proposals: how many members of the ring must sign",
+            oninput: move |evt| {
+                if let Ok(n) = evt.value().parse() {
+                    *co_signers_needed.write() = CoSignersNeeded(n);
+                }
+            },
+        }
+        br {}
+        br {}
         b {
             "Other Keys: "
         }
@@ -983,7 +1311,7 @@ fn Sign() -> Element {
                                 }
                                 td {
                                     class: "email",
-                                    {k.0.holder().email()}
+                                    EmailDisplay { email: k.0.holder().email() }
                                 }
                                 td {
                                     class: "fingerprint",
@@ -1008,6 +1336,21 @@ fn Sign() -> Element {
                                     PublicSignerSelect {
                                         k: k.0.clone()
                                     }
+                                    if suggestions.contains(&k.0) {
+                                        a {
+                                            class: "suggestion action_button",
+                                            href: "",
+                                            title: "Suggested to reach the target ring size. Click to include.",
+                                            onclick: {
+                                                let k_copy = k.0.clone();
+                                                move |e: Event<MouseData>| {
+                                                    e.stop_propagation();
+                                                    selected_public_signers.write().0.insert(k_copy.clone());
+                                                }
+                                            },
+                                            "Suggested"
+                                        }
+                                    }
                                 }
                             }
                     }
@@ -1022,6 +1365,27 @@ fn SignAndCopy() -> Element {
     let mut dbresult = use_context::<Signal<std::io::Result<Database>>>();
     let text_to_sign = use_context::<Signal<TextToSign>>();
     let text_to_sign_val = text_to_sign.read().deref().0.clone();
+    let signing_topic = use_context::<Signal<SigningTopic>>();
+    let signing_topic_val = signing_topic.read().deref().0.clone();
+    let signing_context = use_context::<Signal<SigningContext>>();
+    let signing_context_val = signing_context.read().deref().0.clone();
+    let signing_subject = use_context::<Signal<SigningSubject>>();
+    let signing_subject_val = signing_subject.read().deref().0.clone();
+    let include_signing_date = use_context::<Signal<IncludeSigningDate>>();
+    let include_signing_date_val = include_signing_date.read().deref().0;
+    let claimable_signature = use_context::<Signal<ClaimableSignature>>();
+    let claimable_signature_val = claimable_signature.read().deref().0;
+    let disavowable_signature = use_context::<Signal<DisavowableSignature>>();
+    let disavowable_signature_val = disavowable_signature.read().deref().0;
+    let compact_signature = use_context::<Signal<CompactSignature>>();
+    let compact_signature_val = compact_signature.read().deref().0;
+    let canonical_text_signature = use_context::<Signal<CanonicalTextSignature>>();
+    let canonical_text_signature_val = canonical_text_signature.read().deref().0;
+    let signature_format = use_context::<Signal<SignatureFormat>>();
+    let signature_format_val = signature_format.read().deref().0;
+    let mut pending_analysis = use_context::<Signal<PendingRingAnalysis>>();
+    let mut signing_error = use_context::<Signal<SigningError>>();
+    let signing_error_val = signing_error.read().deref().0.clone();
     let selected_public_signers = use_context::<Signal<SelectedPublicSigners>>();
     let current_signers = selected_public_signers
         .read()
@@ -1031,39 +1395,340 @@ fn SignAndCopy() -> Element {
         .collect::<Vec<_>>();
     let selected_private_signer = use_context::<Signal<SelectedPrivateSigner>>();
     let k = selected_private_signer.read().deref().0.clone();
+    // If the ring looks risky, the first click only shows the warnings, and signing requires a
+    // second, explicit click, with the same key, topic and ring.
+    let warnings_shown = pending_analysis
+        .read()
+        .deref()
+        .0
+        .as_ref()
+        .filter(|pending| {
+            Some(&pending.key) == k.as_ref()
+                && pending.topic == signing_topic_val
+                && pending.ring == current_signers
+        })
+        .is_some();
     rsx!{
         button {
             onclick: move |_| {
                 if let Ok(ref mut db) = dbresult.write().deref_mut() {
-                    if let Ok(mut ctx) = ClipboardContext::new() {
-                        if let Some(k) = &k {
-                            if let Ok(signed_message) = db.sign(&text_to_sign_val, k, &current_signers) {
-                                let _ = ctx.set_contents(String::from(&signed_message));
+                    if let Some(k) = &k {
+                        if !warnings_shown {
+                            if let Ok(analysis) = db.analyze_ring(&signing_topic_val, k, &current_signers) {
+                                if !analysis.warnings.is_empty() {
+                                    *pending_analysis.write() = PendingRingAnalysis(Some(AnalyzedRing {
+                                        key: k.clone(),
+                                        topic: signing_topic_val.clone(),
+                                        ring: current_signers.clone(),
+                                        analysis,
+                                    }));
+                                    return;
+                                }
                             }
                         }
+                        let mut options = SigningOptions {
+                            context: Some(signing_context_val.clone()).filter(|c| !c.is_empty()),
+                            claimable: claimable_signature_val,
+                            disavowable: disavowable_signature_val,
+                            scheme: if compact_signature_val {
+                                RingScheme::OneOfMany
+                            } else {
+                                RingScheme::Sag
+                            },
+                            canonicalization: if canonical_text_signature_val {
+                                Canonicalization::Text
+                            } else {
+                                Canonicalization::Exact
+                            },
+                            hedged: true,
+                            ..SigningOptions::default()
+                        };
+                        if include_signing_date_val {
+                            if let Ok(now) = time::OffsetDateTime::now_utc().replace_nanosecond(0) {
+                                if let Ok(date) = now.format(&Rfc3339) {
+                                    options.headers.push(("Date".to_string(), date));
+                                }
+                            }
+                        }
+                        if !signing_subject_val.is_empty() {
+                            options.headers.push(("Subject".to_string(), signing_subject_val.clone()));
+                        }
+                        let result = db
+                            .sign(&text_to_sign_val, &options, &signing_topic_val, k, &current_signers)
+                            .map_err(|e| format!("Couldn't sign: {e}"))
+                            .and_then(|signed_message| {
+                                let mut ctx = ClipboardContext::new()
+                                    .map_err(|e| format!("Couldn't open the clipboard: {e}"))?;
+                                ctx.set_contents(match signature_format_val {
+                                    SignedMessageFormat::Text => String::from(&signed_message),
+                                    SignedMessageFormat::Html => signed_message.to_html(),
+                                    SignedMessageFormat::Markdown => signed_message.to_markdown(),
+                                })
+                                .map_err(|e| format!("Couldn't copy the signed message: {e}"))
+                            });
+                        match result {
+                            Ok(()) => {
+                                *pending_analysis.write() = PendingRingAnalysis(None);
+                                *signing_error.write() = SigningError(None);
+                            }
+                            Err(e) => *signing_error.write() = SigningError(Some(e)),
+                        }
                     }
                 }
             },
-            "Sign and Copy to Clipboard"
+            if warnings_shown {
+                "Sign Anyway and Copy to Clipboard"
+            } else {
+                "Sign and Copy to Clipboard"
+            }
+        }
+        if let Some(e) = signing_error_val {
+            span {
+                class: "signing_error",
+                "{e}"
+            }
+        }
+    }
+}
+
+fn ProposeThreshold() -> Element {
+    let text_to_sign = use_context::<Signal<TextToSign>>();
+    let text_to_sign_val = text_to_sign.read().deref().0.clone();
+    let co_signers_needed = use_context::<Signal<CoSignersNeeded>>();
+    let co_signers_needed_val = co_signers_needed.read().deref().0;
+    let mut co_sign_proposal = use_context::<Signal<CoSignProposal>>();
+    let mut co_sign_shares = use_context::<Signal<CoSignShares>>();
+    let selected_public_signers = use_context::<Signal<SelectedPublicSigners>>();
+    let selected_private_signer = use_context::<Signal<SelectedPrivateSigner>>();
+    let mut ring = selected_public_signers
+        .read()
+        .0
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    ring.extend(selected_private_signer.read().deref().0.clone());
+    rsx! {
+        button {
+            onclick: move |_| {
+                if let Some(proposal) = ThresholdProposal::new(&text_to_sign_val, co_signers_needed_val, &ring) {
+                    if let Ok(mut ctx) = ClipboardContext::new() {
+                        let _ = ctx.set_contents(String::from(&proposal));
+                    }
+                    *co_sign_proposal.write() = CoSignProposal(Some(proposal));
+                    *co_sign_shares.write() = CoSignShares(vec![]);
+                }
+            },
+            title: "Copies a request for the ring members to co-sign, and opens it in the Co-Sign tab",
+            "Propose Threshold Signature and Copy to Clipboard"
+        }
+    }
+}
+
+fn CoSign() -> Element {
+    let dbresult = use_context::<Signal<std::io::Result<Database>>>();
+    let my_keys = match *dbresult.read() {
+        Ok(ref db) => db.visible_contents.my_public_keys.clone(),
+        Err(_) => BTreeSet::new(),
+    };
+    let mut co_sign_proposal = use_context::<Signal<CoSignProposal>>();
+    let proposal = co_sign_proposal.read().deref().0.clone();
+    let mut co_sign_shares = use_context::<Signal<CoSignShares>>();
+    let shares = co_sign_shares.read().deref().0.clone();
+
+    rsx! {
+        div {
+            class: "toolbar",
+            Icon {
+                class: "action_icon",
+                width: 15,
+                height: 15,
+                fill: "black",
+                icon: GoShieldLock,
+            }
+            button {
+                onclick: move |_| {
+                    let proposal = ClipboardContext::new()
+                        .and_then(|mut ctx| ctx.get_contents())
+                        .ok()
+                        .and_then(|p| ThresholdProposal::from_str(&p).ok())
+                        .filter(|p| p.validate());
+                    *co_sign_proposal.write() = CoSignProposal(proposal);
+                    *co_sign_shares.write() = CoSignShares(vec![]);
+                },
+                "Load Proposal From Clipboard"
+            }
+        },
+        div {
+            class: "data",
+            if let Some(proposal) = proposal {
+                b {
+                    "Message:"
+                }
+                br {}
+                "{proposal.message()}"
+                br {}
+                br {}
+                b {
+                    "This needs signatures from at least {proposal.threshold()} of the holders of these keys:"
+                }
+                table {
+                    thead {
+                        th {
+                            "Name"
+                        }
+                        th {
+                            "Email"
+                        }
+                        th {
+                            "Fingerprint"
+                        }
+                        th {
+                            "Sign"
+                        }
+                    }
+                    tbody {
+                        for k in proposal.ring().cloned() {
+                            tr {
+                                key: "{k.fingerprint()}",
+                                td {
+                                    class: "name",
+                                    "{k.holder().name()}"
+                                }
+                                td {
+                                    class: "email",
+                                    EmailDisplay { email: k.holder().email() }
+                                }
+                                td {
+                                    class: "fingerprint",
+                                    "{k.fingerprint()}"
+                                }
+                                td {
+                                    class: "actions",
+                                    if my_keys.contains(&k) {
+                                        a {
+                                            class: "action_button",
+                                            href: "",
+                                            title: "Sign with this key, and copy the share to send back to whoever is collecting them",
+                                            onclick: {
+                                                let proposal = proposal.clone();
+                                                let k = k.clone();
+                                                move |e: Event<MouseData>| {
+                                                    e.stop_propagation();
+                                                    if let Ok(ref db) = dbresult.read().deref() {
+                                                        if let Ok(share) = db.sign_threshold_proposal(&proposal, &k) {
+                                                            if let Ok(mut ctx) = ClipboardContext::new() {
+                                                                let _ = ctx.set_contents(String::from(&share));
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                            },
+                                            "Sign and Copy Share"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                br {}
+                b {
+                    "Collected {shares.len()} of {proposal.threshold()} shares."
+                }
+                br {}
+                button {
+                    onclick: {
+                        let proposal = proposal.clone();
+                        move |_| {
+                            if let Some(share) = ClipboardContext::new()
+                                .and_then(|mut ctx| ctx.get_contents())
+                                .ok()
+                                .and_then(|s| ThresholdShare::from_str(&s).ok())
+                                .filter(|s| proposal.verify_share(s))
+                            {
+                                let mut shares = co_sign_shares.write();
+                                // Re-adding the same share is harmless: combining ignores
+                                // duplicates. But it shouldn't inflate the count shown here.
+                                if !shares.0.contains(&share) {
+                                    shares.0.push(share);
+                                }
+                            }
+                        }
+                    },
+                    "Add Share From Clipboard"
+                }
+                if let Some(signed) = proposal.combine(&shares) {
+                    button {
+                        onclick: move |_| {
+                            if let Ok(mut ctx) = ClipboardContext::new() {
+                                let _ = ctx.set_contents(String::from(&signed));
+                            }
+                        },
+                        "Combine and Copy Signed Message to Clipboard"
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn RingWarnings() -> Element {
+    let mut pending_analysis = use_context::<Signal<PendingRingAnalysis>>();
+    let analysis = match pending_analysis.read().deref().0.clone() {
+        Some(pending) => pending.analysis,
+        None => return rsx! {},
+    };
+    rsx! {
+        div {
+            class: "ring_warnings",
+            b {
+                "This ring could reveal who you are:"
+            }
+            ul {
+                for warning in analysis.warnings.iter() {
+                    li {
+                        "{warning}"
+                    }
+                }
+            }
+            button {
+                onclick: move |_| *pending_analysis.write() = PendingRingAnalysis(None),
+                "Go Back and Change the Ring"
+            }
         }
     }
 }
 
 fn PasteAndVerify() -> Element {
     let mut message_to_verify = use_context::<Signal<MessageToVerify>>();
+    let mut claim_to_check = use_context::<Signal<ClaimToCheck>>();
+    let mut disavowal_to_check = use_context::<Signal<DisavowalToCheck>>();
+    let mut threshold_message_to_verify = use_context::<Signal<ThresholdMessageToVerify>>();
     rsx! {
         button {
             onclick: move |_| {
-                let mut message_to_verify = message_to_verify.write();
-                if let Some(message) = ClipboardContext::new()
+                *claim_to_check.write() = ClaimToCheck(None);
+                *disavowal_to_check.write() = DisavowalToCheck(None);
+                let clipboard = ClipboardContext::new()
                     .and_then(|mut ctx| ctx.get_contents())
-                        .ok()
-                        .and_then(|m| SignedMessage::from_str(&m).ok())
+                    .ok();
+                let mut message_to_verify = message_to_verify.write();
+                if let Some(message) = clipboard
+                        .as_ref()
+                        .and_then(|m| {
+                            // Either the ASCII format, or a document with a message embedded in it.
+                            SignedMessage::from_str(m)
+                                .ok()
+                                .or_else(|| SignedMessage::find_embedded(m).into_iter().next())
+                        })
                 {
                     *message_to_verify = MessageToVerify(Some(message));
                 } else {
                     *message_to_verify = MessageToVerify(None);
                 }
+                *threshold_message_to_verify.write() = ThresholdMessageToVerify(
+                    clipboard.and_then(|m| ThresholdSignedMessage::from_str(&m).ok()),
+                );
             },
             "Verify Message From Clipboard"
         }
@@ -1104,9 +1769,69 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
     }
 
     let signed_message = props.signed_message.message.clone();
+    let claimable = props.signed_message.is_claimable();
+    let signed_by_me = props.signed_message.ring().any(|k| my_keys.contains(k));
+    let disavowable = props.signed_message.is_disavowable();
+    let my_ring_keys = props
+        .signed_message
+        .ring()
+        .filter(|k| my_keys.contains(*k))
+        .cloned()
+        .collect::<Vec<_>>();
+    let context = props.signed_message.context().map(str::to_string);
+    let headers = props.signed_message.headers().to_vec();
+    let version = props.signed_message.version();
+    let scheme = props.signed_message.scheme();
+    let canonical_text =
+        props.signed_message.canonicalization() == Canonicalization::Text;
+    // No authorities are configured as trusted, so the token is checked against the certificate
+    // it includes, and the authority's name is shown for the user to judge.
+    let timestamp = props.signed_message.timestamp_token().map(|_| {
+        zebra_timestamp::verify_with_included_certificate(&props.signed_message).map(|t| {
+            (
+                t.time().format(&Rfc3339).unwrap_or_default(),
+                t.authority().to_string(),
+            )
+        })
+    });
 
     if props.signed_message.verify() {
         rsx!{
+            if let Some(context) = context {
+                b {
+                    "Context: "
+                }
+                span {
+                    class: "signed_context",
+                    "{context}"
+                }
+                br {}
+                "The signer bound this signature to the context above. Make sure it matches where you found the message."
+                br {}
+                br {}
+            }
+            if !headers.is_empty() {
+                b {
+                    "Signed Headers:"
+                }
+                table {
+                    class: "signed_headers",
+                    tbody {
+                        for (name, value) in headers {
+                            tr {
+                                td {
+                                    class: "header_name",
+                                    "{name}"
+                                }
+                                td {
+                                    "{value}"
+                                }
+                            }
+                        }
+                    }
+                }
+                br {}
+            }
             b {
                 "Message:"
             }
@@ -1114,6 +1839,31 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
             "{props.signed_message.message}"
             br {}
             br {}
+            if canonical_text {
+                "Only the words and paragraphs of this message were signed, so its spacing and line breaks may differ from the original."
+                br {}
+                br {}
+            }
+            match timestamp {
+                Some(Ok((time, authority))) => rsx! {
+                    b {
+                        "Timestamped {time} by {authority}"
+                    }
+                    br {}
+                    "Anyone can make a certificate with any name, so this is only as trustworthy as the authority's name is familiar."
+                    br {}
+                    br {}
+                },
+                Some(Err(e)) => rsx! {
+                    span {
+                        class: "signing_error",
+                        "{e}"
+                    }
+                    br {}
+                    br {}
+                },
+                None => rsx! {},
+            }
             b {
                 if all_known && all_verified {
                     "This message was signed by someone with the private key associated with one of these verified identities:"
@@ -1123,6 +1873,7 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
                     "This message was signed by someone with the private key associated with one of these identities, but not all of these are known identities"
                 }
             }
+            " ({version} format, {scheme} signature)"
             br {}
             br {}
             table {
@@ -1150,7 +1901,7 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
                             }
                             td {
                                 class: "email",
-                                "{pubkey.holder().email()}"
+                                EmailDisplay { email: pubkey.holder().email() }
                             }
                             td {
                                 class: "fingerprint",
@@ -1184,6 +1935,18 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
                     }
                 }
             }
+            if claimable {
+                AuthorshipClaimResults {
+                    signed_message: props.signed_message.clone(),
+                    signed_by_me: signed_by_me,
+                }
+            }
+            if disavowable {
+                DisavowalResults {
+                    signed_message: props.signed_message.clone(),
+                    my_ring_keys: my_ring_keys,
+                }
+            }
         }
     } else {
         rsx! {
@@ -1198,9 +1961,238 @@ fn VerificationResults(props: VerificationResultsProps) -> Element {
     }
 }
 
+#[derive(Clone, PartialEq, Props)]
+struct AuthorshipClaimResultsProps {
+    signed_message: SignedMessage,
+    signed_by_me: bool,
+}
+
+fn AuthorshipClaimResults(props: AuthorshipClaimResultsProps) -> Element {
+    let dbresult = use_context::<Signal<std::io::Result<Database>>>();
+    let mut claim_to_check = use_context::<Signal<ClaimToCheck>>();
+    let claim = claim_to_check.read().deref().0.clone();
+    let signed_for_claim = props.signed_message.clone();
+    let signed_for_check = props.signed_message.clone();
+    rsx! {
+        br {}
+        b {
+            "This signature is claimable: its signer can prove they wrote it, if they choose to."
+        }
+        br {}
+        if props.signed_by_me {
+            button {
+                onclick: move |_| {
+                    if let Ok(ref db) = dbresult.read().deref() {
+                        if let Ok(claim) = db.claim_authorship(&signed_for_claim) {
+                            if let Ok(mut ctx) = ClipboardContext::new() {
+                                let _ = ctx.set_contents(String::from(&claim));
+                            }
+                        }
+                    }
+                },
+                title: "Anyone who sees this claim will know that you signed the message. This can't be undone.",
+                "Claim Authorship and Copy to Clipboard"
+            }
+        }
+        button {
+            onclick: move |_| {
+                let claim = ClipboardContext::new()
+                    .and_then(|mut ctx| ctx.get_contents())
+                    .ok()
+                    .and_then(|c| AuthorshipClaim::from_str(&c).ok());
+                *claim_to_check.write() = ClaimToCheck(claim);
+            },
+            "Check Authorship Claim From Clipboard"
+        }
+        if let Some(claim) = claim {
+            br {}
+            if claim.verify(&signed_for_check) {
+                span {
+                    class: "claim_valid",
+                    "{claim.claimant().holder().name()} <{claim.claimant().holder().email()}> has proven that they signed this message."
+                }
+            } else {
+                span {
+                    class: "claim_invalid",
+                    "The claim in the clipboard is not a valid claim of this message."
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Props)]
+struct DisavowalResultsProps {
+    signed_message: SignedMessage,
+    my_ring_keys: Vec<PublicKey>,
+}
+
+fn DisavowalResults(props: DisavowalResultsProps) -> Element {
+    let dbresult = use_context::<Signal<std::io::Result<Database>>>();
+    let mut disavowal_to_check = use_context::<Signal<DisavowalToCheck>>();
+    let disavowal = disavowal_to_check.read().deref().0.clone();
+    let signed_for_check = props.signed_message.clone();
+    rsx! {
+        br {}
+        b {
+            "This signature is disavowable: everyone in the ring except the signer can prove they didn't write it."
+        }
+        br {}
+        for k in props.my_ring_keys {
+            button {
+                key: "{k.fingerprint()}",
+                onclick: {
+                    let signed = props.signed_message.clone();
+                    let k = k.clone();
+                    move |_| {
+                        if let Ok(ref db) = dbresult.read().deref() {
+                            if let Ok(proof) = db.disavow(&signed, &k) {
+                                if let Ok(mut ctx) = ClipboardContext::new() {
+                                    let _ = ctx.set_contents(String::from(&proof));
+                                }
+                            }
+                        }
+                    }
+                },
+                title: "Copies a proof that this key didn't sign the message. Nothing is copied if it did.",
+                "Prove {k.holder().name()} <{k.holder().email()}> Didn't Sign This and Copy to Clipboard"
+            }
+        }
+        button {
+            onclick: move |_| {
+                let disavowal = ClipboardContext::new()
+                    .and_then(|mut ctx| ctx.get_contents())
+                    .ok()
+                    .and_then(|d| DisavowalProof::from_str(&d).ok());
+                *disavowal_to_check.write() = DisavowalToCheck(disavowal);
+            },
+            "Check Disavowal From Clipboard"
+        }
+        if let Some(disavowal) = disavowal {
+            br {}
+            if signed_for_check.verify_disavowal(&disavowal) {
+                span {
+                    class: "claim_valid",
+                    "{disavowal.disavower().holder().name()} <{disavowal.disavower().holder().email()}> has proven that they did not sign this message."
+                }
+            } else {
+                span {
+                    class: "claim_invalid",
+                    "The proof in the clipboard is not a valid disavowal of this message."
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Props)]
+struct ThresholdVerificationResultsProps {
+    signed_message: ThresholdSignedMessage,
+}
+
+fn ThresholdVerificationResults(props: ThresholdVerificationResultsProps) -> Element {
+    let dbresult = use_context::<Signal<std::io::Result<Database>>>();
+    let (known_keys, my_keys) = match *dbresult.read() {
+        Ok(ref db) => (
+            db.visible_contents.their_public_keys.clone(),
+            db.visible_contents.my_public_keys.clone(),
+        ),
+        Err(_) => (BTreeMap::new(), BTreeSet::new()),
+    };
+    let ring_size = props.signed_message.ring().count();
+
+    if props.signed_message.verify() {
+        rsx! {
+            b {
+                "Message:"
+            }
+            br {}
+            "{props.signed_message.message()}"
+            br {}
+            br {}
+            b {
+                "This message was signed by at least {props.signed_message.threshold()} of these {ring_size} identities:"
+            }
+            br {}
+            br {}
+            table {
+                thead {
+                    th {
+                        "Name"
+                    }
+                    th {
+                        "Email"
+                    }
+                    th {
+                        "Fingerprint"
+                    }
+                    th {
+                        "Known"
+                    }
+                }
+                tbody {
+                    for pubkey in props.signed_message.ring() {
+                        tr {
+                            key: "{pubkey.fingerprint()}",
+                            td {
+                                class: "name",
+                                "{pubkey.holder().name()}"
+                            }
+                            td {
+                                class: "email",
+                                EmailDisplay { email: pubkey.holder().email() }
+                            }
+                            td {
+                                class: "fingerprint",
+                                "{pubkey.fingerprint()}"
+                            }
+                            td {
+                                class: "actions",
+                                if my_keys.contains(pubkey) || known_keys.get(pubkey).map(|v| v.is_verified()).unwrap_or(false) {
+                                        span {
+                                            title: "Key is verified",
+                                            Icon {
+                                                width: 15,
+                                                height: 15,
+                                                fill: "#00f",
+                                                icon: GoVerified,
+                                            }
+                                        }
+                                } else if known_keys.contains_key(pubkey) {
+                                        span {
+                                            title: "Key is known but unverified",
+                                            Icon {
+                                                width: 15,
+                                                height: 15,
+                                                fill: "#d80",
+                                                icon: GoVerified,
+                                            }
+                                        }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    } else {
+        rsx! {
+            b {
+                "Message:"
+            }
+            br {}
+            "{props.signed_message.message()}"
+            br {}
+            "Failed to verify."
+        }
+    }
+}
+
 fn Verify() -> Element {
     let message_to_verify = use_context::<Signal<MessageToVerify>>();
     let message_to_verify_val = message_to_verify.read().deref().0.clone();
+    let threshold_message_to_verify = use_context::<Signal<ThresholdMessageToVerify>>();
+    let threshold_message_to_verify_val = threshold_message_to_verify.read().deref().0.clone();
 
     if let Some(signed_message) = message_to_verify_val {
         rsx! {
@@ -1224,6 +2216,28 @@ fn Verify() -> Element {
                 }
             }
         }
+    } else if let Some(signed_message) = threshold_message_to_verify_val {
+        rsx! {
+            div {
+                class: "toolbar",
+                Icon {
+                    class: "action_icon",
+                    width: 15,
+                    height: 15,
+                    fill: "black",
+                    icon: GoShieldCheck,
+                }
+                PasteAndVerify {}
+            },
+            div {
+                class: "data",
+                div {
+                    ThresholdVerificationResults {
+                        signed_message: signed_message
+                    }
+                }
+            }
+        }
     } else {
         rsx! {
             div {
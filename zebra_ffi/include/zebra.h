@@ -0,0 +1,121 @@
+/* Generated by cbindgen from zebra_ffi. Do not edit. */
+
+#ifndef ZEBRA_H
+#define ZEBRA_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum ZebraError {
+  ZEBRA_ERROR_OK = 0,
+  // A required pointer argument was null.
+  ZEBRA_ERROR_NULL_POINTER = 1,
+  // A string argument wasn't valid UTF-8.
+  ZEBRA_ERROR_INVALID_UTF8 = 2,
+  // A key or message wasn't in the expected format, or was invalid.
+  ZEBRA_ERROR_PARSE_FAILED = 3,
+  // An index was past the end of a list.
+  ZEBRA_ERROR_OUT_OF_BOUNDS = 4,
+  // A string to be returned contains a NUL byte, so it can't be returned as a C string.
+  ZEBRA_ERROR_INTERIOR_NUL = 5,
+  // The private key wasn't the encoding of a valid private key.
+  ZEBRA_ERROR_INVALID_PRIVATE_KEY = 6,
+  // The signing options were invalid (e.g. an empty context).
+  ZEBRA_ERROR_SIGNING_FAILED = 7,
+  // Something went wrong inside the library. This is a bug.
+  ZEBRA_ERROR_PANIC = 8,
+} ZebraError;
+
+// A public key, as returned by `zebra_public_key_parse` or `zebra_signed_message_ring_member`.
+typedef struct ZebraPublicKey ZebraPublicKey;
+
+// A parsed signed message, which may or may not verify.
+typedef struct ZebraSignedMessage ZebraSignedMessage;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// A description of `error` (a `ZebraError`), as a static string, which must not be freed. Values
+// that aren't a `ZebraError` get a description too.
+const char *zebra_error_message(int error);
+
+// Free a string returned by this library. Does nothing if `s` is null.
+void zebra_string_free(char *s);
+
+// Parse a public key in Zebra's text format (`[Name <email> KEYPOINT ATTESTATION]`), checking
+// its attestation.
+enum ZebraError zebra_public_key_parse(const char *text, struct ZebraPublicKey **out_key);
+
+// Free a public key. Does nothing if `key` is null.
+void zebra_public_key_free(struct ZebraPublicKey *key);
+
+// The key in Zebra's text format.
+enum ZebraError zebra_public_key_to_string(const struct ZebraPublicKey *key, char **out_text);
+
+// The key's fingerprint, as shown to users.
+enum ZebraError zebra_public_key_fingerprint(const struct ZebraPublicKey *key,
+                                             char **out_fingerprint);
+
+// The name of the key's holder.
+enum ZebraError zebra_public_key_name(const struct ZebraPublicKey *key, char **out_name);
+
+// The email address of the key's holder.
+enum ZebraError zebra_public_key_email(const struct ZebraPublicKey *key, char **out_email);
+
+// Parse a signed message in Zebra's ASCII format. This doesn't verify it; see
+// `zebra_signed_message_verify`.
+enum ZebraError zebra_signed_message_parse(const char *text,
+                                           struct ZebraSignedMessage **out_message);
+
+// Free a signed message. Does nothing if `message` is null.
+void zebra_signed_message_free(struct ZebraSignedMessage *message);
+
+// Check that the message was signed by one of its ring members, and that every ring member's key
+// is valid. If `expected_context` isn't null, also check that the message was signed for exactly
+// that context; otherwise, any context (or none) is accepted, and callers should show it to the
+// user (see `zebra_signed_message_context`).
+enum ZebraError zebra_signed_message_verify(const struct ZebraSignedMessage *message,
+                                            const char *expected_context,
+                                            bool *out_valid);
+
+// The signed message text.
+enum ZebraError zebra_signed_message_text(const struct ZebraSignedMessage *message,
+                                          char **out_text);
+
+// The context that the message was signed for, or null if there isn't one.
+enum ZebraError zebra_signed_message_context(const struct ZebraSignedMessage *message,
+                                             char **out_context);
+
+// The number of keys in the message's ring.
+enum ZebraError zebra_signed_message_ring_len(const struct ZebraSignedMessage *message,
+                                              size_t *out_len);
+
+// A copy of the `index`th key of the message's ring, which must be freed separately. The ring is
+// in a canonical order, which doesn't depend on who signed.
+enum ZebraError zebra_signed_message_ring_member(const struct ZebraSignedMessage *message,
+                                                 size_t index,
+                                                 struct ZebraPublicKey **out_key);
+
+#if defined(ZEBRA_SIGNING)
+// Sign `message` with a ring of the given private key and `other_keys`, and write the signed
+// message, in Zebra's ASCII format. The private key is its exported (Borsh) encoding, of
+// `private_key_len` bytes; the caller is responsible for erasing it afterwards. `context` may be
+// null. Nonces are hedged, as in the desktop app.
+enum ZebraError zebra_sign(const uint8_t *private_key,
+                           size_t private_key_len,
+                           const char *message,
+                           const char *context,
+                           const struct ZebraPublicKey *const *other_keys,
+                           size_t other_keys_len,
+                           char **out_signed_message);
+#endif
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* ZEBRA_H */
@@ -0,0 +1,489 @@
+//! A C ABI for zebra_crypto, so that programs in other languages can parse and verify Zebra
+//! signatures. The header is `include/zebra.h`, generated by cbindgen (see `cbindgen.toml`). When
+//! the library is built with the `signing` feature, define `ZEBRA_SIGNING` before including it.
+//!
+//! The conventions, for every function:
+//!
+//! - The return value is a `ZebraError`, and results are written through the out-pointer
+//!   arguments, which are only written on success.
+//! - Keys and messages are opaque handles, which must be freed with the matching `_free` function.
+//!   Borrowed handles must outlive the call; nothing keeps a pointer to its arguments.
+//! - Strings passed in must be NUL-terminated UTF-8. Strings passed out are too, and must be freed
+//!   with `zebra_string_free`.
+//! - Null pointers are reported as errors, except that freeing null does nothing.
+//! - Panics never unwind across the boundary; they're reported as `ZEBRA_ERROR_PANIC`.
+
+// The safety requirements are the same for every function, and are described above.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::str::FromStr;
+
+use zebra_crypto::{PublicKey, SignedMessage};
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ZebraError {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// A string argument wasn't valid UTF-8.
+    InvalidUtf8 = 2,
+    /// A key or message wasn't in the expected format, or was invalid.
+    ParseFailed = 3,
+    /// An index was past the end of a list.
+    OutOfBounds = 4,
+    /// A string to be returned contains a NUL byte, so it can't be returned as a C string.
+    InteriorNul = 5,
+    /// The private key wasn't the encoding of a valid private key.
+    InvalidPrivateKey = 6,
+    /// The signing options were invalid (e.g. an empty context).
+    SigningFailed = 7,
+    /// Something went wrong inside the library. This is a bug.
+    Panic = 8,
+}
+
+/// A public key, as returned by `zebra_public_key_parse` or `zebra_signed_message_ring_member`.
+pub struct ZebraPublicKey(PublicKey);
+
+/// A parsed signed message, which may or may not verify.
+pub struct ZebraSignedMessage(SignedMessage);
+
+fn guard(f: impl FnOnce() -> Result<(), ZebraError>) -> ZebraError {
+    // The closures only touch their arguments, and on a panic we return without looking at them
+    // again, so there's nothing that could be observed in a broken state.
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ZebraError::Ok,
+        Ok(Err(e)) => e,
+        Err(_) => ZebraError::Panic,
+    }
+}
+
+unsafe fn input_str<'a>(s: *const c_char) -> Result<&'a str, ZebraError> {
+    if s.is_null() {
+        return Err(ZebraError::NullPointer);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| ZebraError::InvalidUtf8)
+}
+
+unsafe fn input_handle<'a, T>(handle: *const T) -> Result<&'a T, ZebraError> {
+    handle.as_ref().ok_or(ZebraError::NullPointer)
+}
+
+unsafe fn output<T>(out: *mut T, value: T) -> Result<(), ZebraError> {
+    if out.is_null() {
+        return Err(ZebraError::NullPointer);
+    }
+    out.write(value);
+    Ok(())
+}
+
+unsafe fn output_string(out: *mut *mut c_char, s: &str) -> Result<(), ZebraError> {
+    let s = CString::new(s).map_err(|_| ZebraError::InteriorNul)?;
+    output(out, s.into_raw())
+}
+
+unsafe fn output_handle<T>(out: *mut *mut T, value: T) -> Result<(), ZebraError> {
+    if out.is_null() {
+        return Err(ZebraError::NullPointer);
+    }
+    out.write(Box::into_raw(Box::new(value)));
+    Ok(())
+}
+
+/// A description of `error` (a `ZebraError`), as a static string, which must not be freed. Values
+/// that aren't a `ZebraError` get a description too.
+#[no_mangle]
+pub extern "C" fn zebra_error_message(error: c_int) -> *const c_char {
+    // This takes an int rather than a ZebraError because C callers can pass any int, and a Rust
+    // enum with an invalid value is undefined behavior before we ever get to look at it.
+    const ERRORS: [ZebraError; 9] = [
+        ZebraError::Ok,
+        ZebraError::NullPointer,
+        ZebraError::InvalidUtf8,
+        ZebraError::ParseFailed,
+        ZebraError::OutOfBounds,
+        ZebraError::InteriorNul,
+        ZebraError::InvalidPrivateKey,
+        ZebraError::SigningFailed,
+        ZebraError::Panic,
+    ];
+    let message: &'static [u8] = match ERRORS.iter().find(|&&e| e as c_int == error) {
+        None => b"Unknown error\0",
+        Some(ZebraError::Ok) => b"Success\0",
+        Some(ZebraError::NullPointer) => b"A required pointer was null\0",
+        Some(ZebraError::InvalidUtf8) => b"A string wasn't valid UTF-8\0",
+        Some(ZebraError::ParseFailed) => b"Invalid key or message\0",
+        Some(ZebraError::OutOfBounds) => b"Index out of bounds\0",
+        Some(ZebraError::InteriorNul) => b"The result contains a NUL byte\0",
+        Some(ZebraError::InvalidPrivateKey) => b"Invalid private key\0",
+        Some(ZebraError::SigningFailed) => b"Invalid signing options\0",
+        Some(ZebraError::Panic) => b"Internal error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Free a string returned by this library. Does nothing if `s` is null.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Parse a public key in Zebra's text format (`[Name <email> KEYPOINT ATTESTATION]`), checking
+/// its attestation.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_parse(
+    text: *const c_char,
+    out_key: *mut *mut ZebraPublicKey,
+) -> ZebraError {
+    guard(|| {
+        let key = PublicKey::from_str(input_str(text)?).map_err(|_| ZebraError::ParseFailed)?;
+        output_handle(out_key, ZebraPublicKey(key))
+    })
+}
+
+/// Free a public key. Does nothing if `key` is null.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_free(key: *mut ZebraPublicKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// The key in Zebra's text format.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_to_string(
+    key: *const ZebraPublicKey,
+    out_text: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| output_string(out_text, &String::from(input_handle(key)?.0.clone())))
+}
+
+/// The key's fingerprint, as shown to users.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_fingerprint(
+    key: *const ZebraPublicKey,
+    out_fingerprint: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| output_string(out_fingerprint, &input_handle(key)?.0.fingerprint()))
+}
+
+/// The name of the key's holder.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_name(
+    key: *const ZebraPublicKey,
+    out_name: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| output_string(out_name, &input_handle(key)?.0.holder().name()))
+}
+
+/// The email address of the key's holder.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_public_key_email(
+    key: *const ZebraPublicKey,
+    out_email: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| output_string(out_email, &input_handle(key)?.0.holder().email()))
+}
+
+/// Parse a signed message in Zebra's ASCII format. This doesn't verify it; see
+/// `zebra_signed_message_verify`.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_parse(
+    text: *const c_char,
+    out_message: *mut *mut ZebraSignedMessage,
+) -> ZebraError {
+    guard(|| {
+        let message =
+            SignedMessage::from_str(input_str(text)?).map_err(|_| ZebraError::ParseFailed)?;
+        output_handle(out_message, ZebraSignedMessage(message))
+    })
+}
+
+/// Free a signed message. Does nothing if `message` is null.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_free(message: *mut ZebraSignedMessage) {
+    if !message.is_null() {
+        drop(Box::from_raw(message));
+    }
+}
+
+/// Check that the message was signed by one of its ring members, and that every ring member's key
+/// is valid. If `expected_context` isn't null, also check that the message was signed for exactly
+/// that context; otherwise, any context (or none) is accepted, and callers should show it to the
+/// user (see `zebra_signed_message_context`).
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_verify(
+    message: *const ZebraSignedMessage,
+    expected_context: *const c_char,
+    out_valid: *mut bool,
+) -> ZebraError {
+    guard(|| {
+        let message = &input_handle(message)?.0;
+        let valid = if expected_context.is_null() {
+            message.verify()
+        } else {
+            message.verify_with_context(Some(input_str(expected_context)?))
+        };
+        output(out_valid, valid)
+    })
+}
+
+/// The signed message text.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_text(
+    message: *const ZebraSignedMessage,
+    out_text: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| output_string(out_text, &input_handle(message)?.0.message))
+}
+
+/// The context that the message was signed for, or null if there isn't one.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_context(
+    message: *const ZebraSignedMessage,
+    out_context: *mut *mut c_char,
+) -> ZebraError {
+    guard(|| match input_handle(message)?.0.context() {
+        Some(context) => output_string(out_context, context),
+        None => output(out_context, std::ptr::null_mut()),
+    })
+}
+
+/// The number of keys in the message's ring.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_ring_len(
+    message: *const ZebraSignedMessage,
+    out_len: *mut usize,
+) -> ZebraError {
+    guard(|| output(out_len, input_handle(message)?.0.ring().count()))
+}
+
+/// A copy of the `index`th key of the message's ring, which must be freed separately. The ring is
+/// in a canonical order, which doesn't depend on who signed.
+#[no_mangle]
+pub unsafe extern "C" fn zebra_signed_message_ring_member(
+    message: *const ZebraSignedMessage,
+    index: usize,
+    out_key: *mut *mut ZebraPublicKey,
+) -> ZebraError {
+    guard(|| {
+        let key = input_handle(message)?
+            .0
+            .ring()
+            .nth(index)
+            .ok_or(ZebraError::OutOfBounds)?;
+        output_handle(out_key, ZebraPublicKey(key.clone()))
+    })
+}
+
+/// Sign `message` with a ring of the given private key and `other_keys`, and write the signed
+/// message, in Zebra's ASCII format. The private key is its exported (Borsh) encoding, of
+/// `private_key_len` bytes; the caller is responsible for erasing it afterwards. `context` may be
+/// null. Nonces are hedged, as in the desktop app.
+#[cfg(feature = "signing")]
+#[no_mangle]
+pub unsafe extern "C" fn zebra_sign(
+    private_key: *const u8,
+    private_key_len: usize,
+    message: *const c_char,
+    context: *const c_char,
+    other_keys: *const *const ZebraPublicKey,
+    other_keys_len: usize,
+    out_signed_message: *mut *mut c_char,
+) -> ZebraError {
+    use borsh::BorshDeserialize;
+    use zebra_crypto::{PrivateKey, SigningOptions};
+
+    guard(|| {
+        if private_key.is_null() || (other_keys.is_null() && other_keys_len != 0) {
+            return Err(ZebraError::NullPointer);
+        }
+        let private_key =
+            PrivateKey::try_from_slice(std::slice::from_raw_parts(private_key, private_key_len))
+                .map_err(|_| ZebraError::InvalidPrivateKey)?;
+        let message = input_str(message)?;
+        let context = if context.is_null() {
+            None
+        } else {
+            Some(input_str(context)?.to_string())
+        };
+        let mut keys = vec![];
+        if other_keys_len != 0 {
+            for &key in std::slice::from_raw_parts(other_keys, other_keys_len) {
+                keys.push(input_handle(key)?.0.clone());
+            }
+        }
+        let options = SigningOptions {
+            context,
+            hedged: true,
+            ..SigningOptions::default()
+        };
+        let signed = SignedMessage::sign_with_options(message, &private_key, &keys, &options)
+            .ok_or(ZebraError::SigningFailed)?;
+        output_string(out_signed_message, &String::from(&signed))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::ptr::null_mut;
+
+    use zebra_crypto::{Identity, PrivateKey, SignedMessage};
+
+    use crate::*;
+
+    unsafe fn take_string(s: *mut c_char) -> String {
+        let result = CStr::from_ptr(s).to_str().unwrap().to_string();
+        zebra_string_free(s);
+        result
+    }
+
+    #[test]
+    fn parse_and_verify_work() {
+        let alice = PrivateKey::new(Identity::new("Alice", "alice@example.com").unwrap());
+        let bob = PrivateKey::new(Identity::new("Bob", "bob@example.com").unwrap());
+        let signed = SignedMessage::sign("Hello", &alice, &[bob.public()]);
+        let text = CString::new(String::from(&signed)).unwrap();
+        let other_context = CString::new("Some context").unwrap();
+        let garbage = CString::new("Not a signed message").unwrap();
+
+        unsafe {
+            let mut message = null_mut();
+            assert_eq!(
+                zebra_signed_message_parse(text.as_ptr(), &mut message),
+                ZebraError::Ok
+            );
+            let mut valid = false;
+            assert_eq!(
+                zebra_signed_message_verify(message, std::ptr::null(), &mut valid),
+                ZebraError::Ok
+            );
+            assert!(valid);
+            assert_eq!(
+                zebra_signed_message_verify(message, other_context.as_ptr(), &mut valid),
+                ZebraError::Ok
+            );
+            assert!(!valid);
+
+            let mut s = null_mut();
+            assert_eq!(zebra_signed_message_text(message, &mut s), ZebraError::Ok);
+            assert_eq!(take_string(s), "Hello");
+            assert_eq!(
+                zebra_signed_message_context(message, &mut s),
+                ZebraError::Ok
+            );
+            assert!(s.is_null());
+
+            let mut len = 0;
+            assert_eq!(
+                zebra_signed_message_ring_len(message, &mut len),
+                ZebraError::Ok
+            );
+            assert_eq!(len, 2);
+            let mut fingerprints = vec![];
+            for i in 0..len {
+                let mut key = null_mut();
+                assert_eq!(
+                    zebra_signed_message_ring_member(message, i, &mut key),
+                    ZebraError::Ok
+                );
+                assert_eq!(zebra_public_key_fingerprint(key, &mut s), ZebraError::Ok);
+                fingerprints.push(take_string(s));
+                zebra_public_key_free(key);
+            }
+            let mut expected = vec![alice.public().fingerprint(), bob.public().fingerprint()];
+            fingerprints.sort();
+            expected.sort();
+            assert_eq!(fingerprints, expected);
+
+            let mut key = null_mut();
+            assert_eq!(
+                zebra_signed_message_ring_member(message, len, &mut key),
+                ZebraError::OutOfBounds
+            );
+            assert!(key.is_null());
+            zebra_signed_message_free(message);
+
+            let bob_text = CString::new(String::from(bob.public())).unwrap();
+            assert_eq!(
+                zebra_public_key_parse(bob_text.as_ptr(), &mut key),
+                ZebraError::Ok
+            );
+            assert_eq!(zebra_public_key_name(key, &mut s), ZebraError::Ok);
+            assert_eq!(take_string(s), "Bob");
+            assert_eq!(zebra_public_key_to_string(key, &mut s), ZebraError::Ok);
+            assert_eq!(take_string(s), bob_text.to_str().unwrap());
+            zebra_public_key_free(key);
+
+            let mut message = null_mut();
+            assert_eq!(
+                zebra_signed_message_parse(garbage.as_ptr(), &mut message),
+                ZebraError::ParseFailed
+            );
+            assert_eq!(
+                zebra_signed_message_parse(std::ptr::null(), &mut message),
+                ZebraError::NullPointer
+            );
+            assert!(message.is_null());
+
+            let describe = |error| CStr::from_ptr(zebra_error_message(error)).to_str().unwrap();
+            assert_eq!(describe(ZebraError::Ok as c_int), "Success");
+            assert_eq!(describe(ZebraError::Panic as c_int), "Internal error");
+            assert_eq!(describe(-1), "Unknown error");
+            assert_eq!(describe(9), "Unknown error");
+        }
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signing_works() {
+        let alice = PrivateKey::new(Identity::new("Alice", "alice@example.com").unwrap());
+        let bob = PrivateKey::new(Identity::new("Bob", "bob@example.com").unwrap());
+        let exported = borsh::to_vec(&alice).unwrap();
+        let bob_public = ZebraPublicKey(bob.public());
+        let other_keys = [&bob_public as *const ZebraPublicKey];
+        let hello = CString::new("Hello").unwrap();
+        let context = CString::new("A context").unwrap();
+
+        unsafe {
+            let mut s = null_mut();
+            assert_eq!(
+                zebra_sign(
+                    exported.as_ptr(),
+                    exported.len(),
+                    hello.as_ptr(),
+                    context.as_ptr(),
+                    other_keys.as_ptr(),
+                    other_keys.len(),
+                    &mut s,
+                ),
+                ZebraError::Ok
+            );
+            let signed: SignedMessage = take_string(s).parse().unwrap();
+            assert!(signed.verify_with_context(Some("A context")));
+            assert_eq!(signed.ring().count(), 2);
+
+            assert_eq!(
+                zebra_sign(
+                    exported.as_ptr(),
+                    exported.len() - 1,
+                    hello.as_ptr(),
+                    std::ptr::null(),
+                    std::ptr::null(),
+                    0,
+                    &mut s,
+                ),
+                ZebraError::InvalidPrivateKey
+            );
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! Zebra ring signatures for JSON documents, embedded in the documents themselves.
+//!
+//! Signing takes a JSON object, canonicalizes it with the JSON Canonicalization Scheme (JCS, RFC
+//! 8785), and ring-signs the result. The signature, along with the ring's public keys, is then
+//! added to the object as its `SIGNATURE_FIELD` member. Verifying removes that member again, and
+//! canonicalizes and checks what's left, so a signed document can be pretty-printed, have its
+//! members reordered, or be stored in a database that doesn't preserve formatting, and still
+//! verify.
+//!
+//! JCS treats every number as an IEEE 754 double, as JavaScript does, so integers beyond 2^53 are
+//! signed as the nearest double; documents that need them exactly should hold them as strings.
+//! Likewise, serde_json keeps only the last of any duplicate member names, so what's signed is
+//! what a typical JSON parser would see.
+
+use base64::Engine;
+use rand::{CryptoRng, RngCore};
+use serde_json::{Map, Value};
+use zebra_crypto::{PrivateKey, PublicKey, RingScheme, RingSignature};
+
+/// The name of the object member that holds the signature.
+pub const SIGNATURE_FIELD: &str = "zebra_signature";
+
+/// Prepended to the canonical document to get the bytes that are actually signed, so that these
+/// signatures can't be confused with raw ring signatures from any other protocol.
+const SIGNED_DOMAIN: &[u8] = b"Zebra JSON signature v1\0";
+
+/// The `version` of the signature member's contents.
+const SIGNATURE_ENCODING_VERSION: u64 = 1;
+
+// The signature member is an object with exactly three members: "version", which is
+// SIGNATURE_ENCODING_VERSION; "ring", an array of the ring's public keys (in zebra_crypto's serde
+// format), sorted; and "signature", the standard base64 encoding of the `RingSignature::to_bytes`
+// encoding. As in zebra_mime, the full public keys are included so that recipients can see who
+// the ring members claim to be, even if they don't already have their keys.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonError {
+    /// The document isn't a JSON object, so it has nowhere to put a signature.
+    NotAnObject,
+    /// The document to sign is already signed.
+    AlreadySigned,
+    /// The document isn't signed with Zebra.
+    NotSigned,
+    /// The document's signature member isn't in the expected format.
+    Malformed,
+    /// The document is Zebra-signed, but the signature doesn't verify.
+    BadSignature,
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JsonError::NotAnObject => "This JSON document isn't an object",
+            JsonError::AlreadySigned => "This document is already signed",
+            JsonError::NotSigned => "This document isn't signed with Zebra",
+            JsonError::Malformed => "This document's Zebra signature isn't well-formed",
+            JsonError::BadSignature => "This document's Zebra signature is invalid",
+        })
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// A successfully verified document.
+pub struct Verification {
+    ring: Vec<PublicKey>,
+    document: Value,
+}
+
+impl Verification {
+    /// The ring: the signer holds one of these keys' private keys.
+    pub fn ring(&self) -> &[PublicKey] {
+        &self.ring
+    }
+
+    /// The signed document, without its signature member.
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+}
+
+/// Sign a JSON object with a ring of `my_key` and `other_keys`, returning a copy of it with the
+/// signature added as its `SIGNATURE_FIELD` member.
+pub fn sign(
+    document: &Value,
+    my_key: &PrivateKey,
+    other_keys: &[PublicKey],
+    scheme: RingScheme,
+) -> Result<Value, JsonError> {
+    sign_with_rng(document, my_key, other_keys, scheme, &mut rand::rngs::OsRng)
+}
+
+/// Like `sign`, but with randomness from `rng` rather than from the operating system.
+pub fn sign_with_rng(
+    document: &Value,
+    my_key: &PrivateKey,
+    other_keys: &[PublicKey],
+    scheme: RingScheme,
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Result<Value, JsonError> {
+    let object = document.as_object().ok_or(JsonError::NotAnObject)?;
+    if object.contains_key(SIGNATURE_FIELD) {
+        return Err(JsonError::AlreadySigned);
+    }
+
+    let keypoints = other_keys
+        .iter()
+        .map(|k| k.keypoint_bytes())
+        .collect::<Vec<_>>();
+    let signature = RingSignature::sign_keypoints_with_rng(
+        &signed_bytes(document),
+        my_key,
+        &keypoints,
+        scheme,
+        rng,
+    )
+    .expect("Public keys always have valid keypoints");
+    let mut ring = other_keys.to_vec();
+    ring.push(my_key.public());
+    ring.sort();
+    ring.dedup();
+
+    let mut field = Map::new();
+    field.insert("version".into(), SIGNATURE_ENCODING_VERSION.into());
+    field.insert(
+        "ring".into(),
+        serde_json::to_value(ring).expect("Public keys always serialize"),
+    );
+    field.insert(
+        "signature".into(),
+        base64::engine::general_purpose::STANDARD
+            .encode(signature.to_bytes())
+            .into(),
+    );
+    let mut signed = object.clone();
+    signed.insert(SIGNATURE_FIELD.into(), Value::Object(field));
+    Ok(Value::Object(signed))
+}
+
+/// Verify a JSON object signed by `sign` (possibly after it has been reformatted, or had its
+/// members reordered).
+pub fn verify(document: &Value) -> Result<Verification, JsonError> {
+    let mut object = document.as_object().ok_or(JsonError::NotAnObject)?.clone();
+    let field = object.remove(SIGNATURE_FIELD).ok_or(JsonError::NotSigned)?;
+    let field = field.as_object().ok_or(JsonError::Malformed)?;
+    if field.len() != 3
+        || field.get("version").and_then(Value::as_u64) != Some(SIGNATURE_ENCODING_VERSION)
+    {
+        return Err(JsonError::Malformed);
+    }
+    let ring = field
+        .get("ring")
+        .and_then(|r| serde_json::from_value::<Vec<PublicKey>>(r.clone()).ok())
+        .ok_or(JsonError::Malformed)?;
+    let signature = field
+        .get("signature")
+        .and_then(Value::as_str)
+        .and_then(|s| base64::engine::general_purpose::STANDARD.decode(s).ok())
+        .and_then(|bytes| RingSignature::from_bytes(&bytes))
+        .ok_or(JsonError::Malformed)?;
+
+    let document = Value::Object(object);
+    if !signature.verify_with_keys(&signed_bytes(&document), &ring) {
+        return Err(JsonError::BadSignature);
+    }
+    Ok(Verification { ring, document })
+}
+
+fn signed_bytes(document: &Value) -> Vec<u8> {
+    [SIGNED_DOMAIN, canonicalize(document).as_bytes()].concat()
+}
+
+/// The JCS (RFC 8785) canonical form of `value`: no whitespace, object members sorted by the
+/// UTF-16 code units of their names, and strings and numbers serialized as ECMAScript's
+/// `JSON.stringify` does.
+pub fn canonicalize(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => {
+            // Without serde_json's arbitrary_precision feature, every number has an f64 value.
+            out.push_str(&canonical_number(n.as_f64().expect("Numbers are finite")))
+        }
+        Value::String(s) => write_canonical_string(s, out),
+        Value::Array(values) => {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out);
+            }
+            out.push(']');
+        }
+        Value::Object(members) => {
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (name, v)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_string(name, out);
+                out.push(':');
+                write_canonical(v, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_canonical_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Format a double as ECMAScript's Number.prototype.toString does (ECMA-262, section 7.1.12.1),
+/// which is what JCS requires.
+fn canonical_number(n: f64) -> String {
+    if n == 0.0 {
+        // Including -0.
+        return "0".to_string();
+    }
+    // Rust's `{:e}` gives the shortest digits that round-trip, which is also what ECMAScript
+    // uses; only the placement of the decimal point and exponent differs.
+    let (digits, exponent) = split_scientific(&format!("{:e}", n.abs()));
+    let digits = round_half_even(n.abs(), digits, exponent);
+    let k = digits.len() as i32;
+    // The decimal point goes after the n-th digit.
+    let n_point = exponent + 1;
+
+    let mut out = String::new();
+    if n < 0.0 {
+        out.push('-');
+    }
+    if k <= n_point && n_point <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n_point - k) as usize));
+    } else if 0 < n_point && n_point <= 21 {
+        out.push_str(&digits[..n_point as usize]);
+        out.push('.');
+        out.push_str(&digits[n_point as usize..]);
+    } else if -6 < n_point && n_point <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-n_point as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let e = n_point - 1;
+        out.push('e');
+        out.push(if e < 0 { '-' } else { '+' });
+        out.push_str(&e.abs().to_string());
+    }
+    out
+}
+
+/// The significant digits and exponent of a number in Rust's `{:e}` format.
+fn split_scientific(scientific: &str) -> (String, i32) {
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent");
+    (
+        mantissa.replace('.', ""),
+        exponent.parse().expect("Exponents are integers"),
+    )
+}
+
+/// When `n` is exactly halfway between two shortest round-tripping decimals, Rust picks the
+/// larger, but ECMAScript picks the one whose last digit is even (e.g. 1424953923781206.25 is
+/// written as 1424953923781206.2).
+fn round_half_even(n: f64, digits: String, exponent: i32) -> String {
+    let (prefix, last) = digits.split_at(digits.len() - 1);
+    let last = last.as_bytes()[0] - b'0';
+    if last & 1 == 0 || digits.len() < 2 {
+        return digits;
+    }
+    // Every double has a finite decimal expansion, of at most 767 significant digits.
+    let (exact, exact_exponent) = split_scientific(&format!("{:.800e}", n));
+    if exact_exponent != exponent {
+        return digits;
+    }
+    let exact = exact.trim_end_matches('0');
+    for (midpoint, even) in [(last - 1, last - 1), (last, last + 1)] {
+        let candidate = format!("{prefix}{even}");
+        if even <= 9
+            && exact == format!("{prefix}{midpoint}5")
+            && format!("{}.{}e{exponent}", &candidate[..1], &candidate[1..]).parse() == Ok(n)
+        {
+            return candidate;
+        }
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use serde_json::json;
+    use zebra_crypto::Identity;
+
+    use super::*;
+
+    fn keys(rng: &mut StdRng) -> Vec<PrivateKey> {
+        [
+            ("Alice Example", "alice@example.com"),
+            ("Bob Example", "bob@example.com"),
+            ("Zoë Exemple", "zoe@example.org"),
+        ]
+        .iter()
+        .map(|(name, email)| PrivateKey::new_with_rng(Identity::new(name, email).unwrap(), rng))
+        .collect()
+    }
+
+    #[test]
+    fn canonicalization_matches_rfc_8785() {
+        // From RFC 8785, appendix B, as IEEE 754 bit patterns and their canonical forms.
+        for (bits, expected) in [
+            (0x0000000000000000u64, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ] {
+            assert_eq!(canonical_number(f64::from_bits(bits)), expected);
+        }
+
+        // RFC 8785, section 3.2.2.
+        let document: Value = serde_json::from_str(
+            r#"{
+              "numbers": [333333333.33333329, 1E30, 4.50,
+                          2e-3, 0.000000000000000000000000001],
+              "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+              "literals": [null, true, false]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            canonicalize(&document),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        // RFC 8785, section 3.2.3: names are sorted by UTF-16 code units, not code points.
+        let document: Value = serde_json::from_str(
+            r#"{
+              "\u20ac": "Euro Sign",
+              "\r": "Carriage Return",
+              "\ufb33": "Hebrew Letter Dalet With Dagesh",
+              "1": "One",
+              "\ud83d\ude00": "Emoji: Grinning Face",
+              "\u0080": "Control",
+              "\u00f6": "Latin Small Letter O With Diaeresis"
+            }"#,
+        )
+        .unwrap();
+        let names = canonicalize(&document)
+            .split(',')
+            .map(|member| member.split('"').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "\\r",
+                "1",
+                "\u{80}",
+                "\u{f6}",
+                "\u{20ac}",
+                "\u{1f600}",
+                "\u{fb33}"
+            ]
+        );
+    }
+
+    #[test]
+    fn sign_and_verify_work() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys = keys(&mut rng);
+        let others = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let document = json!({
+            "order": 1234,
+            "items": [{"name": "Zebra plush", "price": 19.99, "quantity": 2}],
+            "note": "Leave it with the neighbours ☺",
+        });
+
+        for (signer, scheme) in [(0, RingScheme::Sag), (2, RingScheme::OneOfMany)] {
+            let signed =
+                sign_with_rng(&document, &keys[signer], &others, scheme, &mut rng).unwrap();
+            let verification = verify(&signed).unwrap();
+            assert_eq!(verification.ring().len(), 3);
+            assert_eq!(verification.document(), &document);
+
+            // Reformatting doesn't matter, and neither does member order.
+            let pretty = serde_json::to_string_pretty(&signed).unwrap();
+            let reordered = pretty.replacen(
+                "\"note\": \"Leave it with the neighbours ☺\",",
+                "\"note\": \"Leave it with the neighbours \\u263A\", \"order\": 1234.0,",
+                1,
+            );
+            let reordered = reordered.replacen("\"order\": 1234,", "", 1);
+            assert_ne!(reordered, pretty);
+            let reparsed: Value = serde_json::from_str(&reordered).unwrap();
+            assert!(verify(&reparsed).is_ok());
+
+            assert_eq!(
+                sign_with_rng(&signed, &keys[1], &[], scheme, &mut rng).err(),
+                Some(JsonError::AlreadySigned)
+            );
+        }
+
+        assert_eq!(verify(&document).err(), Some(JsonError::NotSigned));
+        assert_eq!(
+            sign_with_rng(&json!([1, 2, 3]), &keys[0], &[], RingScheme::Sag, &mut rng).err(),
+            Some(JsonError::NotAnObject)
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let keys = keys(&mut rng);
+        let others = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let document = json!({"amount": 100, "to": "alice@example.com"});
+        let signed =
+            sign_with_rng(&document, &keys[1], &others, RingScheme::Sag, &mut rng).unwrap();
+
+        let mut tampered = signed.clone();
+        tampered["amount"] = json!(1000);
+        assert_eq!(verify(&tampered).err(), Some(JsonError::BadSignature));
+
+        let mut added = signed.clone();
+        added["memo"] = json!("");
+        assert_eq!(verify(&added).err(), Some(JsonError::BadSignature));
+
+        // Dropping a ring member, or mangling the signature member, is also caught.
+        let mut smaller_ring = signed.clone();
+        smaller_ring[SIGNATURE_FIELD]["ring"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        assert_eq!(verify(&smaller_ring).err(), Some(JsonError::BadSignature));
+        let mut extra = signed.clone();
+        extra[SIGNATURE_FIELD]["comment"] = json!("hi");
+        assert_eq!(verify(&extra).err(), Some(JsonError::Malformed));
+        let mut mangled = signed;
+        mangled[SIGNATURE_FIELD]["signature"] = json!("not base64!");
+        assert_eq!(verify(&mangled).err(), Some(JsonError::Malformed));
+    }
+}
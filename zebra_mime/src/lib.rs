@@ -0,0 +1,522 @@
+//! Zebra ring signatures for email, as `multipart/signed` messages (RFC 1847).
+//!
+//! Signing takes an RFC 5322 message (as in a .eml file) and moves its body, along with its
+//! `Content-*` header fields, into the first part of a `multipart/signed` body. The second part
+//! is an `application/x-zebra-signature` attachment, holding the ring's public keys and a detached
+//! `RingSignature` of the first part. Because the signature doesn't depend on anything outside of
+//! the first part, mail servers can add and rewrite the message's other header fields without
+//! breaking it, and clients that don't know about Zebra just show the message with an attachment.
+//!
+//! The message's From field isn't part of the first part, so a copy of it is signed along with
+//! the body. `Verification::from_in_ring` checks that the From field still matches that copy, and
+//! that it's the email address of one of the ring members.
+
+use base64::Engine;
+use boringascii::idn;
+use borsh::{BorshDeserialize, BorshSerialize};
+use rand::{CryptoRng, RngCore};
+use zebra_crypto::{PrivateKey, PublicKey, RingScheme, RingSignature};
+
+mod parse;
+
+use parse::{address, canonicalize, param, split_entity, split_multipart, split_params, Header};
+
+/// The MIME type of the signature part, which is also the multipart/signed `protocol` parameter.
+pub const SIGNATURE_TYPE: &str = "application/x-zebra-signature";
+
+/// Prepended to the first part to get the bytes that are actually signed, so that these
+/// signatures can't be confused with raw ring signatures from any other protocol.
+const SIGNED_DOMAIN: &[u8] = b"Zebra MIME signature v1\0";
+
+/// The first byte of the signature part's (decoded) contents.
+const SIGNATURE_ENCODING_VERSION: u8 = 1;
+
+/// Lines in the signature part, and in parts that we base64-encode, are this long. RFC 2045 allows
+/// up to 76 characters.
+const BASE64_LINE_LEN: usize = 76;
+
+// The signature part's contents, after base64 decoding, are SIGNATURE_ENCODING_VERSION followed by
+// the Borsh encoding of (ring, signature), where `ring` is a Vec<PublicKey>, and `signature` is a
+// Vec<u8> holding the `RingSignature::to_bytes` encoding. Including the full public keys (and not
+// just the keypoints in the RingSignature) lets recipients see who the ring members claim to be,
+// even if they don't already have their keys.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MimeError {
+    /// The input isn't a well-formed RFC 5322 message (or, when verifying, it isn't a well-formed
+    /// multipart/signed message).
+    Malformed,
+    /// The message to sign is already signed.
+    AlreadySigned,
+    /// The message isn't signed with Zebra.
+    NotSigned,
+    /// The message is Zebra-signed, but the signature doesn't verify.
+    BadSignature,
+}
+
+impl std::fmt::Display for MimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MimeError::Malformed => "This isn't a well-formed email message",
+            MimeError::AlreadySigned => "This message is already signed",
+            MimeError::NotSigned => "This message isn't signed with Zebra",
+            MimeError::BadSignature => "This message's Zebra signature is invalid",
+        })
+    }
+}
+
+impl std::error::Error for MimeError {}
+
+/// A successfully verified message.
+pub struct Verification {
+    ring: Vec<PublicKey>,
+    from: Option<String>,
+    signed_from: Option<String>,
+    signed_part: Vec<u8>,
+}
+
+impl Verification {
+    /// The ring: the signer holds one of these keys' private keys.
+    pub fn ring(&self) -> &[PublicKey] {
+        &self.ring
+    }
+
+    /// The address in the message's From field, if it has one.
+    pub fn from(&self) -> Option<&str> {
+        self.from.as_deref()
+    }
+
+    /// Whether the From field's address is the one that was signed, and is the email address of
+    /// one of the ring members. If not, the message may have been forwarded or rewritten by a
+    /// mailing list, or someone may be trying to pass off somebody else's message as their own.
+    pub fn from_in_ring(&self) -> bool {
+        // Ring members' addresses are stored with their domains in ASCII (punycode) form, but
+        // From fields may have them in UTF-8 (RFC 6532).
+        let to_ascii = |email: &String| idn::email_to_ascii(email).unwrap_or_else(|| email.clone());
+        match (
+            self.from.as_ref().map(to_ascii),
+            self.signed_from.as_ref().map(to_ascii),
+        ) {
+            (Some(from), Some(signed_from)) => {
+                from.eq_ignore_ascii_case(&signed_from)
+                    && self
+                        .ring
+                        .iter()
+                        .any(|k| k.holder().email().eq_ignore_ascii_case(&from))
+            }
+            _ => false,
+        }
+    }
+
+    /// The signed first part, including its header fields (in MIME format, with CRLF line
+    /// endings).
+    pub fn signed_part(&self) -> &[u8] {
+        &self.signed_part
+    }
+}
+
+/// Sign an email message (in RFC 5322 format, with either CRLF or LF line endings), with a ring
+/// of `my_key` and `other_keys`. The result is in RFC 5322 format, with CRLF line endings.
+pub fn sign(
+    message: &[u8],
+    my_key: &PrivateKey,
+    other_keys: &[PublicKey],
+    scheme: RingScheme,
+) -> Result<Vec<u8>, MimeError> {
+    sign_with_rng(message, my_key, other_keys, scheme, &mut rand::rngs::OsRng)
+}
+
+/// Like `sign`, but with randomness from `rng` rather than from the operating system.
+pub fn sign_with_rng(
+    message: &[u8],
+    my_key: &PrivateKey,
+    other_keys: &[PublicKey],
+    scheme: RingScheme,
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Result<Vec<u8>, MimeError> {
+    let message = canonicalize(message);
+    let (headers, body) = split_entity(&message).ok_or(MimeError::Malformed)?;
+    if let Some(content_type) = headers.iter().find(|h| h.is("Content-Type")) {
+        if split_params(&content_type.value()).0 == "multipart/signed" {
+            return Err(MimeError::AlreadySigned);
+        }
+    }
+
+    let signed_part = signed_part(&headers, body);
+    let keypoints = other_keys
+        .iter()
+        .map(|k| k.keypoint_bytes())
+        .collect::<Vec<_>>();
+    let signature = RingSignature::sign_keypoints_with_rng(
+        &[SIGNED_DOMAIN, &signed_part].concat(),
+        my_key,
+        &keypoints,
+        scheme,
+        rng,
+    )
+    .expect("Public keys always have valid keypoints");
+    let mut ring = other_keys.to_vec();
+    ring.push(my_key.public());
+    ring.sort();
+    ring.dedup();
+    let mut encoded = vec![SIGNATURE_ENCODING_VERSION];
+    (ring, signature.to_bytes())
+        .serialize(&mut encoded)
+        .expect("Failed to serialize into unbounded buffer");
+
+    let mut boundary_bytes = [0; 16];
+    rng.fill_bytes(&mut boundary_bytes);
+    let boundary = format!("zebra-{}", hex::encode(boundary_bytes));
+
+    let mut out = vec![];
+    for header in headers.iter() {
+        if !is_content_header(header) && !header.is("MIME-Version") {
+            out.extend_from_slice(&header.raw);
+        }
+    }
+    out.extend_from_slice(
+        format!(
+            "MIME-Version: 1.0\r\n\
+             Content-Type: multipart/signed; protocol=\"{SIGNATURE_TYPE}\";\r\n \
+             micalg=\"zebra\"; boundary=\"{boundary}\"\r\n\
+             \r\n\
+             This is a Zebra-signed message in MIME format.\r\n\
+             --{boundary}\r\n"
+        )
+        .as_bytes(),
+    );
+    out.extend_from_slice(&signed_part);
+    out.extend_from_slice(
+        format!(
+            "\r\n--{boundary}\r\n\
+             Content-Type: {SIGNATURE_TYPE}; name=\"signature.zebra\"\r\n\
+             Content-Description: Zebra ring signature\r\n\
+             Content-Disposition: attachment; filename=\"signature.zebra\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\
+             \r\n"
+        )
+        .as_bytes(),
+    );
+    out.extend_from_slice(&base64_lines(&encoded));
+    out.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    Ok(out)
+}
+
+/// Verify a `multipart/signed` email message produced by `sign` (possibly after it has been
+/// sent, and had its line endings and header fields changed).
+pub fn verify(message: &[u8]) -> Result<Verification, MimeError> {
+    let message = canonicalize(message);
+    let (headers, body) = split_entity(&message).ok_or(MimeError::Malformed)?;
+    let content_type = headers
+        .iter()
+        .find(|h| h.is("Content-Type"))
+        .ok_or(MimeError::NotSigned)?;
+    let (kind, params) = split_params(&content_type.value());
+    if kind != "multipart/signed"
+        || !param(&params, "protocol").map_or(false, |p| p.eq_ignore_ascii_case(SIGNATURE_TYPE))
+    {
+        return Err(MimeError::NotSigned);
+    }
+    let boundary = param(&params, "boundary").ok_or(MimeError::Malformed)?;
+    let (signed_part, signature_part) = match split_multipart(body, boundary).as_deref() {
+        Some(&[signed_part, signature_part]) => (signed_part, signature_part),
+        _ => return Err(MimeError::Malformed),
+    };
+
+    let (signature_headers, signature_body) =
+        split_entity(signature_part).ok_or(MimeError::Malformed)?;
+    let is_base64 = signature_headers
+        .iter()
+        .find(|h| h.is("Content-Transfer-Encoding"))
+        .map_or(false, |h| h.value().eq_ignore_ascii_case("base64"));
+    if !is_base64 {
+        return Err(MimeError::Malformed);
+    }
+    let encoded = base64::engine::general_purpose::STANDARD
+        .decode(
+            signature_body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|_| MimeError::Malformed)?;
+    let (ring, signature) = match encoded.split_first() {
+        Some((&SIGNATURE_ENCODING_VERSION, rest)) => {
+            <(Vec<PublicKey>, Vec<u8>)>::try_from_slice(rest).map_err(|_| MimeError::Malformed)?
+        }
+        _ => return Err(MimeError::Malformed),
+    };
+    let signature = RingSignature::from_bytes(&signature).ok_or(MimeError::Malformed)?;
+    if !signature.verify_with_keys(&[SIGNED_DOMAIN, signed_part].concat(), &ring) {
+        return Err(MimeError::BadSignature);
+    }
+
+    let from = |headers: &[Header]| {
+        headers
+            .iter()
+            .find(|h| h.is("From"))
+            .and_then(|h| address(&h.value()))
+    };
+    let (part_headers, _) = split_entity(signed_part).ok_or(MimeError::Malformed)?;
+    Ok(Verification {
+        ring,
+        from: from(&headers),
+        signed_from: from(&part_headers),
+        signed_part: signed_part.to_vec(),
+    })
+}
+
+fn is_content_header(header: &Header) -> bool {
+    header.name.len() > 8 && header.name[..8].eq_ignore_ascii_case("Content-")
+}
+
+/// The first part of the signed message: the message's From and `Content-*` fields, and its body.
+/// The body is base64-encoded if it might not make it through transport unchanged.
+fn signed_part(headers: &[Header], body: &[u8]) -> Vec<u8> {
+    let mut part = vec![];
+    for header in headers.iter() {
+        if header.is("From") {
+            part.extend_from_slice(&header.raw);
+        }
+    }
+
+    let encoding = headers
+        .iter()
+        .find(|h| h.is("Content-Transfer-Encoding"))
+        .map(|h| h.value().to_ascii_lowercase());
+    let is_multipart = headers
+        .iter()
+        .find(|h| h.is("Content-Type"))
+        .map_or(false, |h| {
+            split_params(&h.value()).0.starts_with("multipart/")
+        });
+    // Multipart bodies can't be encoded (only their parts can), so we leave them alone, and hope
+    // that their parts are already safe to send.
+    let reencode = !is_multipart
+        && !matches!(encoding.as_deref(), Some("base64" | "quoted-printable"))
+        && !is_transport_safe(body);
+
+    for header in headers.iter() {
+        if is_content_header(header) && !(reencode && header.is("Content-Transfer-Encoding")) {
+            part.extend_from_slice(&header.raw);
+        }
+    }
+    if reencode {
+        part.extend_from_slice(b"Content-Transfer-Encoding: base64\r\n\r\n");
+        part.extend_from_slice(&base64_lines(body));
+    } else {
+        part.extend_from_slice(b"\r\n");
+        part.extend_from_slice(body);
+    }
+    part
+}
+
+/// Whether a body is likely to arrive exactly as it was sent: 7-bit ASCII, in short lines that
+/// don't end in whitespace (which some servers strip) or start with "From " (which some servers
+/// escape). See RFC 3156, section 3.
+fn is_transport_safe(body: &[u8]) -> bool {
+    body.split(|&b| b == b'\n').all(|line| {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line.len() <= BASE64_LINE_LEN
+            && line
+                .iter()
+                .all(|&b| (b' '..=b'~').contains(&b) || b == b'\t')
+            && !line.ends_with(b" ")
+            && !line.ends_with(b"\t")
+            && !line.starts_with(b"From ")
+    })
+}
+
+fn base64_lines(bytes: &[u8]) -> Vec<u8> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    let mut out = vec![];
+    for line in encoded.as_bytes().chunks(BASE64_LINE_LEN) {
+        out.extend_from_slice(line);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use zebra_crypto::Identity;
+
+    use super::*;
+
+    const SAMPLES: [(&str, &[u8]); 3] = [
+        ("plain", include_bytes!("../samples/plain.eml")),
+        ("utf8", include_bytes!("../samples/utf8.eml")),
+        ("multipart", include_bytes!("../samples/multipart.eml")),
+    ];
+
+    fn keys(rng: &mut StdRng) -> Vec<PrivateKey> {
+        [
+            ("Alice Example", "alice@example.com"),
+            ("Bob Example", "bob@example.com"),
+            ("Zoë Exemple", "zoe@example.org"),
+        ]
+        .iter()
+        .map(|(name, email)| PrivateKey::new_with_rng(Identity::new(name, email).unwrap(), rng))
+        .collect()
+    }
+
+    fn sign_sample(sample: &[u8], signer: usize, rng: &mut StdRng) -> Vec<u8> {
+        let keys = keys(rng);
+        let others = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        sign_with_rng(sample, &keys[signer], &others, RingScheme::Sag, rng).unwrap()
+    }
+
+    #[test]
+    fn sign_and_verify_work() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (i, (name, sample)) in SAMPLES.iter().enumerate() {
+            let signed = sign_sample(sample, i, &mut rng);
+            assert!(
+                signed
+                    .split(|&b| b == b'\n')
+                    .all(|l| l.len() <= 78 && l.is_ascii()),
+                "{name}: signed message isn't transport-safe"
+            );
+
+            let verification = verify(&signed).unwrap();
+            assert_eq!(verification.ring().len(), 3, "{name}");
+            assert!(verification.from_in_ring(), "{name}");
+
+            // Mail servers add header fields, and messages are often stored with LF line endings.
+            let mut delivered = b"Received: from mx.example.com by mail.example.net;\n Mon, 12 Oct 2026 09:31:00 +0000\n".to_vec();
+            delivered.extend(signed.iter().filter(|&&b| b != b'\r'));
+            let verification = verify(&delivered).unwrap();
+            assert!(verification.from_in_ring(), "{name}");
+            assert_eq!(
+                verification.signed_part(),
+                verify(&signed).unwrap().signed_part()
+            );
+
+            assert_eq!(
+                sign_with_rng(&signed, &keys(&mut rng)[0], &[], RingScheme::Sag, &mut rng).err(),
+                Some(MimeError::AlreadySigned)
+            );
+            assert_eq!(verify(sample).err(), Some(MimeError::NotSigned), "{name}");
+        }
+
+        // Messages can also be signed with one-of-many proofs.
+        let keys = keys(&mut rng);
+        let others = keys.iter().map(|k| k.public()).collect::<Vec<_>>();
+        let signed = sign_with_rng(
+            SAMPLES[0].1,
+            &keys[0],
+            &others,
+            RingScheme::OneOfMany,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify(&signed).unwrap().from_in_ring());
+    }
+
+    #[test]
+    fn internationalized_domains_match_the_ring() {
+        let mut rng = StdRng::seed_from_u64(2);
+        // Keys are made with the ASCII form of their addresses, as the app and CLI do.
+        let email = idn::email_to_ascii("zoe@exémple.org").unwrap();
+        assert_eq!(email, "zoe@xn--exmple-cva.org");
+        let zoe = PrivateKey::new_with_rng(Identity::new("Zoë Exemple", &email).unwrap(), &mut rng);
+        let message = "From: Zoë Exemple <zoe@exémple.org>\r\n\
+                       Subject: Bonjour\r\n\
+                       \r\n\
+                       Bonjour à tous.\r\n";
+        let signed = sign_with_rng(
+            message.as_bytes(),
+            &zoe,
+            &[zoe.public()],
+            RingScheme::Sag,
+            &mut rng,
+        )
+        .unwrap();
+        let verification = verify(&signed).unwrap();
+        assert_eq!(verification.from(), Some("zoe@exémple.org"));
+        assert!(verification.from_in_ring());
+
+        // The same address, in ASCII form, also matches.
+        let signed = String::from_utf8(signed).unwrap();
+        let ascii = signed.replacen(
+            "From: Zoë Exemple <zoe@exémple.org>",
+            "From: zoe@xn--exmple-cva.org",
+            1,
+        );
+        assert_ne!(ascii, signed);
+        assert!(verify(ascii.as_bytes()).unwrap().from_in_ring());
+    }
+
+    #[test]
+    fn signed_sample_verifies() {
+        // Signed with an earlier version, to check that the format hasn't changed.
+        let verification = verify(include_bytes!("../samples/signed.eml")).unwrap();
+        assert!(verification.from_in_ring());
+        assert_eq!(verification.from(), Some("alice@example.com"));
+        let names = verification
+            .ring()
+            .iter()
+            .map(|k| k.holder().name())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"Bob Example".to_string()));
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let signed = String::from_utf8(sign_sample(SAMPLES[0].1, 0, &mut rng)).unwrap();
+
+        // "Hi all," becomes "Hi alm,".
+        let tampered = signed.replacen("SGkgYWxsLA0K", "SGkgYWxtLA0K", 1);
+        assert_ne!(tampered, signed);
+        assert_eq!(
+            verify(tampered.as_bytes()).err(),
+            Some(MimeError::BadSignature)
+        );
+
+        // Changing the From field doesn't invalidate the signature, but it's no longer from a ring
+        // member (or what was signed).
+        let forged = signed.replacen(
+            "From: Alice Example <alice@example.com>",
+            "From: Mallory <mallory@example.com>",
+            1,
+        );
+        assert_ne!(forged, signed);
+        let verification = verify(forged.as_bytes()).unwrap();
+        assert_eq!(verification.from(), Some("mallory@example.com"));
+        assert!(!verification.from_in_ring());
+        let impersonated = signed.replacen(
+            "From: Alice Example <alice@example.com>",
+            "From: Bob Example <bob@example.com>",
+            1,
+        );
+        assert!(!verify(impersonated.as_bytes()).unwrap().from_in_ring());
+
+        // Dropping the signature part, or mangling it, is also caught.
+        let boundary = signed
+            .split("boundary=\"")
+            .nth(1)
+            .unwrap()
+            .split('"')
+            .next()
+            .unwrap();
+        let first_part_only = format!(
+            "{}--{boundary}--\r\n",
+            &signed[..signed.rfind(&format!("--{boundary}\r\n")).unwrap()]
+        );
+        assert_eq!(
+            verify(first_part_only.as_bytes()).err(),
+            Some(MimeError::Malformed)
+        );
+        let mangled = signed.replacen(
+            "filename=\"signature.zebra\"\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+            "filename=\"signature.zebra\"\r\nContent-Transfer-Encoding: base64\r\n\r\n!",
+            1,
+        );
+        assert_ne!(mangled, signed);
+        assert_eq!(verify(mangled.as_bytes()).err(), Some(MimeError::Malformed));
+    }
+}
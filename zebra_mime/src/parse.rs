@@ -0,0 +1,178 @@
+// Just enough RFC 5322 and MIME (RFC 2045-2046) parsing to sign and verify messages. Everything
+// here works on messages whose line endings have already been canonicalized to CRLF.
+
+/// A header field, as it appeared in the message: `raw` is the whole field, including its name,
+/// any folding, and the final CRLF.
+pub(crate) struct Header {
+    pub(crate) name: String,
+    pub(crate) raw: Vec<u8>,
+}
+
+impl Header {
+    /// The field's value, unfolded and trimmed. (Non-UTF-8 bytes are replaced, since the parts of
+    /// values that we care about are ASCII.)
+    pub(crate) fn value(&self) -> String {
+        let value = &self.raw[self.name.len() + 1..];
+        String::from_utf8_lossy(value)
+            .replace("\r\n", "")
+            .trim()
+            .to_string()
+    }
+
+    pub(crate) fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+/// Convert every line ending (CRLF, or a bare LF or CR) to CRLF, as RFC 5322 and multipart/signed
+/// require. Messages saved to disk, or passed around by local mail tools, often use bare LFs.
+pub(crate) fn canonicalize(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len() + message.len() / 32);
+    let mut bytes = message.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'\r' => {
+                bytes.next_if_eq(&&b'\n');
+                out.extend_from_slice(b"\r\n");
+            }
+            b'\n' => out.extend_from_slice(b"\r\n"),
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+/// Split an entity (a message, or a body part) into its header fields and its body. Returns None
+/// if the header section is malformed.
+pub(crate) fn split_entity(entity: &[u8]) -> Option<(Vec<Header>, &[u8])> {
+    let mut headers: Vec<Header> = vec![];
+    let mut rest = entity;
+    loop {
+        let line_len = find(rest, b"\r\n").map_or(rest.len(), |i| i + 2);
+        let (line, after) = rest.split_at(line_len);
+        if line == b"\r\n" || line.is_empty() {
+            return Some((headers, after));
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            // A folded continuation of the previous field.
+            headers.last_mut()?.raw.extend_from_slice(line);
+        } else {
+            let colon = line.iter().position(|&b| b == b':')?;
+            let name = std::str::from_utf8(&line[..colon]).ok()?;
+            if name.is_empty() || !name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+                return None;
+            }
+            headers.push(Header {
+                name: name.to_string(),
+                raw: line.to_vec(),
+            });
+        }
+        rest = after;
+    }
+}
+
+/// Split a header value like `multipart/signed; boundary="abc"; protocol=x` into its lowercased
+/// type and its parameters (with lowercased names, and unquoted values). RFC 2231 extended
+/// parameters and comments aren't supported.
+pub(crate) fn split_params(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = value.splitn(2, ';');
+    let kind = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let mut params = vec![];
+    let mut rest = parts.next().unwrap_or_default();
+    while let Some((name, after)) = rest.split_once('=') {
+        let name = name
+            .trim()
+            .trim_start_matches(';')
+            .trim()
+            .to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, after) = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            (value, &quoted[end..])
+        } else {
+            let end = after.find(';').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+        params.push((name, value));
+        rest = after.trim_start().trim_start_matches(';');
+    }
+    (kind, params)
+}
+
+pub(crate) fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// The addr-spec in a From (or similar) header value, like "a@example.com" in
+/// `"A. Person" <a@example.com>`. Only single-address values are supported.
+pub(crate) fn address(value: &str) -> Option<String> {
+    let address = match value.rfind('<') {
+        Some(start) => value[start + 1..].split('>').next()?,
+        None => value,
+    }
+    .trim();
+    if address.contains(char::is_whitespace) || !address.contains('@') {
+        return None;
+    }
+    Some(address.to_string())
+}
+
+/// The body parts of a multipart body with the given boundary (not including the preamble and
+/// epilogue), exactly as they appear between the delimiters. Returns None unless the body has a
+/// closing delimiter.
+pub(crate) fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<&'a [u8]>> {
+    // Each delimiter is a CRLF (which belongs to the delimiter, not to the preceding part), then
+    // "--" and the boundary, then either "--" (for the last one) or nothing, then optional
+    // whitespace and a CRLF. The first delimiter may be at the start of the body, without a CRLF.
+    let delimiter = format!("\r\n--{boundary}").into_bytes();
+    let mut parts = vec![];
+    let mut start: Option<usize> = None;
+    let mut search_from = 0;
+    let body_with_crlf = [b"\r\n".as_slice(), body].concat();
+    while let Some(i) = find(&body_with_crlf[search_from..], &delimiter) {
+        let at = search_from + i;
+        let after = &body_with_crlf[at + delimiter.len()..];
+        let is_close = after.starts_with(b"--");
+        let rest = if is_close { &after[2..] } else { after };
+        let padding = rest
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let rest = &rest[padding..];
+        if !is_close && !rest.starts_with(b"\r\n") {
+            // Not a delimiter, just a line that starts with one.
+            search_from = at + 1;
+            continue;
+        }
+        if let Some(s) = start {
+            // Offsets in body_with_crlf are two more than in body.
+            parts.push(&body[s - 2..at - 2]);
+        }
+        if is_close {
+            return Some(parts);
+        }
+        let next = at + delimiter.len() + padding + 2;
+        start = Some(next);
+        search_from = next;
+    }
+    None
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
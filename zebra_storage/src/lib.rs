@@ -1,5 +1,7 @@
 mod dbfile_utils;
 mod keyring_utils;
+mod ring_log;
+mod suggestions;
 
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{File, OpenOptions};
@@ -11,12 +13,17 @@ use fs2::FileExt;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use boringascii::BoringAscii;
-use zebra_crypto::{Identity, PrivateKey, PublicKey, SignedMessage};
+use zebra_crypto::{
+    AuthorshipClaim, DisavowalProof, Identity, PrivateKey, PublicKey, SignedMessage,
+    SigningOptions, ThresholdProposal, ThresholdShare,
+};
 
 use crate::dbfile_utils::lockfile_path;
 use crate::keyring_utils::get_or_create_db_key;
+use crate::ring_log::{analyze_ring, RingLogEntry};
 
 pub use crate::dbfile_utils::default_db_path;
+pub use crate::ring_log::{RingAnalysis, RingWarning};
 
 // Databases will be "human-sized", i.e. almost always have less than 100 private keys and less
 // than 10,000 public keys. A typical public key has <80 bytes for the identity (depending on the
@@ -84,6 +91,30 @@ struct DatabaseContentsV0 {
     public_keys: BTreeMap<PublicKey, VerificationInfo>,
 }
 
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct DatabaseContentsV1 {
+    private_keys: BTreeMap<PublicKey, PrivateKey>,
+    public_keys: BTreeMap<PublicKey, VerificationInfo>,
+    // None if the user hasn't opted in to keeping a log of the rings they sign with. This lives
+    // inside the encrypted database, and is never part of the VisibleDatabaseContents, since it
+    // reveals exactly the information that ring signatures are supposed to hide.
+    ring_log: Option<Vec<RingLogEntry>>,
+}
+
+impl From<DatabaseContentsV0> for DatabaseContentsV1 {
+    fn from(v0: DatabaseContentsV0) -> Self {
+        let DatabaseContentsV0 {
+            private_keys,
+            public_keys,
+        } = v0;
+        Self {
+            private_keys,
+            public_keys,
+            ring_log: None,
+        }
+    }
+}
+
 // We use an enum here, instead of just storing the struct directly, because this will let us
 // migrate the format in the future if desired. This is also why we use an explicit discriminant for
 // this enum.
@@ -92,14 +123,12 @@ struct DatabaseContentsV0 {
 #[repr(u8)]
 enum ZebraDatabaseContents {
     V0(DatabaseContentsV0) = 0,
+    V1(DatabaseContentsV1) = 1,
 }
 
 impl Default for ZebraDatabaseContents {
     fn default() -> Self {
-        Self::V0(DatabaseContentsV0 {
-            private_keys: BTreeMap::new(),
-            public_keys: BTreeMap::new(),
-        })
+        Self::V1(DatabaseContentsV1::default())
     }
 }
 
@@ -107,21 +136,31 @@ impl Default for ZebraDatabaseContents {
 pub struct VisibleDatabaseContents {
     pub my_public_keys: BTreeSet<PublicKey>,
     pub their_public_keys: BTreeMap<PublicKey, VerificationInfo>,
+    pub ring_log_enabled: bool,
 }
 
-impl DatabaseContentsV0 {
+impl DatabaseContentsV1 {
     fn get_visible(&self) -> VisibleDatabaseContents {
-        let DatabaseContentsV0 {
+        let DatabaseContentsV1 {
             private_keys,
             public_keys,
+            ring_log,
         } = self;
         VisibleDatabaseContents {
             my_public_keys: private_keys.iter().map(|k| k.0.clone()).collect(),
             their_public_keys: public_keys.clone(),
+            ring_log_enabled: ring_log.is_some(),
         }
     }
 }
 
+/// The ring that `SignedMessage::sign` will produce: the other keys, plus the signer's own.
+fn full_ring(my_key: &PublicKey, other_keys: &[PublicKey]) -> BTreeSet<PublicKey> {
+    let mut ring = other_keys.iter().cloned().collect::<BTreeSet<_>>();
+    ring.insert(my_key.clone());
+    ring
+}
+
 impl Database {
     pub fn new<P: AsRef<Path> + std::fmt::Debug>(path: P) -> std::io::Result<Self> {
         if let Some(p) = path.as_ref().parent() {
@@ -147,7 +186,7 @@ impl Database {
 
     fn get_contents<P: AsRef<Path> + std::fmt::Debug>(
         path: &P,
-    ) -> std::io::Result<(DatabaseContentsV0, SecretString)> {
+    ) -> std::io::Result<(DatabaseContentsV1, SecretString)> {
         let pw = get_or_create_db_key()?;
 
         let file = OpenOptions::new()
@@ -157,7 +196,7 @@ impl Database {
             .open(path)?;
 
         if file.metadata()?.len() == 0 {
-            return Ok((DatabaseContentsV0::default(), pw));
+            return Ok((DatabaseContentsV1::default(), pw));
         }
 
         let mut reader = match age::Decryptor::new(&file) {
@@ -189,18 +228,20 @@ impl Database {
         // We've already authenticated this file, since age is AEAD when using a passphrase. So we
         // can be relatively sure that it's not crafted to DoS us or anything.
         reader.read_to_end(&mut bytes)?;
-        let ZebraDatabaseContents::V0(res) =
-            BorshDeserialize::deserialize(&mut bytes.as_ref())?;
+        let res = match BorshDeserialize::deserialize(&mut bytes.as_ref())? {
+            ZebraDatabaseContents::V0(v0) => v0.into(),
+            ZebraDatabaseContents::V1(v1) => v1,
+        };
         Ok((res, pw))
     }
 
-    fn write_contents(&mut self, db: DatabaseContentsV0, pw: SecretString) -> std::io::Result<()> {
+    fn write_contents(&mut self, db: DatabaseContentsV1, pw: SecretString) -> std::io::Result<()> {
         use std::io::Write;
 
         let result_vis = db.get_visible();
 
         let mut buffer = vec![];
-        ZebraDatabaseContents::V0(db).serialize(&mut buffer)?;
+        ZebraDatabaseContents::V1(db).serialize(&mut buffer)?;
 
         let mut tmpfile = tempfile::NamedTempFile::new()?;
         let encryptor = age::Encryptor::with_user_passphrase(pw);
@@ -223,13 +264,18 @@ impl Database {
         Ok(())
     }
 
+    /// Sign a message with the given options (see `SignedMessage::sign_with_options`). If
+    /// the ring log is enabled, the ring (but not the message) is recorded under the given topic,
+    /// so that later signatures on the same topic can be checked with `analyze_ring`.
     pub fn sign(
-        &self,
+        &mut self,
         message: &str,
+        options: &SigningOptions,
+        topic: &str,
         my_key_index: &PublicKey,
         other_keys: &[PublicKey],
     ) -> std::io::Result<SignedMessage> {
-        let (contents, _) = Self::get_contents(&self.db_path)?;
+        let (mut contents, pw) = Self::get_contents(&self.db_path)?;
         let my_key = contents
             .private_keys
             .get(my_key_index)
@@ -238,7 +284,109 @@ impl Database {
                 "Requested nonexistant key",
             ))?
             .clone();
-        Ok(SignedMessage::sign(message, &my_key, other_keys))
+        let signed =
+            SignedMessage::sign_with_options(message, &my_key, other_keys, options).ok_or(
+                std::io::Error::new(std::io::ErrorKind::Other, "Invalid signing options"),
+            )?;
+        if let Some(ref mut log) = contents.ring_log {
+            log.push(RingLogEntry::now(
+                topic,
+                my_key_index,
+                full_ring(my_key_index, other_keys),
+            ));
+            self.write_contents(contents, pw)?;
+        }
+        Ok(signed)
+    }
+
+    /// Check how much signing with the given ring would reveal about the signer, taking into
+    /// account the earlier rings used for the same topic (if the ring log is enabled).
+    pub fn analyze_ring(
+        &self,
+        topic: &str,
+        my_key_index: &PublicKey,
+        other_keys: &[PublicKey],
+    ) -> std::io::Result<RingAnalysis> {
+        let (contents, _) = Self::get_contents(&self.db_path)?;
+        let my_keys = contents
+            .private_keys
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        Ok(analyze_ring(
+            contents.ring_log.as_deref().unwrap_or_default(),
+            topic,
+            &my_keys,
+            &full_ring(my_key_index, other_keys),
+        ))
+    }
+
+    /// Prove that one of our keys signed the given claimable message. Fails if the message isn't
+    /// claimable, or wasn't signed with any of our keys.
+    pub fn claim_authorship(&self, signed: &SignedMessage) -> std::io::Result<AuthorshipClaim> {
+        let (contents, _) = Self::get_contents(&self.db_path)?;
+        signed
+            .ring()
+            .filter_map(|k| contents.private_keys.get(k))
+            .find_map(|my_key| AuthorshipClaim::new(signed, my_key))
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "This message wasn't signed with a claimable signature from any of your keys",
+            ))
+    }
+
+    /// Prove that the given key of ours didn't sign the given disavowable message. Fails if the
+    /// message isn't disavowable, if the key isn't in its ring, or if it's the key that signed it.
+    pub fn disavow(
+        &self,
+        signed: &SignedMessage,
+        my_key_index: &PublicKey,
+    ) -> std::io::Result<DisavowalProof> {
+        let (contents, _) = Self::get_contents(&self.db_path)?;
+        let my_key = contents
+            .private_keys
+            .get(my_key_index)
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Requested nonexistant key",
+            ))?;
+        DisavowalProof::new(signed, my_key).ok_or(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "This key can't disavow this message",
+        ))
+    }
+
+    /// Sign a threshold signing proposal with the given key of ours. Note that each key counts
+    /// as a separate signer, so signing with several of our own keys will make it look like
+    /// several people signed.
+    pub fn sign_threshold_proposal(
+        &self,
+        proposal: &ThresholdProposal,
+        my_key_index: &PublicKey,
+    ) -> std::io::Result<ThresholdShare> {
+        let (contents, _) = Self::get_contents(&self.db_path)?;
+        let my_key = contents
+            .private_keys
+            .get(my_key_index)
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Requested nonexistant key",
+            ))?;
+        proposal.sign(my_key).ok_or(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "This key isn't in the proposal's ring",
+        ))
+    }
+
+    /// Start or stop keeping a log of the rings used for signing. Disabling the log deletes it.
+    pub fn set_ring_log_enabled(&mut self, enabled: bool) -> std::io::Result<()> {
+        let (mut contents, pw) = Self::get_contents(&self.db_path)?;
+        if enabled {
+            contents.ring_log.get_or_insert_with(Vec::new);
+        } else {
+            contents.ring_log = None;
+        }
+        self.write_contents(contents, pw)
     }
 
     pub fn set_verified(&mut self, public_key: &PublicKey) -> std::io::Result<()> {
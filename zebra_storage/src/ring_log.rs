@@ -0,0 +1,252 @@
+use std::collections::BTreeSet;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use zebra_crypto::PublicKey;
+
+// Rings smaller than this get a warning. This is a judgment call: with four other plausible
+// signers, an observer who can rule out even a couple of them (e.g. because they were on vacation)
+// is left with very few candidates.
+const SMALL_RING_SIZE: usize = 5;
+
+/// A record of a ring that the user signed with. We deliberately never store the message itself:
+/// the point of this log is only to notice when a series of signatures, taken together, would
+/// narrow down who the signer is.
+///
+/// If someone signs several messages about the same topic, an observer will tend to assume that
+/// the same person wrote all of them. The signer must then be in *every* one of those rings, so
+/// the observer can intersect them. Each new ring that leaves out some of the previous members
+/// shrinks that intersection.
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Zeroize, ZeroizeOnDrop)]
+pub struct RingLogEntry {
+    topic: String,
+    signer: PublicKey,
+    // Sorted, without duplicates. This is a Vec rather than a BTreeSet so that it can be zeroized.
+    ring: Vec<PublicKey>,
+    // Unix timestamp (in UTC) of the signature.
+    signed_date: i64,
+}
+
+impl RingLogEntry {
+    pub(crate) fn now(topic: &str, signer: &PublicKey, ring: BTreeSet<PublicKey>) -> Self {
+        Self {
+            topic: normalize_topic(topic),
+            signer: signer.clone(),
+            ring: ring.into_iter().collect(),
+            signed_date: time::OffsetDateTime::now_utc().unix_timestamp(),
+        }
+    }
+}
+
+// Topics are free text typed by the user, so we don't want "Board Vote" and " board vote" to be
+// treated as unrelated.
+fn normalize_topic(topic: &str) -> String {
+    topic.trim().to_lowercase()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RingWarning {
+    /// The ring contains nobody but the signer (possibly under several keys).
+    OnlySigner,
+    /// The ring has fewer than `SMALL_RING_SIZE` distinct members.
+    SmallRing { size: usize },
+    /// Combined with earlier signatures on the same topic, this ring would shrink the set of
+    /// people who could have signed all of them.
+    IntersectionShrinks { before: usize, after: usize },
+    /// Combined with earlier signatures on the same topic, this ring would leave the signer as the
+    /// only person who could have signed all of them.
+    SinglesOut,
+}
+
+impl std::fmt::Display for RingWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RingWarning::OnlySigner => write!(
+                f,
+                "The ring contains only your own keys, so anyone can tell that you signed it."
+            ),
+            RingWarning::SmallRing { size } => write!(
+                f,
+                "The ring has only {size} possible signers. Ruling out just a few of them would identify you."
+            ),
+            RingWarning::IntersectionShrinks { before, after } => write!(
+                f,
+                "You have signed on this topic before. Anyone who assumes the same person signed every message would narrow the possible signers from {before} to {after}."
+            ),
+            RingWarning::SinglesOut => write!(
+                f,
+                "You have signed on this topic before. Anyone who assumes the same person signed every message could tell that it was you."
+            ),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RingAnalysis {
+    /// The number of distinct possible signers in the new ring. All of the user's own keys count
+    /// as a single signer, since they don't make it any less likely that the user signed.
+    pub ring_size: usize,
+    /// How many earlier signatures on the same topic were found in the log.
+    pub previous_signatures: usize,
+    /// The number of possible signers common to all earlier rings on this topic, if there were
+    /// any.
+    pub anonymity_set_before: Option<usize>,
+    /// The number of possible signers common to all rings on this topic, including the new one.
+    pub anonymity_set_after: usize,
+    pub warnings: Vec<RingWarning>,
+}
+
+// For the purposes of anonymity, every one of the user's keys is the same person.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Member<'a> {
+    Me,
+    Other(&'a PublicKey),
+}
+
+fn members<'a>(
+    ring: impl IntoIterator<Item = &'a PublicKey>,
+    my_keys: &BTreeSet<PublicKey>,
+) -> BTreeSet<Member<'a>> {
+    ring.into_iter()
+        .map(|k| {
+            if my_keys.contains(k) {
+                Member::Me
+            } else {
+                Member::Other(k)
+            }
+        })
+        .collect()
+}
+
+/// Work out how much signing with `ring` would reveal, given the earlier signatures in `log`. The
+/// ring must include the signer's own key.
+pub(crate) fn analyze_ring(
+    log: &[RingLogEntry],
+    topic: &str,
+    my_keys: &BTreeSet<PublicKey>,
+    ring: &BTreeSet<PublicKey>,
+) -> RingAnalysis {
+    let topic = normalize_topic(topic);
+    let new_members = members(ring, my_keys);
+
+    let previous = log
+        .iter()
+        .filter(|entry| entry.topic == topic)
+        .map(|entry| members(&entry.ring, my_keys))
+        .collect::<Vec<_>>();
+    let before = previous
+        .iter()
+        .cloned()
+        .reduce(|acc, m| acc.intersection(&m).cloned().collect());
+    let after = match before {
+        Some(ref b) => b
+            .intersection(&new_members)
+            .cloned()
+            .collect::<BTreeSet<_>>(),
+        None => new_members.clone(),
+    };
+
+    let mut warnings = vec![];
+    if new_members.len() <= 1 {
+        warnings.push(RingWarning::OnlySigner);
+    } else if new_members.len() < SMALL_RING_SIZE {
+        warnings.push(RingWarning::SmallRing {
+            size: new_members.len(),
+        });
+    }
+    if let Some(ref before) = before {
+        if after.len() == 1 && after.contains(&Member::Me) && new_members.len() > 1 {
+            warnings.push(RingWarning::SinglesOut);
+        } else if after.contains(&Member::Me) && after.len() < before.len() {
+            warnings.push(RingWarning::IntersectionShrinks {
+                before: before.len(),
+                after: after.len(),
+            });
+        }
+    }
+
+    RingAnalysis {
+        ring_size: new_members.len(),
+        previous_signatures: previous.len(),
+        anonymity_set_before: before.map(|b| b.len()),
+        anonymity_set_after: after.len(),
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_crypto::{Identity, PrivateKey};
+
+    fn key(name: &str) -> PublicKey {
+        let email = format!("{}@example.com", name.to_lowercase().replace(' ', "."));
+        PrivateKey::new(Identity::new(name, &email).unwrap()).public()
+    }
+
+    fn ring<'a>(keys: impl IntoIterator<Item = &'a PublicKey>) -> BTreeSet<PublicKey> {
+        keys.into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn lone_and_small_rings_are_flagged() {
+        let me = key("Me");
+        let my_other_key = key("Me Again");
+        let my_keys = ring([&me, &my_other_key]);
+        let others = ["A", "B", "C", "D", "E"].map(key);
+
+        let analysis = analyze_ring(&[], "topic", &my_keys, &ring([&me, &my_other_key]));
+        assert_eq!(analysis.ring_size, 1);
+        assert_eq!(analysis.previous_signatures, 0);
+        assert_eq!(analysis.anonymity_set_before, None);
+        assert_eq!(analysis.warnings, vec![RingWarning::OnlySigner]);
+
+        let analysis = analyze_ring(&[], "topic", &my_keys, &ring([&me, &others[0], &others[1]]));
+        assert_eq!(analysis.warnings, vec![RingWarning::SmallRing { size: 3 }]);
+
+        let analysis = analyze_ring(&[], "topic", &my_keys, &ring(others.iter().chain([&me])));
+        assert_eq!(analysis.ring_size, 6);
+        assert_eq!(analysis.anonymity_set_after, 6);
+        assert!(analysis.warnings.is_empty());
+    }
+
+    #[test]
+    fn earlier_rings_on_the_same_topic_are_intersected() {
+        let me = key("Me");
+        let my_keys = ring([&me]);
+        let others = ["A", "B", "C", "D", "E", "F", "G", "H", "I", "J"].map(key);
+        let first = ring(others[..5].iter().chain([&me]));
+        let log = vec![RingLogEntry::now("Board Vote", &me, first.clone())];
+
+        // Signing with the same ring again doesn't narrow anything down.
+        let analysis = analyze_ring(&log, " board vote", &my_keys, &first);
+        assert_eq!(analysis.previous_signatures, 1);
+        assert_eq!(analysis.anonymity_set_before, Some(6));
+        assert_eq!(analysis.anonymity_set_after, 6);
+        assert!(analysis.warnings.is_empty());
+
+        let overlapping = ring(others[2..7].iter().chain([&me]));
+        let analysis = analyze_ring(&log, "board vote", &my_keys, &overlapping);
+        assert_eq!(analysis.anonymity_set_after, 4);
+        assert_eq!(
+            analysis.warnings,
+            vec![RingWarning::IntersectionShrinks {
+                before: 6,
+                after: 4
+            }]
+        );
+
+        let disjoint = ring(others[5..].iter().chain([&me]));
+        let analysis = analyze_ring(&log, "board vote", &my_keys, &disjoint);
+        assert_eq!(analysis.anonymity_set_after, 1);
+        assert_eq!(analysis.warnings, vec![RingWarning::SinglesOut]);
+
+        // Other topics are unrelated.
+        let analysis = analyze_ring(&log, "lunch order", &my_keys, &disjoint);
+        assert_eq!(analysis.previous_signatures, 0);
+        assert_eq!(analysis.anonymity_set_before, None);
+        assert!(analysis.warnings.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use zebra_crypto::PublicKey;
+
+use crate::VisibleDatabaseContents;
+
+fn email_domain(key: &PublicKey) -> Option<String> {
+    key.holder()
+        .email()
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+}
+
+impl VisibleDatabaseContents {
+    /// Suggest additional ring members, so that a ring containing `my_key` and the already
+    /// `selected` keys reaches `target_size` members. Returns the suggestions in order of
+    /// preference, and never more than are needed.
+    ///
+    /// Suggestions favor verified keys, and keys whose email domain matches one already in the
+    /// ring (including the signer's), since those are the people an observer would find most
+    /// plausible as the signer. Keys that obviously can't be the signer are never suggested: the
+    /// user's own keys, keys claiming the same email address as the signer, and keys for
+    /// addresses that are already in the ring. Adding those would make the ring look bigger
+    /// without adding any plausible signers.
+    pub fn suggest_ring_members(
+        &self,
+        my_key: &PublicKey,
+        selected: &BTreeSet<PublicKey>,
+        target_size: usize,
+    ) -> Vec<PublicKey> {
+        let current_size = selected.iter().filter(|k| *k != my_key).count() + 1;
+        let needed = target_size.saturating_sub(current_size);
+        if needed == 0 {
+            return vec![];
+        }
+
+        let mut ring_emails = selected
+            .iter()
+            .map(|k| k.holder().email().to_lowercase())
+            .collect::<BTreeSet<_>>();
+        ring_emails.insert(my_key.holder().email().to_lowercase());
+        let my_emails = self
+            .my_public_keys
+            .iter()
+            .map(|k| k.holder().email().to_lowercase())
+            .collect::<BTreeSet<_>>();
+        let ring_domains = selected
+            .iter()
+            .chain(std::iter::once(my_key))
+            .filter_map(email_domain)
+            .collect::<BTreeSet<_>>();
+
+        // At most one candidate per email address: the best-scoring one.
+        let mut best_per_email: BTreeMap<String, (u8, &PublicKey)> = BTreeMap::new();
+        for (k, verification) in self.their_public_keys.iter() {
+            let email = k.holder().email().to_lowercase();
+            if selected.contains(k)
+                || self.my_public_keys.contains(k)
+                || ring_emails.contains(&email)
+                || my_emails.contains(&email)
+            {
+                continue;
+            }
+            let mut score = 0;
+            if verification.is_verified() {
+                score += 2;
+            }
+            if email_domain(k)
+                .map(|d| ring_domains.contains(&d))
+                .unwrap_or(false)
+            {
+                score += 1;
+            }
+            let entry = best_per_email.entry(email).or_insert((score, k));
+            if score > entry.0 {
+                *entry = (score, k);
+            }
+        }
+
+        let mut candidates = best_per_email.into_values().collect::<Vec<_>>();
+        // Sort by descending score, and then by name so that the suggestions are stable.
+        candidates.sort_by(|(score_a, a), (score_b, b)| {
+            score_b
+                .cmp(score_a)
+                .then_with(|| a.holder().name().cmp(&b.holder().name()))
+        });
+        candidates
+            .into_iter()
+            .take(needed)
+            .map(|(_, k)| k.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_crypto::{Identity, PrivateKey};
+
+    use crate::VerificationInfo;
+
+    fn key(name: &str, email: &str) -> PublicKey {
+        PrivateKey::new(Identity::new(name, email).unwrap()).public()
+    }
+
+    #[test]
+    fn suggestions_are_ranked_and_filtered() {
+        let me = key("Me", "me@acme.com");
+        let my_other_key = key("Me", "me@home.org");
+        let selected = key("Selected", "selected@elsewhere.org");
+        let verified = key("Verified", "verified@other.org");
+        let colleague = key("Colleague", "colleague@acme.com");
+        let stranger = key("Stranger", "stranger@other.org");
+        let verified_twin = key("Twin", "twin@other.org");
+        let unverified_twin = key("Twin", "twin@other.org");
+        let impostor = key("Me", "me@acme.com");
+        let home_impostor = key("Me", "ME@home.org");
+        let selected_twin = key("Selected", "selected@elsewhere.org");
+
+        let contents = VisibleDatabaseContents {
+            my_public_keys: [me.clone(), my_other_key].into_iter().collect(),
+            their_public_keys: [
+                (selected.clone(), VerificationInfo::unverified()),
+                (verified.clone(), VerificationInfo::now()),
+                (colleague.clone(), VerificationInfo::unverified()),
+                (stranger.clone(), VerificationInfo::unverified()),
+                (verified_twin.clone(), VerificationInfo::now()),
+                (unverified_twin, VerificationInfo::unverified()),
+                (impostor, VerificationInfo::now()),
+                (home_impostor, VerificationInfo::now()),
+                (selected_twin, VerificationInfo::now()),
+            ]
+            .into_iter()
+            .collect(),
+            ring_log_enabled: false,
+        };
+        let selected = [selected].into_iter().collect();
+
+        // Verified keys come before ones that merely share a domain with the ring, which come
+        // before everyone else. Each address is suggested at most once, and the signer's own
+        // addresses, and the ring's, never are.
+        let fingerprints =
+            |keys: &[PublicKey]| keys.iter().map(PublicKey::fingerprint).collect::<Vec<_>>();
+        assert_eq!(
+            fingerprints(&contents.suggest_ring_members(&me, &selected, 10)),
+            fingerprints(&[verified_twin.clone(), verified, colleague, stranger])
+        );
+        assert_eq!(
+            fingerprints(&contents.suggest_ring_members(&me, &selected, 3)),
+            fingerprints(&[verified_twin])
+        );
+        assert!(contents.suggest_ring_members(&me, &selected, 2).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+// Just enough DER (X.690) for timestamp requests and responses, CMS signed data, and X.509
+// certificates: single-byte tags, and definite, minimally-encoded lengths.
+
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const NULL: u8 = 0x05;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const UTC_TIME: u8 = 0x17;
+pub(crate) const GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const SET: u8 = 0x31;
+
+/// The tag of a constructed, context-specific field, like `[0]` in ASN.1.
+pub(crate) const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+// Object identifiers, as the contents of their DER encodings.
+
+/// 2.16.840.1.101.3.4.2.1
+pub(crate) const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// 2.16.840.1.101.3.4.2.2
+pub(crate) const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+/// 2.16.840.1.101.3.4.2.3
+pub(crate) const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+/// 1.2.840.113549.1.1.1
+pub(crate) const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+/// 1.2.840.113549.1.1.11
+pub(crate) const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+/// 1.2.840.113549.1.1.12
+pub(crate) const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+/// 1.2.840.113549.1.1.13
+pub(crate) const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+/// 1.2.840.113549.1.7.2
+pub(crate) const SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+/// 1.2.840.113549.1.9.3
+pub(crate) const CONTENT_TYPE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x03];
+/// 1.2.840.113549.1.9.4
+pub(crate) const MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+/// 1.2.840.113549.1.9.16.1.4
+pub(crate) const TST_INFO: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+/// 2.5.4.3
+pub(crate) const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// 2.5.29.37
+pub(crate) const EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+/// 1.3.6.1.5.5.7.3.8
+pub(crate) const TIME_STAMPING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+
+/// Reads a sequence of DER values, front to back. Every method returns None if the next value
+/// isn't what was asked for, or isn't valid DER.
+#[derive(Clone, Copy)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The tag of the next value, if there is one.
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    /// The next value's tag, its contents, and its whole encoding (including the tag and length).
+    pub(crate) fn read_any(&mut self) -> Option<(u8, &'a [u8], &'a [u8])> {
+        let (&tag, rest) = self.bytes.split_first()?;
+        // Multi-byte tags never come up in the structures we read.
+        if tag & 0x1f == 0x1f {
+            return None;
+        }
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = if first < 0x80 {
+            (usize::from(first), rest)
+        } else {
+            let count = usize::from(first & 0x7f);
+            if count == 0 || count > 4 || rest.len() < count || rest[0] == 0 {
+                return None;
+            }
+            let (len_bytes, rest) = rest.split_at(count);
+            let len = len_bytes
+                .iter()
+                .fold(0usize, |len, &b| (len << 8) | usize::from(b));
+            if len < 0x80 {
+                return None;
+            }
+            (len, rest)
+        };
+        if rest.len() < len {
+            return None;
+        }
+        let header_len = self.bytes.len() - rest.len();
+        let element = &self.bytes[..header_len + len];
+        self.bytes = &rest[len..];
+        Some((tag, &element[header_len..], element))
+    }
+
+    /// The contents of the next value, which must have the given tag.
+    pub(crate) fn read(&mut self, tag: u8) -> Option<&'a [u8]> {
+        self.read_element(tag).map(|(contents, _)| contents)
+    }
+
+    /// The contents and the whole encoding of the next value, which must have the given tag.
+    pub(crate) fn read_element(&mut self, tag: u8) -> Option<(&'a [u8], &'a [u8])> {
+        let mut copy = *self;
+        match copy.read_any()? {
+            (t, contents, element) if t == tag => {
+                *self = copy;
+                Some((contents, element))
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `read`, but if the next value has a different tag (or there isn't one), it's left
+    /// alone, and the result is Some(None).
+    pub(crate) fn read_optional(&mut self, tag: u8) -> Option<Option<&'a [u8]>> {
+        if self.peek_tag() == Some(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    /// A reader for the contents of the next value, which must have the given tag.
+    pub(crate) fn nested(&mut self, tag: u8) -> Option<Reader<'a>> {
+        self.read(tag).map(Reader::new)
+    }
+
+    /// A non-negative INTEGER's big-endian bytes, without any leading zeros.
+    pub(crate) fn read_unsigned(&mut self) -> Option<&'a [u8]> {
+        let contents = self.read(INTEGER)?;
+        match contents {
+            [] => None,
+            [b, ..] if b & 0x80 != 0 => None,
+            [0, b, ..] if b & 0x80 == 0 => None,
+            [0, rest @ ..] => Some(rest),
+            _ => Some(contents),
+        }
+    }
+
+    /// An AlgorithmIdentifier's OID, checking that it has no parameters (or NULL ones).
+    pub(crate) fn read_algorithm(&mut self) -> Option<&'a [u8]> {
+        let mut algorithm = self.nested(SEQUENCE)?;
+        let oid = algorithm.read(OID)?;
+        let parameters = algorithm.read_optional(NULL)?;
+        if parameters.filter(|p| !p.is_empty()).is_some() || !algorithm.is_empty() {
+            return None;
+        }
+        Some(oid)
+    }
+}
+
+/// Encode a value with the given tag and contents.
+pub(crate) fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let skip = len_bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (len_bytes.len() - skip) as u8);
+        out.extend_from_slice(&len_bytes[skip..]);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+/// Encode the concatenation of several encoded values with the given tag.
+pub(crate) fn encode_all(tag: u8, values: &[&[u8]]) -> Vec<u8> {
+    encode(tag, &values.concat())
+}
+
+/// Encode a non-negative INTEGER from its big-endian bytes.
+pub(crate) fn encode_unsigned(bytes: &[u8]) -> Vec<u8> {
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    let mut contents = bytes[skip..].to_vec();
+    if contents.first().filter(|&&b| b & 0x80 == 0).is_none() {
+        contents.insert(0, 0);
+    }
+    encode(INTEGER, &contents)
+}
+
+/// Encode an AlgorithmIdentifier with NULL parameters, as is usual for hashes and RSA.
+pub(crate) fn encode_algorithm(oid: &[u8]) -> Vec<u8> {
+    encode_all(SEQUENCE, &[&encode(OID, oid), &encode(NULL, &[])])
+}
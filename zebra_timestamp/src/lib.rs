@@ -0,0 +1,759 @@
+//! Trusted timestamps (RFC 3161) for Zebra-signed messages.
+//!
+//! A ring signature shows who might have signed a message, but not when. A timestamping authority
+//! (TSA) can vouch that a signed message existed at a particular time: `request_timestamp` sends
+//! it a hash of the message, and attaches the token that it signs in reply to the message (see
+//! `SignedMessage::set_timestamp_token`). The token then travels with the message in its ASCII
+//! format, and `verify` checks it against the authorities that the verifier trusts.
+//!
+//! The hash is SHA-512, of the message's Borsh encoding, which covers everything that the ring
+//! signature does, as well as the signature itself, but not the token. So a token can't be moved
+//! to a different message, or a different signature of the same message.
+//!
+//! Requests are sent with HTTP (not HTTPS), as most TSAs expect: the token is signed, so it doesn't
+//! matter who can see or change it along the way.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use rand::RngCore;
+use time::OffsetDateTime;
+use zebra_crypto::SignedMessage;
+
+mod der;
+mod token;
+
+pub use token::Certificate;
+use token::Token;
+
+/// How long to wait for a TSA to connect, or to send or receive anything.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The most that we'll read from a TSA. Tokens are usually a few kilobytes.
+const MAX_RESPONSE_LEN: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampError {
+    /// The TSA's URL isn't an "http://" URL.
+    UnsupportedUrl,
+    /// The TSA couldn't be reached, or didn't send a complete response.
+    Network,
+    /// The TSA refused to timestamp the message.
+    Rejected,
+    /// The TSA's response, or the message's token, isn't in the expected format.
+    Malformed,
+    /// The message doesn't have a timestamp token.
+    NotTimestamped,
+    /// The token is for a different message, or its signature is invalid.
+    BadToken,
+    /// The token is valid, but it isn't from one of the trusted authorities (or their certificates
+    /// don't allow it).
+    Untrusted,
+}
+
+impl std::fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TimestampError::UnsupportedUrl => "Timestamping authority URLs must start with http://",
+            TimestampError::Network => "Couldn't get a response from the timestamping authority",
+            TimestampError::Rejected => "The timestamping authority refused the request",
+            TimestampError::Malformed => "This timestamp isn't in the expected format",
+            TimestampError::NotTimestamped => "This message isn't timestamped",
+            TimestampError::BadToken => "This message's timestamp is invalid",
+            TimestampError::Untrusted => "This message's timestamp isn't from a trusted authority",
+        })
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+/// A successfully verified timestamp.
+pub struct Timestamp {
+    time: OffsetDateTime,
+    authority: String,
+    serial_number: Vec<u8>,
+}
+
+impl Timestamp {
+    /// When the authority says that the message existed (in UTC).
+    pub fn time(&self) -> OffsetDateTime {
+        self.time
+    }
+
+    /// The name of the authority, from its certificate.
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    /// The authority's serial number for the token (big-endian), which is unique among its tokens.
+    pub fn serial_number(&self) -> &[u8] {
+        &self.serial_number
+    }
+}
+
+/// Get a timestamp for `message` from the TSA at `tsa_url`, and return a copy of the message with
+/// the token attached (replacing any that it already had). The token is checked against the
+/// certificate that the TSA includes with it, but that certificate isn't necessarily trusted; see
+/// `verify`.
+pub fn request_timestamp(
+    message: &SignedMessage,
+    tsa_url: &str,
+) -> Result<SignedMessage, TimestampError> {
+    let mut nonce = [0; 8];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let nonce = der::encode_unsigned(&nonce);
+    let imprint = imprint(message);
+    // TimeStampReq: version 1, the message imprint, no policy, our nonce, and a request for the
+    // TSA's certificate.
+    let request = der::encode_all(
+        der::SEQUENCE,
+        &[
+            &der::encode(der::INTEGER, &[1]),
+            &der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode_algorithm(der::SHA512),
+                    &der::encode(der::OCTET_STRING, &imprint),
+                ],
+            ),
+            &nonce,
+            &der::encode(der::BOOLEAN, &[0xff]),
+        ],
+    );
+    let response = post(tsa_url, &request)?;
+
+    // TimeStampResp: the status, and then the token, if it was granted.
+    let mut outer = der::Reader::new(&response);
+    let mut response = outer
+        .nested(der::SEQUENCE)
+        .ok_or(TimestampError::Malformed)?;
+    let mut status = response
+        .nested(der::SEQUENCE)
+        .ok_or(TimestampError::Malformed)?;
+    // 0 is "granted", and 1 is "granted with modifications".
+    if !matches!(status.read(der::INTEGER), Some([0] | [1])) {
+        return Err(TimestampError::Rejected);
+    }
+    let (_, _, token_bytes) = response.read_any().ok_or(TimestampError::Malformed)?;
+    if !response.is_empty() || !outer.is_empty() {
+        return Err(TimestampError::Malformed);
+    }
+    let token = Token::parse(token_bytes).ok_or(TimestampError::Malformed)?;
+    if token.imprint_algorithm != der::SHA512
+        || token.imprint != imprint
+        || token.nonce.map(|n| der::encode(der::INTEGER, n)) != Some(nonce)
+        || included_signer(&token).is_none()
+    {
+        return Err(TimestampError::BadToken);
+    }
+
+    let mut timestamped = message.clone();
+    timestamped.set_timestamp_token(Some(token_bytes.to_vec()));
+    Ok(timestamped)
+}
+
+/// Verify the timestamp token attached to `message`, which must be from one of the `trusted`
+/// authorities. This doesn't verify the message's signature; see `SignedMessage::verify`.
+pub fn verify(
+    message: &SignedMessage,
+    trusted: &[Certificate],
+) -> Result<Timestamp, TimestampError> {
+    verify_token(message, |token| {
+        trusted.iter().find(|c| token.is_signed_by(c)).cloned()
+    })
+}
+
+/// Like `verify`, but trusting the certificate that the authority included in the token, for
+/// showing a timestamp when the verifier hasn't chosen any authorities to trust. The timestamp is
+/// only as believable as the authority's name, since anyone can make a certificate with any name.
+pub fn verify_with_included_certificate(
+    message: &SignedMessage,
+) -> Result<Timestamp, TimestampError> {
+    verify_token(message, included_signer)
+}
+
+/// Verify `message`'s token, which must be signed by the certificate that `authority` finds.
+fn verify_token(
+    message: &SignedMessage,
+    authority: impl FnOnce(&Token) -> Option<Certificate>,
+) -> Result<Timestamp, TimestampError> {
+    let token_bytes = message
+        .timestamp_token()
+        .ok_or(TimestampError::NotTimestamped)?;
+    let token = Token::parse(token_bytes).ok_or(TimestampError::Malformed)?;
+    if token.imprint_algorithm != der::SHA512 || token.imprint != imprint(message) {
+        return Err(TimestampError::BadToken);
+    }
+    match authority(&token) {
+        Some(authority) if authority.can_timestamp(token.time) => Ok(Timestamp {
+            time: token.time,
+            authority: authority.name().to_string(),
+            serial_number: token.serial_number.to_vec(),
+        }),
+        Some(_) => Err(TimestampError::Untrusted),
+        None if included_signer(&token).is_some() => Err(TimestampError::Untrusted),
+        None => Err(TimestampError::BadToken),
+    }
+}
+
+/// The certificate included in `token` that signed it, if there is one.
+fn included_signer(token: &Token) -> Option<Certificate> {
+    token
+        .certificates
+        .iter()
+        .filter_map(|c| Certificate::from_der(c))
+        .find(|c| token.is_signed_by(c))
+}
+
+/// What the TSA timestamps: the SHA-512 hash of the message's Borsh encoding.
+fn imprint(message: &SignedMessage) -> Vec<u8> {
+    let encoding = borsh::to_vec(message).expect("Failed to serialize into unbounded buffer");
+    token::hash(der::SHA512, &encoding).expect("SHA-512 is supported")
+}
+
+/// POST a timestamp request to `url`, and return the body of the response.
+fn post(url: &str, request: &[u8]) -> Result<Vec<u8>, TimestampError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or(TimestampError::UnsupportedUrl)?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    if host.is_empty() || host.contains('@') || path.contains(char::is_whitespace) {
+        return Err(TimestampError::UnsupportedUrl);
+    }
+    let has_port = host
+        .rsplit_once(':')
+        .filter(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        .is_some();
+    let address = if has_port {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+    let address = address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or(TimestampError::Network)?;
+
+    let mut stream = TcpStream::connect_timeout(&address, NETWORK_TIMEOUT)
+        .map_err(|_| TimestampError::Network)?;
+    stream
+        .set_read_timeout(Some(NETWORK_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(NETWORK_TIMEOUT)))
+        .map_err(|_| TimestampError::Network)?;
+    // HTTP/1.0, so that the response isn't chunked, and ends when the connection does.
+    let mut http_request = format!(
+        "POST {path} HTTP/1.0\r\n\
+         Host: {host}\r\n\
+         Content-Type: application/timestamp-query\r\n\
+         Accept: application/timestamp-reply\r\n\
+         Content-Length: {}\r\n\
+         \r\n",
+        request.len()
+    )
+    .into_bytes();
+    http_request.extend_from_slice(request);
+    stream
+        .write_all(&http_request)
+        .map_err(|_| TimestampError::Network)?;
+    let mut response = vec![];
+    stream
+        .take(MAX_RESPONSE_LEN)
+        .read_to_end(&mut response)
+        .map_err(|_| TimestampError::Network)?;
+
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or(TimestampError::Network)?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let mut head_lines = head.split("\r\n");
+    let status = head_lines.next().unwrap_or_default();
+    if status.split(' ').nth(1) != Some("200") {
+        return Err(TimestampError::Rejected);
+    }
+    let mut body = response[header_end + 4..].to_vec();
+    for line in head_lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                let len = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| TimestampError::Malformed)?;
+                if body.len() < len {
+                    return Err(TimestampError::Network);
+                }
+                body.truncate(len);
+            }
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::str::FromStr;
+
+    use num_bigint::BigUint;
+    use zebra_crypto::{Identity, PrivateKey};
+
+    use super::*;
+    use der::{context, Reader};
+
+    const TSA_CERTIFICATE: &str = include_str!("../samples/tsa.pem");
+    const OTHER_TSA_CERTIFICATE: &str = include_str!("../samples/other_tsa.pem");
+    /// The test TSA's private key, as a DER-encoded PKCS #1 RSAPrivateKey.
+    const TSA_KEY: &[u8] = include_bytes!("../samples/tsa_key.der");
+
+    /// Start a stand-in TSA on a local port, which answers `requests` requests and then stops,
+    /// and return its URL. It rejects requests to /reject, and grants all others.
+    fn serve(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/tsa", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (serial, stream) in listener.incoming().take(requests).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                let (head, body) = loop {
+                    let n = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..n]);
+                    let head_end = request.windows(4).position(|w| w == b"\r\n\r\n");
+                    if let Some(head_end) = head_end {
+                        let head = String::from_utf8(request[..head_end].to_vec()).unwrap();
+                        let len = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("Content-Length: "))
+                            .unwrap()
+                            .parse::<usize>()
+                            .unwrap();
+                        if request.len() == head_end + 4 + len {
+                            break (head, &request[head_end + 4..]);
+                        }
+                    }
+                };
+                let response = if head.starts_with("POST /reject ") {
+                    // Status 2 is "rejection".
+                    der::encode_all(
+                        der::SEQUENCE,
+                        &[&der::encode_all(
+                            der::SEQUENCE,
+                            &[&der::encode(der::INTEGER, &[2])],
+                        )],
+                    )
+                } else {
+                    grant(body, serial as u8 + 1)
+                };
+                write!(
+                    stream,
+                    "HTTP/1.0 200 OK\r\n\
+                     Content-Type: application/timestamp-reply\r\n\
+                     Content-Length: {}\r\n\r\n",
+                    response.len()
+                )
+                .unwrap();
+                stream.write_all(&response).unwrap();
+            }
+        });
+        url
+    }
+
+    /// The test TSA's modulus and private exponent.
+    fn tsa_key() -> (BigUint, BigUint) {
+        let mut key = Reader::new(TSA_KEY).nested(der::SEQUENCE).unwrap();
+        key.read(der::INTEGER).unwrap(); // version
+        let modulus = BigUint::from_bytes_be(key.read_unsigned().unwrap());
+        key.read_unsigned().unwrap(); // public exponent
+        let private_exponent = BigUint::from_bytes_be(key.read_unsigned().unwrap());
+        (modulus, private_exponent)
+    }
+
+    /// The test TSA's certificate, with the `index`th field of the part that its issuer signs
+    /// replaced (or removed, if `replacement` is None). The fields are the version, serial
+    /// number, signature algorithm, issuer, validity, subject, public key, and extensions.
+    fn modified_certificate(index: usize, replacement: Option<Vec<u8>>) -> Vec<u8> {
+        let original = Certificate::from_str(TSA_CERTIFICATE).unwrap();
+        let mut certificate = Reader::new(original.der()).nested(der::SEQUENCE).unwrap();
+        let mut tbs = certificate.nested(der::SEQUENCE).unwrap();
+        let mut fields = vec![];
+        while !tbs.is_empty() {
+            fields.push(tbs.read_any().unwrap().2.to_vec());
+        }
+        match replacement {
+            Some(replacement) => fields[index] = replacement,
+            None => drop(fields.remove(index)),
+        }
+        let (_, _, signature_algorithm) = certificate.read_any().unwrap();
+        let (_, _, signature) = certificate.read_any().unwrap();
+        der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::SEQUENCE, &fields.concat()),
+                signature_algorithm,
+                signature,
+            ],
+        )
+    }
+
+    /// A token with the same contents as `token`, but with `copies` copies of its SignerInfo.
+    fn with_signer_infos(token: &[u8], copies: usize) -> Vec<u8> {
+        let mut content_info = Reader::new(token).nested(der::SEQUENCE).unwrap();
+        let content_type = content_info.read(der::OID).unwrap();
+        let mut signed_data = content_info
+            .nested(context(0))
+            .unwrap()
+            .nested(der::SEQUENCE)
+            .unwrap();
+        let mut fields = vec![];
+        while !signed_data.is_empty() {
+            let (tag, contents, field) = signed_data.read_any().unwrap();
+            // The SignerInfos are last.
+            fields.push(if signed_data.is_empty() {
+                der::encode(tag, &contents.repeat(copies))
+            } else {
+                field.to_vec()
+            });
+        }
+        der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::OID, content_type),
+                &der::encode_all(context(0), &[&der::encode(der::SEQUENCE, &fields.concat())]),
+            ],
+        )
+    }
+
+    /// Grant a DER-encoded timestamp request, returning the DER-encoded response, as a TSA would.
+    fn grant(request: &[u8], serial: u8) -> Vec<u8> {
+        let mut request = Reader::new(request).nested(der::SEQUENCE).unwrap();
+        request.read(der::INTEGER).unwrap();
+        let (_, imprint) = request.read_element(der::SEQUENCE).unwrap();
+        let nonce = der::encode(der::INTEGER, request.read(der::INTEGER).unwrap());
+        let now = OffsetDateTime::now_utc();
+        let time = format!(
+            "{:04}{:02}{:02}{:02}{:02}{:02}Z",
+            now.year(),
+            now.month() as u8,
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let tst_info = der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::INTEGER, &[1]),
+                // The policy, 1.2.3.4.
+                &der::encode(der::OID, &[0x2a, 0x03, 0x04]),
+                imprint,
+                &der::encode(der::INTEGER, &[serial]),
+                &der::encode(der::GENERALIZED_TIME, time.as_bytes()),
+                &nonce,
+            ],
+        );
+        let attributes = [
+            der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode(der::OID, der::CONTENT_TYPE),
+                    &der::encode_all(der::SET, &[&der::encode(der::OID, der::TST_INFO)]),
+                ],
+            ),
+            der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode(der::OID, der::MESSAGE_DIGEST),
+                    &der::encode_all(
+                        der::SET,
+                        &[&der::encode(
+                            der::OCTET_STRING,
+                            &token::hash(der::SHA256, &tst_info).unwrap(),
+                        )],
+                    ),
+                ],
+            ),
+        ]
+        .concat();
+
+        let (modulus, private_exponent) = tsa_key();
+        let len = ((modulus.bits() + 7) / 8) as usize;
+        let digest = token::hash(der::SHA256, &der::encode(der::SET, &attributes)).unwrap();
+        let encoded = token::pkcs1_encoding(der::SHA256, &digest, len).unwrap();
+        let signature = BigUint::from_bytes_be(&encoded)
+            .modpow(&private_exponent, &modulus)
+            .to_bytes_be();
+        let signature = [vec![0; len - signature.len()], signature].concat();
+
+        let signer_info = der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::INTEGER, &[1]),
+                // The issuer and serial number of the TSA's certificate, which aren't checked.
+                &der::encode_all(
+                    der::SEQUENCE,
+                    &[
+                        &der::encode(der::SEQUENCE, &[]),
+                        &der::encode(der::INTEGER, &[1]),
+                    ],
+                ),
+                &der::encode_algorithm(der::SHA256),
+                &der::encode(context(0), &attributes),
+                &der::encode_algorithm(der::SHA256_WITH_RSA),
+                &der::encode(der::OCTET_STRING, &signature),
+            ],
+        );
+        let certificate = Certificate::from_str(TSA_CERTIFICATE).unwrap();
+        let signed_data = der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::INTEGER, &[3]),
+                &der::encode_all(der::SET, &[&der::encode_algorithm(der::SHA256)]),
+                &der::encode_all(
+                    der::SEQUENCE,
+                    &[
+                        &der::encode(der::OID, der::TST_INFO),
+                        &der::encode_all(context(0), &[&der::encode(der::OCTET_STRING, &tst_info)]),
+                    ],
+                ),
+                &der::encode(context(0), certificate.der()),
+                &der::encode_all(der::SET, &[&signer_info]),
+            ],
+        );
+        let token = der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::OID, der::SIGNED_DATA),
+                &der::encode_all(context(0), &[&signed_data]),
+            ],
+        );
+        der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode_all(der::SEQUENCE, &[&der::encode(der::INTEGER, &[0])]),
+                &token,
+            ],
+        )
+    }
+
+    fn keys() -> Vec<PrivateKey> {
+        [
+            ("Alice Example", "alice@example.com"),
+            ("Bob Example", "bob@example.com"),
+        ]
+        .iter()
+        .map(|(name, email)| PrivateKey::new(Identity::new(name, email).unwrap()))
+        .collect()
+    }
+
+    #[test]
+    fn timestamps_work() {
+        let url = serve(3);
+        let keys = keys();
+        let sign = || SignedMessage::sign("We accept the offer.", &keys[0], &[keys[1].public()]);
+        let signed = sign();
+        let trusted = [Certificate::from_str(TSA_CERTIFICATE).unwrap()];
+        assert_eq!(trusted[0].name(), "Zebra Test TSA");
+        assert_eq!(
+            verify(&signed, &trusted).err(),
+            Some(TimestampError::NotTimestamped)
+        );
+
+        // The TSA's clock only has whole seconds.
+        let before = OffsetDateTime::now_utc() - Duration::from_secs(1);
+        let timestamped = request_timestamp(&signed, &url).unwrap();
+        let timestamp = verify(&timestamped, &trusted).unwrap();
+        assert!(before <= timestamp.time() && timestamp.time() <= OffsetDateTime::now_utc());
+        assert_eq!(timestamp.authority(), "Zebra Test TSA");
+        assert_eq!(timestamp.serial_number(), [1]);
+
+        // The token survives the ASCII format, and doesn't affect the signature.
+        let parsed = SignedMessage::from_str(&String::from(&timestamped)).unwrap();
+        assert!(parsed.verify());
+        assert_eq!(verify(&parsed, &trusted).unwrap().serial_number(), [1]);
+
+        // Only the trusted authorities count.
+        let other = [Certificate::from_str(OTHER_TSA_CERTIFICATE).unwrap()];
+        assert_eq!(
+            verify(&timestamped, &other).err(),
+            Some(TimestampError::Untrusted)
+        );
+
+        // The token can't be moved to another signature of the same message, or be changed.
+        let resigned = sign();
+        let mut moved = resigned.clone();
+        moved.set_timestamp_token(timestamped.timestamp_token().map(<[u8]>::to_vec));
+        assert_eq!(
+            verify(&moved, &trusted).err(),
+            Some(TimestampError::BadToken)
+        );
+        let mut token = timestamped.timestamp_token().unwrap().to_vec();
+        // The last byte is part of the TSA's signature.
+        *token.last_mut().unwrap() ^= 1;
+        let mut tampered = timestamped.clone();
+        tampered.set_timestamp_token(Some(token));
+        assert_eq!(
+            verify(&tampered, &trusted).err(),
+            Some(TimestampError::BadToken)
+        );
+        let timestamped_again = request_timestamp(&resigned, &url).unwrap();
+        assert_eq!(
+            verify(&timestamped_again, &trusted)
+                .unwrap()
+                .serial_number(),
+            [2]
+        );
+
+        assert_eq!(
+            request_timestamp(&signed, &url.replace("/tsa", "/reject")).err(),
+            Some(TimestampError::Rejected)
+        );
+        assert_eq!(
+            request_timestamp(&signed, "https://timestamp.example.com/").err(),
+            Some(TimestampError::UnsupportedUrl)
+        );
+    }
+
+    #[test]
+    fn unsuitable_certificates_are_rejected() {
+        let url = serve(1);
+        let keys = keys();
+        let signed = SignedMessage::sign("We accept the offer.", &keys[0], &[keys[1].public()]);
+        let timestamped = request_timestamp(&signed, &url).unwrap();
+        let original = Certificate::from_str(TSA_CERTIFICATE).unwrap();
+
+        // The certificates below all have the TSA's key, but shouldn't be trusted with it.
+        let usages = |usage: &[u8]| {
+            let usages = der::encode_all(der::SEQUENCE, &[&der::encode(der::OID, usage)]);
+            let extension = der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode(der::OID, der::EXTENDED_KEY_USAGE),
+                    &der::encode(der::OCTET_STRING, &usages),
+                ],
+            );
+            der::encode_all(context(3), &[&der::encode(der::SEQUENCE, &extension)])
+        };
+        assert!(modified_certificate(7, Some(usages(der::TIME_STAMPING))) != original.der());
+        assert!(verify(
+            &timestamped,
+            &[
+                Certificate::from_der(&modified_certificate(7, Some(usages(der::TIME_STAMPING))))
+                    .unwrap()
+            ]
+        )
+        .is_ok());
+        // TLS server authentication, 1.3.6.1.5.5.7.3.1.
+        let server_auth = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+        let expired = der::encode_all(
+            der::SEQUENCE,
+            &[
+                &der::encode(der::UTC_TIME, b"200101000000Z"),
+                &der::encode(der::UTC_TIME, b"210101000000Z"),
+            ],
+        );
+        for certificate in [
+            modified_certificate(7, Some(usages(&server_auth))),
+            modified_certificate(7, None),
+            modified_certificate(4, Some(expired)),
+        ] {
+            let certificate = Certificate::from_der(&certificate).unwrap();
+            assert_eq!(
+                verify(&timestamped, &[certificate]).err(),
+                Some(TimestampError::Untrusted)
+            );
+        }
+
+        // Keys that are too easy to forge signatures for aren't accepted at all. (With an exponent
+        // of 1, the padded digest is its own signature.)
+        let modulus = tsa_key().0.to_bytes_be();
+        let key_info = |modulus: &[u8], exponent: &[u8]| {
+            let key = der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode_unsigned(modulus),
+                    &der::encode_unsigned(exponent),
+                ],
+            );
+            der::encode_all(
+                der::SEQUENCE,
+                &[
+                    &der::encode_algorithm(der::RSA_ENCRYPTION),
+                    &der::encode(der::BIT_STRING, &[&[0], key.as_slice()].concat()),
+                ],
+            )
+        };
+        assert!(Certificate::from_der(&modified_certificate(
+            6,
+            Some(key_info(&modulus, &[1, 0, 1]))
+        ))
+        .is_some());
+        for (modulus, exponent) in [
+            (&modulus[..], &[1][..]),
+            (&modulus[..], &[4, 0, 0, 0]),
+            (&modulus[..128], &[1, 0, 1]),
+        ] {
+            assert!(Certificate::from_der(&modified_certificate(
+                6,
+                Some(key_info(modulus, exponent))
+            ))
+            .is_none());
+        }
+
+        let mut trailing = original.der().to_vec();
+        trailing.push(0);
+        assert!(Certificate::from_der(&trailing).is_none());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let url = serve(1);
+        let keys = keys();
+        let signed = SignedMessage::sign("We accept the offer.", &keys[0], &[keys[1].public()]);
+        let timestamped = request_timestamp(&signed, &url).unwrap();
+        let trusted = [Certificate::from_str(TSA_CERTIFICATE).unwrap()];
+        let token = timestamped.timestamp_token().unwrap();
+        let with_token = |token: Vec<u8>| {
+            let mut message = timestamped.clone();
+            message.set_timestamp_token(Some(token));
+            verify(&message, &trusted).err()
+        };
+
+        assert_eq!(with_signer_infos(token, 1), token);
+        assert_eq!(
+            with_token(with_signer_infos(token, 2)),
+            Some(TimestampError::Malformed)
+        );
+        assert_eq!(
+            with_token([token, &[0]].concat()),
+            Some(TimestampError::Malformed)
+        );
+        assert_eq!(
+            with_token(token[..token.len() - 1].to_vec()),
+            Some(TimestampError::Malformed)
+        );
+
+        // Without trusted authorities, the included certificate can still say who it's from.
+        let timestamp = verify_with_included_certificate(&timestamped).unwrap();
+        assert_eq!(timestamp.authority(), "Zebra Test TSA");
+    }
+
+    #[test]
+    fn openssl_timestamps_verify() {
+        // Timestamped by `openssl ts -reply`, with the same key and certificate as the stand-in TSA,
+        // to check that we agree with another implementation.
+        let timestamped =
+            SignedMessage::from_str(include_str!("../samples/timestamped.txt")).unwrap();
+        assert!(timestamped.verify());
+        let trusted = [Certificate::from_str(TSA_CERTIFICATE).unwrap()];
+        let timestamp = verify(&timestamped, &trusted).unwrap();
+        // 2026-10-18 17:47:41 UTC.
+        assert_eq!(timestamp.time().unix_timestamp(), 1792345661);
+        assert_eq!(timestamp.authority(), "Zebra Test TSA");
+        assert_eq!(timestamp.serial_number(), [0x2b]);
+    }
+}
@@ -0,0 +1,438 @@
+use std::str::FromStr;
+
+use base64::Engine;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::der::{self, context, Reader};
+
+// A timestamp token is a CMS (RFC 5652) ContentInfo, holding SignedData whose content is a
+// TSTInfo (RFC 3161, section 2.4.2). The TSTInfo has the hash of what was timestamped (its
+// "message imprint"), and the time. The SignedData's single SignerInfo has signed attributes,
+// which include a hash of the TSTInfo, and the TSA's signature of those attributes. The TSA's
+// certificate is usually included too, though we only use it when we haven't been told which
+// authorities to trust (when checking a token that we've just been sent).
+//
+// Only RSA (PKCS #1 v1.5) signatures, with SHA-256, SHA-384 or SHA-512, are supported, which is
+// what public TSAs use.
+//
+// This is all parsed and checked here, rather than with the `cms`, `x509-cert` and `rsa` crates,
+// because we need so little of them: no certificate chains, revocation, or other algorithms, and
+// no private key operations. Between them, they would more than double the dependencies of the
+// app, for code that would mostly go unused. What's here only has to handle public data, so it
+// doesn't need to be constant-time, and it's strict: anything that isn't DER (see `der`), has
+// extra data anywhere that it isn't allowed, or has a weak key is rejected, rather than
+// interpreted.
+
+// RSA keys with a smaller modulus than this are rejected, as NIST has recommended since 2013.
+const MIN_MODULUS_BITS: u64 = 2048;
+
+/// A timestamping authority's X.509 certificate. Only certificates with RSA keys are supported.
+#[derive(Clone)]
+pub struct Certificate {
+    der: Vec<u8>,
+    name: String,
+    modulus: BigUint,
+    exponent: BigUint,
+    not_before: OffsetDateTime,
+    not_after: OffsetDateTime,
+    // Whether its extended key usage includes timestamping, as RFC 3161 requires.
+    for_timestamping: bool,
+}
+
+impl Certificate {
+    /// Parse a DER-encoded certificate. Returns None if it isn't one, or if its key isn't RSA.
+    pub fn from_der(bytes: &[u8]) -> Option<Certificate> {
+        let mut outer = Reader::new(bytes);
+        let mut certificate = outer.nested(der::SEQUENCE)?;
+        let mut tbs = certificate.nested(der::SEQUENCE)?;
+        // The issuer's signature isn't checked: certificates are trusted by being pinned.
+        certificate.read(der::SEQUENCE)?; // signature algorithm
+        certificate.read(der::BIT_STRING)?; // signature
+        if !outer.is_empty() || !certificate.is_empty() {
+            return None;
+        }
+        tbs.read_optional(context(0))?; // version
+        tbs.read(der::INTEGER)?; // serial number
+        tbs.read(der::SEQUENCE)?; // signature algorithm
+        tbs.read(der::SEQUENCE)?; // issuer
+        let mut validity = tbs.nested(der::SEQUENCE)?;
+        let not_before = read_time(&mut validity)?;
+        let not_after = read_time(&mut validity)?;
+        let subject = tbs.read(der::SEQUENCE)?;
+        let mut key_info = tbs.nested(der::SEQUENCE)?;
+        if key_info.read_algorithm()? != der::RSA_ENCRYPTION {
+            return None;
+        }
+        let key_bits = match key_info.read(der::BIT_STRING)? {
+            [0, key_bits @ ..] => key_bits,
+            _ => return None,
+        };
+        let mut key = Reader::new(key_bits).nested(der::SEQUENCE)?;
+        let modulus = BigUint::from_bytes_be(key.read_unsigned()?);
+        let exponent = BigUint::from_bytes_be(key.read_unsigned()?);
+        // An exponent of 1 (or an even one) would let anyone forge signatures, and small moduli
+        // can be factored.
+        if !key.is_empty()
+            || modulus.bits() < MIN_MODULUS_BITS
+            || exponent < BigUint::from(3u32)
+            || !exponent.bit(0)
+        {
+            return None;
+        }
+        tbs.read_optional(0x81)?; // issuer unique ID
+        tbs.read_optional(0x82)?; // subject unique ID
+        let for_timestamping = match tbs.read_optional(context(3))? {
+            Some(extensions) => has_timestamping_usage(extensions)?,
+            None => false,
+        };
+        if !tbs.is_empty() || !key_info.is_empty() {
+            return None;
+        }
+
+        Some(Certificate {
+            der: bytes.to_vec(),
+            name: common_name(subject).unwrap_or_default(),
+            modulus,
+            exponent,
+            not_before,
+            not_after,
+            for_timestamping,
+        })
+    }
+
+    /// The certificate's DER encoding.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The common name of the certificate's subject (e.g. "DigiCert SHA256 RSA4096 Timestamp
+    /// Responder 2025 1"), or "" if it doesn't have one.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether this certificate is allowed to sign a timestamp of `time`.
+    pub(crate) fn can_timestamp(&self, time: OffsetDateTime) -> bool {
+        self.for_timestamping && self.not_before <= time && time <= self.not_after
+    }
+}
+
+impl FromStr for Certificate {
+    type Err = ();
+    /// Parse a PEM-encoded certificate ("-----BEGIN CERTIFICATE-----" and so on).
+    fn from_str(s: &str) -> Result<Certificate, ()> {
+        let body = s
+            .trim()
+            .strip_prefix("-----BEGIN CERTIFICATE-----")
+            .and_then(|s| s.strip_suffix("-----END CERTIFICATE-----"))
+            .ok_or(())?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(body.split_whitespace().collect::<String>())
+            .map_err(|_| ())?;
+        Certificate::from_der(&bytes).ok_or(())
+    }
+}
+
+/// The parts of a timestamp token that we check.
+pub(crate) struct Token<'a> {
+    /// The OID of the hash in the message imprint.
+    pub(crate) imprint_algorithm: &'a [u8],
+    pub(crate) imprint: &'a [u8],
+    pub(crate) serial_number: &'a [u8],
+    pub(crate) time: OffsetDateTime,
+    /// The nonce's INTEGER contents, if the request had one.
+    pub(crate) nonce: Option<&'a [u8]>,
+    /// The DER encodings of the certificates included in the token.
+    pub(crate) certificates: Vec<&'a [u8]>,
+    // What the TSA signed (the signed attributes, as a SET), the OID of the hash it signed it with,
+    // and the signature.
+    signed: Vec<u8>,
+    signature_hash: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> Token<'a> {
+    /// Parse a DER-encoded token. Besides checking its structure, this checks that the signed
+    /// attributes match the TSTInfo, but not the signature itself; see `is_signed_by`.
+    pub(crate) fn parse(bytes: &'a [u8]) -> Option<Token<'a>> {
+        let mut outer = Reader::new(bytes);
+        let mut content_info = outer.nested(der::SEQUENCE)?;
+        if !outer.is_empty() || content_info.read(der::OID)? != der::SIGNED_DATA {
+            return None;
+        }
+        let mut content = content_info.nested(context(0))?;
+        let mut signed_data = content.nested(der::SEQUENCE)?;
+        if !content.is_empty() || !content_info.is_empty() {
+            return None;
+        }
+        signed_data.read(der::INTEGER)?; // version
+        signed_data.read(der::SET)?; // digest algorithms
+        let mut content = signed_data.nested(der::SEQUENCE)?;
+        if content.read(der::OID)? != der::TST_INFO {
+            return None;
+        }
+        let mut tst_info_octets = content.nested(context(0))?;
+        let tst_info = tst_info_octets.read(der::OCTET_STRING)?;
+        if !tst_info_octets.is_empty() || !content.is_empty() {
+            return None;
+        }
+        let mut certificates = vec![];
+        if let Some(included) = signed_data.read_optional(context(0))? {
+            let mut included = Reader::new(included);
+            while !included.is_empty() {
+                // Other kinds of certificate (like attribute certificates) have other tags.
+                let (tag, _, certificate) = included.read_any()?;
+                if tag == der::SEQUENCE {
+                    certificates.push(certificate);
+                }
+            }
+        }
+        signed_data.read_optional(context(1))?; // CRLs
+
+        // Exactly one SignerInfo: a token that's signed by several keys could be accepted because
+        // of one signature, but shown as being from the authority of another.
+        let mut signer_infos = signed_data.nested(der::SET)?;
+        let mut signer_info = signer_infos.nested(der::SEQUENCE)?;
+        if !signer_infos.is_empty() || !signed_data.is_empty() {
+            return None;
+        }
+        signer_info.read(der::INTEGER)?; // version
+        signer_info.read_any()?; // signer identifier
+        let digest_algorithm = signer_info.read_algorithm()?;
+        let attributes = signer_info.read(context(0))?;
+        let signature_algorithm = signer_info.read_algorithm()?;
+        let signature = signer_info.read(der::OCTET_STRING)?;
+        signer_info.read_optional(context(1))?; // unsigned attributes
+        if !signer_info.is_empty() {
+            return None;
+        }
+        let signature_hash = match signature_algorithm {
+            der::RSA_ENCRYPTION => digest_algorithm,
+            der::SHA256_WITH_RSA => der::SHA256,
+            der::SHA384_WITH_RSA => der::SHA384,
+            der::SHA512_WITH_RSA => der::SHA512,
+            _ => return None,
+        };
+
+        // The signed attributes must say what the content is, and have its hash.
+        let mut content_type = None;
+        let mut message_digest = None;
+        let mut attribute_reader = Reader::new(attributes);
+        while !attribute_reader.is_empty() {
+            let mut attribute = attribute_reader.nested(der::SEQUENCE)?;
+            let oid = attribute.read(der::OID)?;
+            let mut values = attribute.nested(der::SET)?;
+            // Each must appear once, with a single value.
+            let duplicate = if oid == der::CONTENT_TYPE {
+                content_type.replace(values.read(der::OID)?).is_some()
+            } else if oid == der::MESSAGE_DIGEST {
+                message_digest
+                    .replace(values.read(der::OCTET_STRING)?)
+                    .is_some()
+            } else {
+                continue;
+            };
+            if duplicate || !values.is_empty() || !attribute.is_empty() {
+                return None;
+            }
+        }
+        if content_type != Some(der::TST_INFO)
+            || message_digest != Some(hash(digest_algorithm, tst_info)?.as_slice())
+        {
+            return None;
+        }
+
+        let mut tst_info = Reader::new(tst_info).nested(der::SEQUENCE)?;
+        if tst_info.read_unsigned()? != [1] {
+            return None;
+        }
+        tst_info.read(der::OID)?; // policy
+        let mut message_imprint = tst_info.nested(der::SEQUENCE)?;
+        let imprint_algorithm = message_imprint.read_algorithm()?;
+        let imprint = message_imprint.read(der::OCTET_STRING)?;
+        if !message_imprint.is_empty() {
+            return None;
+        }
+        let serial_number = tst_info.read_unsigned()?;
+        let time = std::str::from_utf8(tst_info.read(der::GENERALIZED_TIME)?).ok()?;
+        let time = parse_generalized_time(time)?;
+        tst_info.read_optional(der::SEQUENCE)?; // accuracy
+        tst_info.read_optional(der::BOOLEAN)?; // ordering
+        let nonce = tst_info.read_optional(der::INTEGER)?;
+        tst_info.read_optional(context(0))?; // the TSA's name
+        tst_info.read_optional(context(1))?; // extensions
+        if !tst_info.is_empty() {
+            return None;
+        }
+
+        Some(Token {
+            imprint_algorithm,
+            imprint,
+            serial_number,
+            time,
+            nonce,
+            certificates,
+            signed: der::encode(der::SET, attributes),
+            signature_hash,
+            signature,
+        })
+    }
+
+    /// Whether the token's signature was made with `certificate`'s key.
+    pub(crate) fn is_signed_by(&self, certificate: &Certificate) -> bool {
+        let Some(digest) = hash(self.signature_hash, &self.signed) else {
+            return false;
+        };
+        verify_rsa(certificate, self.signature_hash, &digest, self.signature)
+    }
+}
+
+/// Hash `data` with the hash that has the given OID (if it's one that we support).
+pub(crate) fn hash(oid: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    match oid {
+        der::SHA256 => Some(Sha256::digest(data).to_vec()),
+        der::SHA384 => Some(Sha384::digest(data).to_vec()),
+        der::SHA512 => Some(Sha512::digest(data).to_vec()),
+        _ => None,
+    }
+}
+
+/// The PKCS #1 v1.5 encoding of a `digest` (made with the hash `digest_oid`) for a key with a
+/// `len`-byte modulus, which is what the key signs.
+pub(crate) fn pkcs1_encoding(digest_oid: &[u8], digest: &[u8], len: usize) -> Option<Vec<u8>> {
+    let digest_info = der::encode_all(
+        der::SEQUENCE,
+        &[
+            &der::encode_algorithm(digest_oid),
+            &der::encode(der::OCTET_STRING, digest),
+        ],
+    );
+    // At least 8 bytes of padding.
+    let padding = len.checked_sub(digest_info.len() + 3).filter(|&p| p >= 8)?;
+    let mut encoded = vec![0, 1];
+    encoded.resize(padding + 2, 0xff);
+    encoded.push(0);
+    encoded.extend_from_slice(&digest_info);
+    Some(encoded)
+}
+
+fn verify_rsa(
+    certificate: &Certificate,
+    digest_oid: &[u8],
+    digest: &[u8],
+    signature: &[u8],
+) -> bool {
+    let len = ((certificate.modulus.bits() + 7) / 8) as usize;
+    let signature = BigUint::from_bytes_be(signature);
+    if signature >= certificate.modulus {
+        return false;
+    }
+    let Some(expected) = pkcs1_encoding(digest_oid, digest, len) else {
+        return false;
+    };
+    let encoded = signature
+        .modpow(&certificate.exponent, &certificate.modulus)
+        .to_bytes_be();
+    // `encoded` has no leading zeros, and `expected` always has exactly one.
+    expected[1..] == encoded[..]
+}
+
+/// Whether a certificate's extensions include an extended key usage for timestamping. Returns None
+/// if they're malformed.
+fn has_timestamping_usage(extensions: &[u8]) -> Option<bool> {
+    let mut extensions = Reader::new(extensions).nested(der::SEQUENCE)?;
+    while !extensions.is_empty() {
+        let mut extension = extensions.nested(der::SEQUENCE)?;
+        let oid = extension.read(der::OID)?;
+        extension.read_optional(der::BOOLEAN)?; // critical
+        let value = extension.read(der::OCTET_STRING)?;
+        if oid == der::EXTENDED_KEY_USAGE {
+            let mut usages = Reader::new(value).nested(der::SEQUENCE)?;
+            while !usages.is_empty() {
+                if usages.read(der::OID)? == der::TIME_STAMPING {
+                    return Some(true);
+                }
+            }
+        }
+    }
+    Some(false)
+}
+
+/// The first common name in an X.509 Name, if it has one.
+fn common_name(name: &[u8]) -> Option<String> {
+    let mut name = Reader::new(name);
+    while !name.is_empty() {
+        let mut attributes = name.nested(der::SET)?;
+        while !attributes.is_empty() {
+            let mut attribute = attributes.nested(der::SEQUENCE)?;
+            if attribute.read(der::OID)? == der::COMMON_NAME {
+                // Whichever string type it is, it's some subset of UTF-8 (or, for the obsolete
+                // BMPString and UniversalString, won't usually be valid UTF-8).
+                let (_, value, _) = attribute.read_any()?;
+                return String::from_utf8(value.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+/// A UTCTime or GeneralizedTime, as in an X.509 certificate's validity.
+fn read_time(reader: &mut Reader) -> Option<OffsetDateTime> {
+    let (tag, contents, _) = reader.read_any()?;
+    let s = std::str::from_utf8(contents).ok()?;
+    match tag {
+        der::UTC_TIME => {
+            // Two-digit years are from 1950 to 2049 (RFC 5280, section 4.1.2.5.1).
+            let year = digits(s.get(..2)?)?;
+            let century = if year < 50 { "20" } else { "19" };
+            parse_generalized_time(&format!("{century}{s}"))
+        }
+        der::GENERALIZED_TIME => parse_generalized_time(s),
+        _ => None,
+    }
+}
+
+/// A GeneralizedTime in DER's form: "YYYYMMDDHHMMSS", then maybe a fraction of a second, then "Z".
+fn parse_generalized_time(s: &str) -> Option<OffsetDateTime> {
+    let s = s.strip_suffix('Z')?;
+    let (whole, fraction) = match s.split_once('.') {
+        // DER doesn't allow trailing zeros, or an empty fraction.
+        Some((whole, fraction)) if !fraction.is_empty() && !fraction.ends_with('0') => {
+            (whole, fraction)
+        }
+        Some(_) => return None,
+        None => (s, ""),
+    };
+    if whole.len() != 14 || !whole.is_ascii() || fraction.len() > 9 {
+        return None;
+    }
+    let date = Date::from_calendar_date(
+        digits(&whole[0..4])? as i32,
+        Month::try_from(digits(&whole[4..6])? as u8).ok()?,
+        digits(&whole[6..8])? as u8,
+    )
+    .ok()?;
+    let nanoseconds = if fraction.is_empty() {
+        0
+    } else {
+        digits(fraction)? * 10u32.pow(9 - fraction.len() as u32)
+    };
+    let time = Time::from_hms_nano(
+        digits(&whole[8..10])? as u8,
+        digits(&whole[10..12])? as u8,
+        digits(&whole[12..14])? as u8,
+        nanoseconds,
+    )
+    .ok()?;
+    Some(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+/// A string of ASCII digits, as a number.
+fn digits(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}